@@ -0,0 +1,146 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, LitStr};
+
+/// Derives `ms_nrbf::NrbfClass` and `ms_nrbf::NrbfField` for a struct with named fields.
+///
+/// Members are written in declaration order. The .NET type is set with
+/// `#[nrbf(type = "Game.Player", assembly = "Game, Version=1.0.0.0")]`; without an assembly the
+/// class is treated as a system class. Member names default to the Rust field names and can be
+/// changed with `#[nrbf(rename = "<Name>k__BackingField")]`. Converting from a class with a
+/// different type name fails with `ConvertError::TypeMismatch`.
+#[proc_macro_derive(NrbfClass, attributes(nrbf))]
+pub fn derive_nrbf_class(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let mut type_name = ident.to_string();
+    let mut assembly = None;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("nrbf")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type") {
+                type_name = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("assembly") {
+                assembly = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else {
+                return Err(meta.error("expected `type` or `assembly`"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "NrbfClass can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "NrbfClass can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut field_idents = vec![];
+    let mut member_names = vec![];
+
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let mut member_name = field_ident.to_string();
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("nrbf")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    member_name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename`"))
+                }
+            })?;
+        }
+
+        field_idents.push(field_ident);
+        member_names.push(member_name);
+    }
+
+    let library_name = match assembly {
+        Some(assembly) => quote!(::std::option::Option::Some(#assembly.to_string())),
+        None => quote!(::std::option::Option::None),
+    };
+    // Type parameters are member types, so each one has to convert to and from a field.
+    let mut generics = input.generics.clone();
+
+    for param in input.generics.type_params() {
+        let param = &param.ident;
+
+        generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#param: ::ms_nrbf::NrbfField));
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::ms_nrbf::NrbfClass for #ident #ty_generics #where_clause {
+            fn into_class(self) -> ::ms_nrbf::Class {
+                let mut class = ::ms_nrbf::Class::new(#library_name, #type_name);
+                #(
+                    class.fields.insert(
+                        #member_names.to_string(),
+                        ::ms_nrbf::NrbfField::into_field(self.#field_idents),
+                    );
+                )*
+                class
+            }
+
+            fn from_class(
+                mut class: ::ms_nrbf::Class,
+            ) -> ::std::result::Result<Self, ::ms_nrbf::ConvertError> {
+                if class.name != #type_name {
+                    return ::std::result::Result::Err(::ms_nrbf::ConvertError::TypeMismatch {
+                        expected: #type_name,
+                        found: class.name,
+                    });
+                }
+
+                ::std::result::Result::Ok(Self {
+                    #(
+                        #field_idents: ::ms_nrbf::NrbfField::from_field(
+                            class.fields.swap_remove(#member_names).ok_or_else(|| {
+                                ::ms_nrbf::ConvertError::MissingMember(#member_names.to_string())
+                            })?,
+                        )?,
+                    )*
+                })
+            }
+        }
+
+        impl #impl_generics ::ms_nrbf::NrbfField for #ident #ty_generics #where_clause {
+            fn into_field(self) -> ::ms_nrbf::Field {
+                ::ms_nrbf::Field::Class(::ms_nrbf::NrbfClass::into_class(self))
+            }
+
+            fn from_field(
+                field: ::ms_nrbf::Field,
+            ) -> ::std::result::Result<Self, ::ms_nrbf::ConvertError> {
+                ::ms_nrbf::NrbfClass::from_class(::ms_nrbf::NrbfField::from_field(field)?)
+            }
+        }
+    })
+}
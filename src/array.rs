@@ -0,0 +1,370 @@
+use crate::{
+    enums::{Primitive, PrimitiveType},
+    io::{self, Read, Write},
+    parse::{ParseError, ParseTyped},
+    time::{DateTime, TimeSpan},
+    unparse::{Unparse, UnparseTo},
+};
+use alloc::{string::String, vec, vec::Vec};
+use core::mem::size_of;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PrimitiveArray {
+    Boolean(Vec<bool>),
+    Byte(Vec<u8>),
+    Char(Vec<char>),
+    Decimal(Vec<String>),
+    Double(Vec<f64>),
+    Int16(Vec<i16>),
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    SByte(Vec<i8>),
+    Single(Vec<f32>),
+    TimeSpan(Vec<TimeSpan>),
+    DateTime(Vec<DateTime>),
+    UInt16(Vec<u16>),
+    UInt32(Vec<u32>),
+    UInt64(Vec<u64>),
+    Null,
+    String(Vec<Option<String>>),
+}
+
+macro_rules! into_field {
+    ($primitive_type:ident, $array:expr) => {
+        PrimitiveArray::$primitive_type(
+            $array
+                .iter()
+                .map(|primitive| {
+                    if let Primitive::$primitive_type(value) = primitive {
+                        value.clone()
+                    } else {
+                        unreachable!()
+                    }
+                })
+                .collect(),
+        )
+    };
+}
+
+macro_rules! from_field {
+    ($primitive_type:ident, $value:expr) => {
+        $value
+            .iter()
+            .map(|value| Primitive::$primitive_type(value.clone()))
+            .collect()
+    };
+}
+
+impl PrimitiveArray {
+    pub fn get_type(&self) -> PrimitiveType {
+        match self {
+            Self::Boolean(_) => PrimitiveType::Boolean,
+            Self::Byte(_) => PrimitiveType::Byte,
+            Self::Char(_) => PrimitiveType::Char,
+            Self::Decimal(_) => PrimitiveType::Decimal,
+            Self::Double(_) => PrimitiveType::Double,
+            Self::Int16(_) => PrimitiveType::Int16,
+            Self::Int32(_) => PrimitiveType::Int32,
+            Self::Int64(_) => PrimitiveType::Int64,
+            Self::SByte(_) => PrimitiveType::SByte,
+            Self::Single(_) => PrimitiveType::Single,
+            Self::TimeSpan(_) => PrimitiveType::TimeSpan,
+            Self::DateTime(_) => PrimitiveType::DateTime,
+            Self::UInt16(_) => PrimitiveType::UInt16,
+            Self::UInt32(_) => PrimitiveType::UInt32,
+            Self::UInt64(_) => PrimitiveType::UInt64,
+            Self::Null => PrimitiveType::Null,
+            Self::String(_) => PrimitiveType::String,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Boolean(values) => values.len(),
+            Self::Byte(values) => values.len(),
+            Self::Char(values) => values.len(),
+            Self::Decimal(values) => values.len(),
+            Self::Double(values) => values.len(),
+            Self::Int16(values) => values.len(),
+            Self::Int32(values) => values.len(),
+            Self::Int64(values) => values.len(),
+            Self::SByte(values) => values.len(),
+            Self::Single(values) => values.len(),
+            Self::TimeSpan(values) => values.len(),
+            Self::DateTime(values) => values.len(),
+            Self::UInt16(values) => values.len(),
+            Self::UInt32(values) => values.len(),
+            Self::UInt64(values) => values.len(),
+            Self::Null => 0,
+            Self::String(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn into_field(array: Vec<Primitive>, primitive_type: PrimitiveType) -> Self {
+        match primitive_type {
+            PrimitiveType::Boolean => into_field!(Boolean, array),
+            PrimitiveType::Byte => into_field!(Byte, array),
+            PrimitiveType::Char => into_field!(Char, array),
+            PrimitiveType::Decimal => into_field!(Decimal, array),
+            PrimitiveType::Double => into_field!(Double, array),
+            PrimitiveType::Int16 => into_field!(Int16, array),
+            PrimitiveType::Int32 => into_field!(Int32, array),
+            PrimitiveType::Int64 => into_field!(Int64, array),
+            PrimitiveType::SByte => into_field!(SByte, array),
+            PrimitiveType::Single => into_field!(Single, array),
+            PrimitiveType::TimeSpan => into_field!(TimeSpan, array),
+            PrimitiveType::DateTime => into_field!(DateTime, array),
+            PrimitiveType::UInt16 => into_field!(UInt16, array),
+            PrimitiveType::UInt32 => into_field!(UInt32, array),
+            PrimitiveType::UInt64 => into_field!(UInt64, array),
+            PrimitiveType::Null => Self::Null,
+            PrimitiveType::String => Self::String(
+                array
+                    .into_iter()
+                    .map(|primitive| match primitive {
+                        Primitive::String(value) => Some(value),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// Expands to a match that replaces an element when the value has the array's element type.
+#[cfg(feature = "std")]
+macro_rules! replace_element {
+    ($array:expr, $index:expr, $value:expr, $($primitive_type:ident),*) => {
+        match ($array, $value) {
+            $((PrimitiveArray::$primitive_type(values), Primitive::$primitive_type(value)) => {
+                Some(Primitive::$primitive_type(core::mem::replace(
+                    values.get_mut($index)?,
+                    value,
+                )))
+            })*
+            (PrimitiveArray::String(values), Primitive::String(value)) => {
+                let old = values.get_mut($index)?.replace(value);
+                Some(old.map_or(Primitive::Null, Primitive::String))
+            }
+            (PrimitiveArray::String(values), Primitive::Null) => {
+                let old = values.get_mut($index)?.take();
+                Some(old.map_or(Primitive::Null, Primitive::String))
+            }
+            _ => None,
+        }
+    };
+}
+
+macro_rules! from_le_bytes {
+    ($primitive_type:ident, $type:ty, $bytes:expr) => {
+        PrimitiveArray::$primitive_type(
+            $bytes
+                .chunks_exact(size_of::<$type>())
+                .map(|chunk| <$type>::from_le_bytes(chunk.try_into().unwrap()))
+                .collect(),
+        )
+    };
+}
+
+// Converts a few thousand elements at a time so that large arrays are not copied whole.
+macro_rules! write_le_bytes {
+    ($writer:expr, $values:expr) => {{
+        for chunk in $values.chunks(4096) {
+            let bytes: Vec<u8> = chunk.iter().flat_map(|value| value.to_le_bytes()).collect();
+            $writer.write_all(&bytes)?;
+        }
+
+        Ok(())
+    }};
+}
+
+impl PrimitiveArray {
+    const READ_CHUNK: usize = 1 << 24;
+
+    // Arrays of fixed-width types are stored as one contiguous run of little-endian values, so
+    // they can be read and written in bulk instead of element by element.
+    pub(crate) fn element_size(primitive_type: PrimitiveType) -> Option<usize> {
+        match primitive_type {
+            PrimitiveType::Boolean | PrimitiveType::Byte | PrimitiveType::SByte => Some(1),
+            PrimitiveType::Int16 | PrimitiveType::UInt16 => Some(2),
+            PrimitiveType::Int32 | PrimitiveType::UInt32 | PrimitiveType::Single => Some(4),
+            PrimitiveType::Int64 | PrimitiveType::UInt64 | PrimitiveType::Double => Some(8),
+            _ => None,
+        }
+    }
+
+    /// `primitive_type` must have an `element_size`.
+    pub(crate) fn from_le_bytes(primitive_type: PrimitiveType, bytes: &[u8]) -> Self {
+        match primitive_type {
+            PrimitiveType::Boolean => Self::Boolean(bytes.iter().map(|byte| *byte > 0).collect()),
+            PrimitiveType::Byte => Self::Byte(bytes.to_vec()),
+            PrimitiveType::SByte => Self::SByte(bytes.iter().map(|byte| *byte as i8).collect()),
+            PrimitiveType::Int16 => from_le_bytes!(Int16, i16, bytes),
+            PrimitiveType::UInt16 => from_le_bytes!(UInt16, u16, bytes),
+            PrimitiveType::Int32 => from_le_bytes!(Int32, i32, bytes),
+            PrimitiveType::UInt32 => from_le_bytes!(UInt32, u32, bytes),
+            PrimitiveType::Single => from_le_bytes!(Single, f32, bytes),
+            PrimitiveType::Int64 => from_le_bytes!(Int64, i64, bytes),
+            PrimitiveType::UInt64 => from_le_bytes!(UInt64, u64, bytes),
+            PrimitiveType::Double => from_le_bytes!(Double, f64, bytes),
+            other => unreachable!("{:?} has no fixed width", other),
+        }
+    }
+
+    pub(crate) fn read_from<R: Read>(
+        reader: &mut R,
+        primitive_type: PrimitiveType,
+        length: usize,
+    ) -> Result<Self, ParseError> {
+        let Some(element_size) = Self::element_size(primitive_type) else {
+            let mut members = vec![];
+
+            for _ in 0..length {
+                members.push(reader.parse_typed(primitive_type)?);
+            }
+
+            return Ok(Self::into_field(members, primitive_type));
+        };
+
+        // The buffer grows as data arrives, so a corrupt length cannot allocate up front.
+        let byte_length = length.saturating_mul(element_size);
+        let mut bytes = Vec::with_capacity(byte_length.min(Self::READ_CHUNK));
+
+        while bytes.len() < byte_length {
+            let start = bytes.len();
+
+            bytes.resize(byte_length.min(start + Self::READ_CHUNK), 0);
+            reader.read_exact(&mut bytes[start..])?;
+        }
+
+        Ok(match primitive_type {
+            PrimitiveType::Byte => Self::Byte(bytes),
+            other => Self::from_le_bytes(other, &bytes),
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl PrimitiveArray {
+    /// Returns one element, or `None` past the end. Missing strings are returned as
+    /// [`Primitive::Null`].
+    pub fn get(&self, index: usize) -> Option<Primitive> {
+        match self {
+            Self::Boolean(values) => values.get(index).cloned().map(Primitive::Boolean),
+            Self::Byte(values) => values.get(index).cloned().map(Primitive::Byte),
+            Self::Char(values) => values.get(index).cloned().map(Primitive::Char),
+            Self::Decimal(values) => values.get(index).cloned().map(Primitive::Decimal),
+            Self::Double(values) => values.get(index).cloned().map(Primitive::Double),
+            Self::Int16(values) => values.get(index).cloned().map(Primitive::Int16),
+            Self::Int32(values) => values.get(index).cloned().map(Primitive::Int32),
+            Self::Int64(values) => values.get(index).cloned().map(Primitive::Int64),
+            Self::SByte(values) => values.get(index).cloned().map(Primitive::SByte),
+            Self::Single(values) => values.get(index).cloned().map(Primitive::Single),
+            Self::TimeSpan(values) => values.get(index).cloned().map(Primitive::TimeSpan),
+            Self::DateTime(values) => values.get(index).cloned().map(Primitive::DateTime),
+            Self::UInt16(values) => values.get(index).cloned().map(Primitive::UInt16),
+            Self::UInt32(values) => values.get(index).cloned().map(Primitive::UInt32),
+            Self::UInt64(values) => values.get(index).cloned().map(Primitive::UInt64),
+            Self::Null => None,
+            Self::String(values) => values
+                .get(index)
+                .map(|value| value.clone().map_or(Primitive::Null, Primitive::String)),
+        }
+    }
+
+    // Returns the old element, or `None` if the index is out of bounds or the value is not of
+    // the array's element type.
+    pub(crate) fn replace(&mut self, index: usize, value: Primitive) -> Option<Primitive> {
+        replace_element!(
+            self, index, value, Boolean, Byte, Char, Decimal, Double, Int16, Int32, Int64, SByte,
+            Single, TimeSpan, DateTime, UInt16, UInt32, UInt64
+        )
+    }
+
+    pub(crate) fn remove(&mut self, index: usize) -> Option<Primitive> {
+        if index >= self.len() {
+            return None;
+        }
+
+        Some(match self {
+            Self::Boolean(values) => Primitive::Boolean(values.remove(index)),
+            Self::Byte(values) => Primitive::Byte(values.remove(index)),
+            Self::Char(values) => Primitive::Char(values.remove(index)),
+            Self::Decimal(values) => Primitive::Decimal(values.remove(index)),
+            Self::Double(values) => Primitive::Double(values.remove(index)),
+            Self::Int16(values) => Primitive::Int16(values.remove(index)),
+            Self::Int32(values) => Primitive::Int32(values.remove(index)),
+            Self::Int64(values) => Primitive::Int64(values.remove(index)),
+            Self::SByte(values) => Primitive::SByte(values.remove(index)),
+            Self::Single(values) => Primitive::Single(values.remove(index)),
+            Self::TimeSpan(values) => Primitive::TimeSpan(values.remove(index)),
+            Self::DateTime(values) => Primitive::DateTime(values.remove(index)),
+            Self::UInt16(values) => Primitive::UInt16(values.remove(index)),
+            Self::UInt32(values) => Primitive::UInt32(values.remove(index)),
+            Self::UInt64(values) => Primitive::UInt64(values.remove(index)),
+            Self::Null => return None,
+            Self::String(values) => values
+                .remove(index)
+                .map_or(Primitive::Null, Primitive::String),
+        })
+    }
+}
+
+impl<W: Write> UnparseTo<W> for PrimitiveArray {
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        match self {
+            Self::Boolean(values) => {
+                writer.write_all(&values.iter().map(|value| *value as u8).collect::<Vec<_>>())
+            }
+            Self::Byte(values) => writer.write_all(values),
+            Self::Char(values) => writer.unparse(values),
+            Self::Decimal(values) => writer.unparse(values),
+            Self::Double(values) => write_le_bytes!(writer, values),
+            Self::Int16(values) => write_le_bytes!(writer, values),
+            Self::Int32(values) => write_le_bytes!(writer, values),
+            Self::Int64(values) => write_le_bytes!(writer, values),
+            Self::SByte(values) => {
+                writer.write_all(&values.iter().map(|value| *value as u8).collect::<Vec<_>>())
+            }
+            Self::Single(values) => write_le_bytes!(writer, values),
+            Self::TimeSpan(values) => writer.unparse(values),
+            Self::DateTime(values) => writer.unparse(values),
+            Self::UInt16(values) => write_le_bytes!(writer, values),
+            Self::UInt32(values) => write_le_bytes!(writer, values),
+            Self::UInt64(values) => write_le_bytes!(writer, values),
+            Self::Null => Ok(()),
+            Self::String(values) => writer.unparse(values),
+        }
+    }
+}
+
+impl From<PrimitiveArray> for Vec<Primitive> {
+    fn from(value: PrimitiveArray) -> Self {
+        match value {
+            PrimitiveArray::Boolean(value) => from_field!(Boolean, value),
+            PrimitiveArray::Byte(value) => from_field!(Byte, value),
+            PrimitiveArray::Char(value) => from_field!(Char, value),
+            PrimitiveArray::Decimal(value) => from_field!(Decimal, value),
+            PrimitiveArray::Double(value) => from_field!(Double, value),
+            PrimitiveArray::Int16(value) => from_field!(Int16, value),
+            PrimitiveArray::Int32(value) => from_field!(Int32, value),
+            PrimitiveArray::Int64(value) => from_field!(Int64, value),
+            PrimitiveArray::SByte(value) => from_field!(SByte, value),
+            PrimitiveArray::Single(value) => from_field!(Single, value),
+            PrimitiveArray::TimeSpan(value) => from_field!(TimeSpan, value),
+            PrimitiveArray::DateTime(value) => from_field!(DateTime, value),
+            PrimitiveArray::UInt16(value) => from_field!(UInt16, value),
+            PrimitiveArray::UInt32(value) => from_field!(UInt32, value),
+            PrimitiveArray::UInt64(value) => from_field!(UInt64, value),
+            PrimitiveArray::Null => vec![],
+            PrimitiveArray::String(value) => value
+                .into_iter()
+                .map(|value| value.map_or(Primitive::Null, Primitive::String))
+                .collect(),
+        }
+    }
+}
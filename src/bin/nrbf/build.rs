@@ -0,0 +1,25 @@
+use crate::Input;
+use ms_nrbf::Stream;
+use std::{error::Error, fs, path::PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    input: Input,
+    /// The file to write the stream to.
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let reader = crate::open(args.input.path.as_deref())?;
+    let value: serde_json::Value = serde_json::from_reader(reader)?;
+    let stream = Stream::from_json(&value)?;
+
+    // Encoded up front so that a failure doesn't leave a truncated file behind.
+    let mut buffer = Vec::new();
+    stream.encode(&mut buffer)?;
+
+    fs::write(&args.output, buffer)
+        .map_err(|error| format!("{}: {error}", args.output.display()).into())
+}
@@ -0,0 +1,71 @@
+use crate::field_summary;
+use anstream::AutoStream;
+use anstyle::{AnsiColor, Style};
+use ms_nrbf::{Change, Stream};
+use std::{
+    error::Error,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+const ADDED: Style = AnsiColor::Green.on_default();
+const REMOVED: Style = AnsiColor::Red.on_default();
+const CHANGED: Style = AnsiColor::Yellow.on_default();
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The old stream, or `-` for stdin.
+    old: PathBuf,
+    /// The new stream, or `-` for stdin.
+    new: PathBuf,
+    /// Exit with status 1 if the streams differ.
+    #[arg(long)]
+    exit_code: bool,
+}
+
+pub fn run(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    let old = decode(&args.old)?;
+    let new = decode(&args.new)?;
+    let changes = old.diff(&new);
+
+    let stdout = io::stdout();
+    let choice = AutoStream::choice(&stdout);
+    let mut out = AutoStream::new(
+        Box::new(BufWriter::new(stdout.lock())) as Box<dyn Write>,
+        choice,
+    );
+
+    for change in &changes {
+        match change {
+            Change::Added { path, value } => {
+                writeln!(out, "{ADDED}+ {path}: {}{ADDED:#}", field_summary(value))?
+            }
+            Change::Removed { path, value } => writeln!(
+                out,
+                "{REMOVED}- {path}: {}{REMOVED:#}",
+                field_summary(value)
+            )?,
+            Change::Changed { path, old, new } => writeln!(
+                out,
+                "{CHANGED}~ {path}: {} → {}{CHANGED:#}",
+                field_summary(old),
+                field_summary(new),
+            )?,
+        }
+    }
+
+    out.flush()?;
+
+    Ok(if args.exit_code && !changes.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn decode(path: &Path) -> Result<Stream, Box<dyn Error>> {
+    let mut reader = crate::open(Some(path))?;
+
+    Stream::decode(&mut reader).map_err(|error| format!("{}: {error}", path.display()).into())
+}
@@ -0,0 +1,241 @@
+use crate::Input;
+use ms_nrbf::{
+    records::{
+        AdditionalInfo, ArrayOfValueWithCode, BinaryType, ClassInfo, MemberTypeInfo, MessageFlags,
+        Record,
+    },
+    DecodeOptions, Primitive, PrimitiveArray, RecordReader, Stream,
+};
+use serde_json::{json, Value};
+use std::error::Error;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    input: Input,
+    /// Print the records as they appear in the stream instead of the object graph. The object
+    /// graph is printed in the lossless form that `ms_nrbf::json` describes.
+    #[arg(long)]
+    records: bool,
+    /// Print the JSON on a single line.
+    #[arg(long)]
+    compact: bool,
+}
+
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let mut reader = crate::open(args.input.path.as_deref())?;
+    let value = if args.records {
+        Value::Array(
+            RecordReader::new(&mut reader)
+                .map(|record| record.map(|record| record_json(&record)))
+                .collect::<Result<_, _>>()?,
+        )
+    } else {
+        let options = DecodeOptions {
+            keep_references: true,
+        };
+
+        Stream::decode_with(&mut reader, options)?.to_json()
+    };
+
+    crate::write_json(&value, args.compact)
+}
+
+fn array_json(array: &PrimitiveArray) -> Value {
+    Vec::<Primitive>::from(array.clone())
+        .iter()
+        .map(Primitive::to_json)
+        .collect()
+}
+
+fn record_json(record: &Record) -> Value {
+    match record {
+        Record::SerializationHeader(header) => json!({
+            "record": "SerializationHeader",
+            "root_id": header.root_id,
+            "header_id": header.header_id,
+            "major_version": header.major_version,
+            "minor_version": header.minor_version,
+        }),
+        Record::ClassWithId(class) => json!({
+            "record": "ClassWithId",
+            "object_id": class.object_id,
+            "metadata_id": class.metadata_id,
+            "members": records_json(&class.member_references),
+        }),
+        Record::SystemClassWithMembers(class) => json!({
+            "record": "SystemClassWithMembers",
+            "class_info": class_info_json(&class.class_info),
+            "members": records_json(&class.member_references),
+        }),
+        Record::ClassWithMembers(class) => json!({
+            "record": "ClassWithMembers",
+            "class_info": class_info_json(&class.class_info),
+            "library_id": class.library_id,
+            "members": records_json(&class.member_references),
+        }),
+        Record::SystemClassWithMembersAndTypes(class) => json!({
+            "record": "SystemClassWithMembersAndTypes",
+            "class_info": class_info_json(&class.class_info),
+            "member_type_info": member_type_info_json(&class.member_type_info),
+            "members": records_json(&class.member_references),
+        }),
+        Record::ClassWithMembersAndTypes(class) => json!({
+            "record": "ClassWithMembersAndTypes",
+            "class_info": class_info_json(&class.class_info),
+            "member_type_info": member_type_info_json(&class.member_type_info),
+            "library_id": class.library_id,
+            "members": records_json(&class.member_references),
+        }),
+        Record::BinaryObjectString(string) => json!({
+            "record": "BinaryObjectString",
+            "object_id": string.object_id,
+            "value": string.value,
+        }),
+        Record::BinaryArray(array) => json!({
+            "record": "BinaryArray",
+            "object_id": array.object_id,
+            "binary_array_type": format!("{:?}", array.binary_array_type),
+            "rank": array.rank,
+            "lengths": array.lengths,
+            "lower_bounds": array.lower_bounds,
+            "binary_type": binary_type_name(array.binary_type),
+            "additional_info": array.additional_info.as_ref().map(additional_info_json),
+            "members": records_json(&array.members),
+        }),
+        Record::MemberPrimitiveUnTyped(value) => json!({
+            "record": "MemberPrimitiveUnTyped",
+            "primitive_type": format!("{:?}", value.get_type()),
+            "value": value.to_json(),
+        }),
+        Record::MemberTypedPrimitive { value } => json!({
+            "record": "MemberTypedPrimitive",
+            "primitive_type": format!("{:?}", value.get_type()),
+            "value": value.to_json(),
+        }),
+        Record::MemberReference { id } => json!({
+            "record": "MemberReference",
+            "id": id,
+        }),
+        Record::ObjectNull => json!({ "record": "ObjectNull" }),
+        Record::MessageEnd => json!({ "record": "MessageEnd" }),
+        Record::ObjectNullMultiple256 { null_count } => json!({
+            "record": "ObjectNullMultiple256",
+            "null_count": null_count,
+        }),
+        Record::ObjectNullMultiple { null_count } => json!({
+            "record": "ObjectNullMultiple",
+            "null_count": null_count,
+        }),
+        Record::BinaryLibrary(library) => json!({
+            "record": "BinaryLibrary",
+            "library_id": library.library_id,
+            "library_name": library.library_name,
+        }),
+        Record::ArraySinglePrimitive(array) => json!({
+            "record": "ArraySinglePrimitive",
+            "object_id": array.array_info.object_id,
+            "length": array.array_info.length,
+            "primitive_type": format!("{:?}", array.members.get_type()),
+            "members": array_json(&array.members),
+        }),
+        Record::ArraySingleObject(array) => json!({
+            "record": "ArraySingleObject",
+            "object_id": array.array_info.object_id,
+            "length": array.array_info.length,
+            "members": records_json(&array.members),
+        }),
+        Record::ArraySingleString(array) => json!({
+            "record": "ArraySingleString",
+            "object_id": array.array_info.object_id,
+            "length": array.array_info.length,
+            "members": records_json(&array.members),
+        }),
+        Record::MethodCall(call) => json!({
+            "record": "MethodCall",
+            "message_flags": message_flags_json(&call.message_flags),
+            "method_name": call.method_name.0,
+            "type_name": call.type_name.0,
+            "call_context": call.call_context.as_ref().map(|context| &context.0),
+            "args": call.args.as_ref().map(args_json),
+        }),
+        Record::MethodReturn(call) => json!({
+            "record": "MethodReturn",
+            "message_flags": message_flags_json(&call.message_flags),
+            "return_value": call.return_value.as_ref().map(|value| value.0.to_json()),
+            "call_context": call.call_context.as_ref().map(|context| &context.0),
+            "args": call.args.as_ref().map(args_json),
+        }),
+    }
+}
+
+fn records_json(records: &[Record]) -> Value {
+    records.iter().map(record_json).collect()
+}
+
+fn class_info_json(class_info: &ClassInfo) -> Value {
+    json!({
+        "object_id": class_info.object_id,
+        "name": class_info.name,
+        "member_names": class_info.member_names,
+    })
+}
+
+fn member_type_info_json(member_type_info: &MemberTypeInfo) -> Value {
+    json!({
+        "member_types": member_type_info
+            .member_types
+            .iter()
+            .map(|binary_type| binary_type_name(*binary_type))
+            .collect::<Vec<_>>(),
+        "additional_info": member_type_info
+            .additional_info
+            .iter()
+            .map(additional_info_json)
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn binary_type_name(binary_type: BinaryType) -> String {
+    format!("{binary_type:?}").trim_end_matches('_').to_owned()
+}
+
+fn additional_info_json(additional_info: &AdditionalInfo) -> Value {
+    match additional_info {
+        AdditionalInfo::Primitive(primitive_type)
+        | AdditionalInfo::PrimitiveArray(primitive_type) => format!("{primitive_type:?}").into(),
+        AdditionalInfo::SystemClass(name) => name.clone().into(),
+        AdditionalInfo::Class(class) => json!({
+            "type_name": class.type_name,
+            "library_id": class.library_id,
+        }),
+    }
+}
+
+fn args_json(args: &ArrayOfValueWithCode) -> Value {
+    args.0.iter().map(|value| value.0.to_json()).collect()
+}
+
+fn message_flags_json(flags: &MessageFlags) -> Value {
+    [
+        ("NoArgs", flags.no_args),
+        ("ArgsInline", flags.args_inline),
+        ("ArgsIsArray", flags.args_is_array),
+        ("ArgsInArray", flags.args_in_array),
+        ("NoContext", flags.no_context),
+        ("ContextInline", flags.context_inline),
+        ("ContextInArray", flags.context_in_array),
+        ("MethodSignatureInArray", flags.method_signature_in_array),
+        ("PropertiesInArray", flags.properties_in_array),
+        ("NoReturnValue", flags.no_return_value),
+        ("ReturnValueVoid", flags.return_value_void),
+        ("ReturnValueInline", flags.return_value_inline),
+        ("ReturnValueInArray", flags.return_value_in_array),
+        ("ExceptionInArray", flags.exception_in_array),
+        ("GenericMethod", flags.generic_method),
+    ]
+    .into_iter()
+    .filter(|(_, set)| *set)
+    .map(|(name, _)| name)
+    .collect()
+}
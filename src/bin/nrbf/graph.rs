@@ -0,0 +1,37 @@
+use crate::Input;
+use ms_nrbf::{DecodeOptions, DotOptions, Stream};
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    input: Input,
+    /// How many edges away from the root to draw objects. Deeper objects are named in their
+    /// parent's label instead.
+    #[arg(long)]
+    depth: Option<usize>,
+    /// How many elements of each array to show.
+    #[arg(long, default_value_t = 10)]
+    items: usize,
+}
+
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let mut reader = crate::open(args.input.path.as_deref())?;
+    let options = DecodeOptions {
+        keep_references: true,
+    };
+    let stream = Stream::decode_with(&mut reader, options)?;
+    let dot = stream.to_dot_with(DotOptions {
+        max_array_items: Some(args.items),
+        max_depth: args.depth,
+    });
+
+    let mut stdout = io::stdout().lock();
+
+    stdout.write_all(dot.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,619 @@
+use crate::{primitive_summary, quote, Input};
+use anstream::AutoStream;
+use anstyle::{AnsiColor, Style};
+use ms_nrbf::{
+    records::{AdditionalInfo, BinaryType, ClassInfo, Record},
+    Primitive, PrimitiveArray, RecordReader, RecordWriter,
+};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    error::Error,
+    io::{self, BufWriter, Write},
+};
+
+const NAME: Style = Style::new().bold();
+const TYPE: Style = AnsiColor::Cyan.on_default();
+const ID: Style = AnsiColor::Yellow.on_default();
+const VALUE: Style = AnsiColor::Green.on_default();
+const DIM: Style = Style::new().dimmed();
+const HEADING: Style = Style::new().bold().underline();
+
+// How many arrays the statistics list.
+const LARGEST_ARRAYS: usize = 10;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    input: Input,
+    /// How many levels of the object graph to print. Deeper objects are cut off with `…`.
+    #[arg(long)]
+    depth: Option<usize>,
+    /// How many elements of each array to print.
+    #[arg(long, default_value_t = 10)]
+    items: usize,
+    /// Only print the statistics.
+    #[arg(long)]
+    stats: bool,
+}
+
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let reader = crate::open(args.input.path.as_deref())?;
+    let mut stats = Stats::default();
+    // The tree is printed in graph order, so it needs every record; the statistics don't.
+    let mut records = vec![];
+
+    for record in RecordReader::new(reader) {
+        let record = record?;
+
+        stats.add(&record)?;
+
+        if !args.stats {
+            records.push(record);
+        }
+    }
+
+    let stdout = io::stdout();
+    let choice = AutoStream::choice(&stdout);
+    let mut out = AutoStream::new(
+        Box::new(BufWriter::new(stdout.lock())) as Box<dyn Write>,
+        choice,
+    );
+
+    if !args.stats {
+        Graph::new(&records)?.write_tree(&mut out, &args)?;
+        writeln!(out)?;
+    }
+
+    stats.write(&mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Where a member or element record sits in its parent.
+#[derive(Clone, Copy)]
+enum Key<'r> {
+    Member(&'r str),
+    Index(usize),
+}
+
+/// The records of a stream, with every object indexed by its id.
+struct Graph<'r> {
+    objects: HashMap<i32, &'r Record>,
+    root_id: i32,
+}
+
+impl<'r> Graph<'r> {
+    fn new(records: &'r [Record]) -> Result<Self, Box<dyn Error>> {
+        let root_id = match records.first() {
+            Some(Record::SerializationHeader(header)) => header.root_id,
+            _ => return Err("the stream doesn't start with a header".into()),
+        };
+        let mut objects = HashMap::new();
+        let mut stack: Vec<&Record> = records.iter().collect();
+
+        while let Some(record) = stack.pop() {
+            if let Some(id) = record.object_id() {
+                objects.insert(id, record);
+            }
+
+            stack.extend(record.members());
+        }
+
+        if !objects.contains_key(&root_id) {
+            return Err(format!("the root object {root_id} is not in the stream").into());
+        }
+
+        Ok(Self { objects, root_id })
+    }
+
+    // `ClassWithId` records borrow the class info of an earlier class.
+    fn class_info(&self, record: &'r Record) -> Option<&'r ClassInfo> {
+        match record {
+            Record::ClassWithId(class) => self
+                .objects
+                .get(&class.metadata_id)
+                .and_then(|metadata| self.class_info(metadata)),
+            other => class_info(other),
+        }
+    }
+
+    fn type_name(&self, record: &'r Record) -> String {
+        type_name(record, self.class_info(record))
+    }
+
+    /// The member and element records of an object, with where each one sits.
+    fn members(&self, record: &'r Record) -> Vec<(Key<'r>, &'r Record)> {
+        // Libraries written among the members don't take a member's place.
+        let members = record
+            .members()
+            .iter()
+            .filter(|member| !matches!(member, Record::BinaryLibrary(_)));
+
+        if let Some(class_info) = self.class_info(record) {
+            return class_info
+                .member_names
+                .iter()
+                .map(|name| Key::Member(name))
+                .zip(members)
+                .collect();
+        }
+
+        let mut index = 0;
+
+        members
+            .map(|member| {
+                let key = Key::Index(index);
+
+                index += null_count(member).unwrap_or(1);
+                (key, member)
+            })
+            .collect()
+    }
+
+    /// Follows a `MemberReference` to the object it points at.
+    fn resolve(&self, record: &'r Record) -> Option<&'r Record> {
+        match record {
+            Record::MemberReference { id } => self.objects.get(id).copied(),
+            other => other.object_id().map(|_| other),
+        }
+    }
+
+    fn root(&self) -> &'r Record {
+        self.objects[&self.root_id]
+    }
+
+    fn write_tree(&self, out: &mut impl Write, args: &Args) -> io::Result<()> {
+        enum Entry<'r> {
+            Member(String, &'r Record),
+            More(usize),
+        }
+
+        let mut shown = HashSet::new();
+        let mut stack = vec![(0, Entry::Member("root".to_owned(), self.root()))];
+
+        while let Some((depth, entry)) = stack.pop() {
+            let indent = "  ".repeat(depth);
+            let (label, record) = match entry {
+                Entry::Member(label, record) => (label, record),
+                Entry::More(count) => {
+                    writeln!(out, "{indent}{DIM}… {count} more{DIM:#}")?;
+                    continue;
+                }
+            };
+
+            write!(out, "{indent}{NAME}{label}{NAME:#}: ")?;
+
+            let object = match self.resolve(record) {
+                Some(object) => object,
+                None => {
+                    writeln!(out, "{}", self.summary(record))?;
+                    continue;
+                }
+            };
+            let id = object.object_id().unwrap_or_default();
+
+            write!(
+                out,
+                "{TYPE}{}{TYPE:#} {ID}#{id}{ID:#}",
+                self.type_name(object)
+            )?;
+
+            if !shown.insert(id) {
+                writeln!(out, " {DIM}(shown above){DIM:#}")?;
+                continue;
+            }
+
+            match object {
+                Record::BinaryObjectString(string) => {
+                    writeln!(out, " = {VALUE}{}{VALUE:#}", quote(&string.value))?;
+                    continue;
+                }
+                Record::ArraySinglePrimitive(array) => {
+                    writeln!(
+                        out,
+                        " ({} items) = {}",
+                        array.members.len(),
+                        array_summary(&array.members, args.items),
+                    )?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let members = self.members(object);
+
+            if let Some(length) = array_length(object) {
+                write!(out, " ({length} items)")?;
+            }
+
+            if members.is_empty() {
+                writeln!(out)?;
+                continue;
+            }
+
+            if args.depth.is_some_and(|max| depth >= max) {
+                writeln!(out, " {DIM}…{DIM:#}")?;
+                continue;
+            }
+
+            writeln!(out)?;
+
+            let is_array = array_length(object).is_some();
+            let limit = if is_array { args.items } else { usize::MAX };
+            let mut children: Vec<_> = members
+                .iter()
+                .take(limit)
+                .map(|(key, member)| {
+                    let label = match (key, null_count(member)) {
+                        (Key::Member(name), _) => (*name).to_owned(),
+                        (Key::Index(index), Some(count)) if count > 1 => {
+                            format!("[{index}..{}]", index + count)
+                        }
+                        (Key::Index(index), _) => format!("[{index}]"),
+                    };
+
+                    (depth + 1, Entry::Member(label, member))
+                })
+                .collect();
+
+            if members.len() > limit {
+                let (key, _) = members[limit];
+
+                if let (Key::Index(index), Some(length)) = (key, array_length(object)) {
+                    children.push((depth + 1, Entry::More(length - index)));
+                }
+            }
+
+            stack.extend(children.into_iter().rev());
+        }
+
+        Ok(())
+    }
+
+    /// Summarises a record that isn't an object: a primitive, a null or a dangling reference.
+    fn summary(&self, record: &Record) -> String {
+        match record {
+            Record::MemberPrimitiveUnTyped(value) | Record::MemberTypedPrimitive { value } => {
+                format!("{VALUE}{}{VALUE:#}", primitive_summary(value))
+            }
+            Record::MemberReference { id } => {
+                format!("{DIM}reference to missing object{DIM:#} {ID}#{id}{ID:#}")
+            }
+            Record::ObjectNull
+            | Record::ObjectNullMultiple256 { .. }
+            | Record::ObjectNullMultiple { .. } => format!("{DIM}null{DIM:#}"),
+            other => format!("{DIM}({:?}){DIM:#}", other.record_type()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    records: HashMap<String, usize>,
+    // Bytes and object count per type name.
+    types: HashMap<String, (u64, usize)>,
+    // Id, bytes, length and type name of each array.
+    arrays: Vec<(i32, u64, usize, String)>,
+    libraries: BTreeSet<String>,
+    total: u64,
+    root_id: Option<i32>,
+    // The class info of each record that defines a class, by its object id.
+    classes: HashMap<i32, ClassInfo>,
+    links: HashMap<i32, Links>,
+}
+
+/// The objects an object refers to. Only ids are kept, so that the paths to the largest arrays
+/// can be found once the records are gone.
+struct Links {
+    /// The id of the class info naming the members, if the object is a class.
+    class: Option<i32>,
+    /// The member position or element index of each reference, and the id it leads to.
+    targets: Vec<(usize, i32)>,
+}
+
+impl Stats {
+    fn add(&mut self, record: &Record) -> Result<(), Box<dyn Error>> {
+        match record {
+            Record::SerializationHeader(header) if self.total == 0 => {
+                self.root_id = Some(header.root_id)
+            }
+            _ if self.total == 0 => return Err("the stream doesn't start with a header".into()),
+            _ => {}
+        }
+
+        self.total += record_size(record)?;
+
+        let mut stack = vec![(record, true)];
+
+        while let Some((record, is_top_level)) = stack.pop() {
+            let record_type = match record {
+                Record::MemberPrimitiveUnTyped(_) => "MemberPrimitiveUnTyped".to_owned(),
+                other => format!("{:?}", other.record_type()),
+            };
+
+            *self.records.entry(record_type).or_default() += 1;
+            stack.extend(record.members().iter().map(|member| (member, false)));
+
+            if let Record::BinaryLibrary(library) = record {
+                self.libraries.insert(library.library_name.clone());
+            }
+
+            self.link(record);
+
+            // Objects written inline are counted on their own rather than as part of the
+            // object that contains them.
+            if !is_top_level && record.object_id().is_none() {
+                continue;
+            }
+
+            let mut size = record_size(record)?;
+
+            for member in record.members() {
+                if member.object_id().is_some() {
+                    size -= record_size(member)?;
+                }
+            }
+
+            let class_info = match record {
+                Record::ClassWithId(class) => self.classes.get(&class.metadata_id),
+                other => class_info(other),
+            };
+            let type_name = type_name(record, class_info);
+            let entry = self.types.entry(type_name.clone()).or_default();
+
+            entry.0 += size;
+            entry.1 += usize::from(record.object_id().is_some());
+
+            if let (Some(id), Some(length)) = (record.object_id(), array_length(record)) {
+                self.arrays.push((id, size, length, type_name));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn link(&mut self, record: &Record) {
+        let Some(id) = record.object_id() else {
+            return;
+        };
+        let class = match record {
+            Record::ClassWithId(class) => Some(class.metadata_id),
+            other => class_info(other).map(|class_info| {
+                self.classes.insert(id, class_info.clone());
+                id
+            }),
+        };
+        let mut index = 0;
+        let targets = record
+            .members()
+            .iter()
+            .filter(|member| !matches!(member, Record::BinaryLibrary(_)))
+            .enumerate()
+            .filter_map(|(position, member)| {
+                let key = if class.is_some() { position } else { index };
+
+                index += null_count(member).unwrap_or(1);
+
+                match member {
+                    Record::MemberReference { id } => Some((key, *id)),
+                    other => Some((key, other.object_id()?)),
+                }
+            })
+            .collect();
+
+        self.links.insert(id, Links { class, targets });
+    }
+
+    /// The path to each object from the root, through the fewest members.
+    fn paths(&self) -> HashMap<i32, String> {
+        let Some(root_id) = self.root_id else {
+            return HashMap::new();
+        };
+        let mut paths = HashMap::from([(root_id, "root".to_owned())]);
+        let mut queue = VecDeque::from([root_id]);
+
+        while let Some(id) = queue.pop_front() {
+            let Some(links) = self.links.get(&id) else {
+                continue;
+            };
+            let names = links
+                .class
+                .and_then(|class| self.classes.get(&class))
+                .map(|class_info| &class_info.member_names);
+            let path = paths[&id].clone();
+
+            for (key, target) in &links.targets {
+                if paths.contains_key(target) {
+                    continue;
+                }
+
+                paths.insert(
+                    *target,
+                    match names.and_then(|names| names.get(*key)) {
+                        Some(name) => format!("{path}.{name}"),
+                        None => format!("{path}[{key}]"),
+                    },
+                );
+                queue.push_back(*target);
+            }
+        }
+
+        paths
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let total = self.total;
+
+        writeln!(out, "{HEADING}Records{HEADING:#}")?;
+
+        let mut records: Vec<_> = self.records.iter().collect();
+
+        records.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        for (record_type, count) in records {
+            writeln!(out, "  {count:>10}  {record_type}")?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "{HEADING}Bytes by type{HEADING:#} ({total} in total)")?;
+
+        let mut types: Vec<_> = self.types.iter().collect();
+
+        types.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(b.0)));
+
+        for (name, (bytes, count)) in types {
+            let objects = match count {
+                0 => String::new(),
+                1 => format!(" {DIM}(1 object){DIM:#}"),
+                _ => format!(" {DIM}({count} objects){DIM:#}"),
+            };
+
+            writeln!(
+                out,
+                "  {bytes:>10}  {:>5.1}%  {TYPE}{name}{TYPE:#}{objects}",
+                percent(*bytes, total),
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "{HEADING}Largest arrays{HEADING:#}")?;
+
+        let mut arrays = self.arrays.clone();
+        let paths = self.paths();
+
+        arrays.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        if arrays.is_empty() {
+            writeln!(out, "  {DIM}none{DIM:#}")?;
+        }
+
+        for (id, bytes, length, type_name) in arrays.into_iter().take(LARGEST_ARRAYS) {
+            let path = paths.get(&id).map_or("(unreachable)", String::as_str);
+
+            writeln!(
+                out,
+                "  {bytes:>10}  {:>5.1}%  {TYPE}{}{TYPE:#} {ID}#{id}{ID:#} ({length} items)  {path}",
+                percent(bytes, total),
+                type_name,
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{HEADING}Libraries{HEADING:#} ({})",
+            self.libraries.len()
+        )?;
+
+        for library in &self.libraries {
+            writeln!(out, "  {library}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// The class info of a record that defines a class, which `ClassWithId` records refer back to.
+fn class_info(record: &Record) -> Option<&ClassInfo> {
+    match record {
+        Record::SystemClassWithMembers(class) => Some(&class.class_info),
+        Record::ClassWithMembers(class) => Some(&class.class_info),
+        Record::SystemClassWithMembersAndTypes(class) => Some(&class.class_info),
+        Record::ClassWithMembersAndTypes(class) => Some(&class.class_info),
+        _ => None,
+    }
+}
+
+fn type_name(record: &Record, class_info: Option<&ClassInfo>) -> String {
+    match record {
+        Record::BinaryObjectString(_) => "String".to_owned(),
+        Record::ArraySinglePrimitive(array) => format!("{:?}[]", array.members.get_type()),
+        Record::ArraySingleObject(_) => "Object[]".to_owned(),
+        Record::ArraySingleString(_) => "String[]".to_owned(),
+        Record::BinaryArray(array) => {
+            let element = match (array.binary_type, &array.additional_info) {
+                (_, Some(AdditionalInfo::Primitive(primitive_type))) => {
+                    format!("{primitive_type:?}")
+                }
+                (_, Some(AdditionalInfo::PrimitiveArray(primitive_type))) => {
+                    format!("{primitive_type:?}[]")
+                }
+                (_, Some(AdditionalInfo::SystemClass(name))) => name.clone(),
+                (_, Some(AdditionalInfo::Class(class))) => class.type_name.clone(),
+                (BinaryType::String, None) => "String".to_owned(),
+                (BinaryType::ObjectArray, None) => "Object[]".to_owned(),
+                (BinaryType::StringArray, None) => "String[]".to_owned(),
+                _ => "Object".to_owned(),
+            };
+            let commas = ",".repeat(array.rank.max(1) as usize - 1);
+
+            format!("{element}[{commas}]")
+        }
+        other => match class_info {
+            Some(class_info) => class_info.name.clone(),
+            None => format!("({:?})", other.record_type()),
+        },
+    }
+}
+
+// Counts the bytes a record takes up by writing it out again.
+fn record_size(record: &Record) -> io::Result<u64> {
+    struct Counter(u64);
+
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+
+    RecordWriter::new(&mut counter).write_record(record)?;
+    Ok(counter.0)
+}
+
+fn array_length(record: &Record) -> Option<usize> {
+    let length = match record {
+        Record::ArraySinglePrimitive(array) => array.array_info.length,
+        Record::ArraySingleObject(array) => array.array_info.length,
+        Record::ArraySingleString(array) => array.array_info.length,
+        Record::BinaryArray(array) => array.lengths.iter().product(),
+        _ => return None,
+    };
+
+    Some(length.max(0) as usize)
+}
+
+fn null_count(record: &Record) -> Option<usize> {
+    match record {
+        Record::ObjectNull => Some(1),
+        Record::ObjectNullMultiple256 { null_count } => Some(*null_count as usize),
+        Record::ObjectNullMultiple { null_count } => Some((*null_count).max(0) as usize),
+        _ => None,
+    }
+}
+
+fn array_summary(array: &PrimitiveArray, items: usize) -> String {
+    let mut summary: Vec<_> = (0..array.len().min(items))
+        .map(|index| primitive_summary(&array.get(index).unwrap_or(Primitive::Null)))
+        .collect();
+
+    if array.len() > items {
+        summary.push("…".to_owned());
+    }
+
+    format!("{VALUE}[{}]{VALUE:#}", summary.join(", "))
+}
+
+fn percent(bytes: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        bytes as f64 * 100.0 / total as f64
+    }
+}
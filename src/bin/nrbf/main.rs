@@ -0,0 +1,126 @@
+mod build;
+mod diff;
+mod dump;
+mod graph;
+mod inspect;
+mod query;
+
+use clap::{Parser, Subcommand};
+use ms_nrbf::{Field, Primitive};
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+// Strings are cut short after this many characters when summarised.
+const STRING_LENGTH: usize = 60;
+
+/// Inspect MS-NRBF streams from the command line.
+#[derive(Parser)]
+#[command(name = "nrbf", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a stream as JSON.
+    Dump(dump::Args),
+    /// Encode the JSON that `dump` prints back into a stream.
+    Build(build::Args),
+    /// Print the object graph as a tree, followed by size statistics.
+    Inspect(inspect::Args),
+    /// Compare two streams by structure, matching objects by where they are in the graph.
+    Diff(diff::Args),
+    /// Print the paths of the values that a query matches.
+    Query(query::Args),
+    /// Print the object graph in the Graphviz DOT language, for `dot -Tsvg`.
+    Graph(graph::Args),
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Dump(args) => dump::run(args).map(|()| ExitCode::SUCCESS),
+        Command::Build(args) => build::run(args).map(|()| ExitCode::SUCCESS),
+        Command::Inspect(args) => inspect::run(args).map(|()| ExitCode::SUCCESS),
+        Command::Diff(args) => diff::run(args),
+        Command::Query(args) => query::run(args).map(|()| ExitCode::SUCCESS),
+        Command::Graph(args) => graph::run(args).map(|()| ExitCode::SUCCESS),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("nrbf: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Opens a file for reading, or stdin when no path or `-` is given.
+fn open(path: Option<&Path>) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    match path {
+        None => Ok(Box::new(io::stdin().lock())),
+        Some(path) if path == Path::new("-") => Ok(Box::new(io::stdin().lock())),
+        Some(path) => {
+            let file = File::open(path).map_err(|error| format!("{}: {error}", path.display()))?;
+
+            Ok(Box::new(BufReader::new(file)))
+        }
+    }
+}
+
+fn write_json(value: &serde_json::Value, compact: bool) -> Result<(), Box<dyn Error>> {
+    let mut stdout = BufWriter::new(io::stdout().lock());
+
+    if compact {
+        serde_json::to_writer(&mut stdout, value)?;
+    } else {
+        serde_json::to_writer_pretty(&mut stdout, value)?;
+    }
+
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[derive(clap::Args)]
+struct Input {
+    /// The file to read, or `-` for stdin.
+    path: Option<PathBuf>,
+}
+
+/// A value as it is shown in the diff and query output: primitives in full, and objects by
+/// their type.
+fn field_summary(field: &Field) -> String {
+    match field {
+        Field::Primitive(primitive) => primitive_summary(primitive),
+        Field::PrimitiveArray(array) => format!("{:?}[{}]", array.get_type(), array.len()),
+        Field::Class(class) => class.name.clone(),
+        Field::ObjectArray(elements) => format!("Object[{}]", elements.len()),
+        Field::ClassArray(array) => format!("{}[{}]", array.name, array.elements.len()),
+        Field::StreamedArray(array) => format!("{:?}[{}]", array.get_type(), array.len()),
+        Field::Shared(_, value) => field_summary(value),
+        Field::Reference(id) => format!("reference to object {id}"),
+        Field::Null => "null".to_owned(),
+    }
+}
+
+/// A primitive as it is shown in the inspect, diff and query output.
+fn primitive_summary(primitive: &Primitive) -> String {
+    match primitive {
+        Primitive::String(value) => quote(value),
+        primitive => primitive.to_string(),
+    }
+}
+
+fn quote(value: &str) -> String {
+    match value.char_indices().nth(STRING_LENGTH) {
+        Some((end, _)) => format!("{:?}…", &value[..end]),
+        None => format!("{value:?}"),
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{field_summary, Input};
+use anstream::AutoStream;
+use anstyle::Style;
+use ms_nrbf::{Query, Stream};
+use std::{
+    error::Error,
+    io::{self, BufWriter, Write},
+};
+
+const PATH: Style = Style::new().bold();
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The query, like `Game.Item where Durability < 10`. See `ms_nrbf::Query` for the syntax.
+    query: String,
+    #[command(flatten)]
+    input: Input,
+    /// Only print how many values match.
+    #[arg(long)]
+    count: bool,
+}
+
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let query = Query::parse(&args.query).map_err(|error| format!("query: {error}"))?;
+    let mut reader = crate::open(args.input.path.as_deref())?;
+    let stream = Stream::decode(&mut reader)?;
+    let matches = stream.query(&query);
+
+    let stdout = io::stdout();
+    let choice = AutoStream::choice(&stdout);
+    let mut out = AutoStream::new(
+        Box::new(BufWriter::new(stdout.lock())) as Box<dyn Write>,
+        choice,
+    );
+
+    if args.count {
+        writeln!(out, "{}", matches.len())?;
+    } else {
+        for found in &matches {
+            writeln!(
+                out,
+                "{PATH}{}{PATH:#}: {}",
+                found.path,
+                field_summary(&found.value)
+            )?;
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
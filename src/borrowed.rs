@@ -0,0 +1,519 @@
+use crate::{
+    array::PrimitiveArray,
+    common::{ArrayInfo, MemberTypeInfo},
+    enums::{AdditionalInfo, BinaryArrayType, BinaryType, Primitive, PrimitiveType, RecordType},
+    parse::{parse_count, parse_length, Parse, ParseError, ParseSized, ParseTyped},
+    records::{read_elements, read_references, BinaryArray, ReadMember, SerializationHeader},
+    stream::{Class, ClassArray, Field, Stream},
+};
+use indexmap::IndexMap;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io,
+    rc::Rc,
+    str,
+};
+
+/// A decoded stream whose strings and byte arrays borrow from the input buffer.
+#[derive(Debug)]
+pub struct BorrowedStream<'a> {
+    pub root: BorrowedClass<'a>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BorrowedClass<'a> {
+    /// The assembly the class belongs to, or `None` for system classes from mscorlib.
+    pub library_name: Option<&'a str>,
+    pub name: &'a str,
+    pub fields: IndexMap<&'a str, BorrowedField<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum BorrowedField<'a> {
+    /// Any primitive other than a string.
+    Primitive(Primitive),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    /// Any primitive array other than a byte or string array.
+    PrimitiveArray(PrimitiveArray),
+    StringArray(Vec<Option<&'a str>>),
+    Class(BorrowedClass<'a>),
+    ObjectArray(Vec<BorrowedField<'a>>),
+    ClassArray(BorrowedClassArray<'a>),
+    Null,
+}
+
+/// An array whose elements are all of one class, like a `Game.Item[]`.
+#[derive(Debug, Clone)]
+pub struct BorrowedClassArray<'a> {
+    /// The assembly the element class belongs to, or `None` for system classes from mscorlib.
+    pub library_name: Option<&'a str>,
+    pub name: &'a str,
+    pub elements: Vec<BorrowedField<'a>>,
+}
+
+impl<'a> BorrowedStream<'a> {
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        let mut decoder = BorrowedDecoder {
+            libraries: HashMap::new(),
+            metadata: HashMap::new(),
+            objects: HashMap::new(),
+            resolving: RefCell::default(),
+        };
+        let root_id = decoder.read_records(&mut &*bytes)?;
+
+        decoder.resolving.borrow_mut().insert(root_id);
+
+        Ok(Self {
+            root: decoder.resolve_class(root_id)?,
+        })
+    }
+
+    pub fn into_owned(self) -> Stream {
+        Stream {
+            root: self.root.into_owned(),
+        }
+    }
+}
+
+impl BorrowedClass<'_> {
+    pub fn into_owned(self) -> Class {
+        Class {
+            library_name: self.library_name.map(str::to_string),
+            name: self.name.to_string(),
+            fields: self
+                .fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.into_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl BorrowedField<'_> {
+    pub fn into_owned(self) -> Field {
+        match self {
+            Self::Primitive(value) => Field::Primitive(value),
+            Self::String(value) => Field::Primitive(Primitive::String(value.to_string())),
+            Self::Bytes(value) => Field::PrimitiveArray(PrimitiveArray::Byte(value.to_vec())),
+            Self::PrimitiveArray(value) => Field::PrimitiveArray(value),
+            Self::StringArray(values) => Field::PrimitiveArray(PrimitiveArray::String(
+                values
+                    .into_iter()
+                    .map(|value| value.map(str::to_string))
+                    .collect(),
+            )),
+            Self::Class(value) => Field::Class(value.into_owned()),
+            Self::ObjectArray(values) => {
+                Field::ObjectArray(values.into_iter().map(BorrowedField::into_owned).collect())
+            }
+            Self::ClassArray(array) => Field::ClassArray(ClassArray {
+                library_name: array.library_name.map(str::to_string),
+                name: array.name.to_string(),
+                elements: array
+                    .elements
+                    .into_iter()
+                    .map(BorrowedField::into_owned)
+                    .collect(),
+            }),
+            Self::Null => Field::Null,
+        }
+    }
+}
+
+struct Metadata<'a> {
+    library_id: Option<i32>,
+    name: &'a str,
+    member_names: Vec<&'a str>,
+    member_type_info: MemberTypeInfo,
+}
+
+enum Member<'a> {
+    Primitive(Primitive),
+    String(&'a str),
+    Reference(i32),
+    Nulls(usize),
+}
+
+enum StoredObject<'a> {
+    Class {
+        metadata: Rc<Metadata<'a>>,
+        members: Vec<Member<'a>>,
+    },
+    String(&'a str),
+    PrimitiveArray(BorrowedField<'a>),
+    StringArray(Vec<Member<'a>>),
+    ObjectArray(Vec<Member<'a>>),
+    ClassArray {
+        library_id: Option<i32>,
+        name: &'a str,
+        members: Vec<Member<'a>>,
+    },
+}
+
+struct BorrowedDecoder<'a> {
+    libraries: HashMap<i32, &'a str>,
+    metadata: HashMap<i32, Rc<Metadata<'a>>>,
+    objects: HashMap<i32, StoredObject<'a>>,
+    resolving: RefCell<HashSet<i32>>,
+}
+
+fn take<'a>(reader: &mut &'a [u8], length: usize) -> Result<&'a [u8], ParseError> {
+    if length > reader.len() {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    let (bytes, rest) = reader.split_at(length);
+    *reader = rest;
+    Ok(bytes)
+}
+
+fn read_str<'a>(reader: &mut &'a [u8]) -> Result<&'a str, ParseError> {
+    let length = parse_length(reader)?;
+    Ok(str::from_utf8(take(reader, length)?)?)
+}
+
+// Headers shared with the `Record` parsers read strings into owned ones. This finds the same
+// string, `length` bytes long and ending `suffix` bytes before the end of the header, in the input.
+fn header_str(header: &[u8], length: usize, suffix: usize) -> Result<&str, ParseError> {
+    let end = header.len() - suffix;
+    Ok(str::from_utf8(&header[end - length..end])?)
+}
+
+// Members and elements are read with the grammar the `Record` parsers use, with each record
+// read by `read_record` below.
+impl<'a> ReadMember<&'a [u8]> for BorrowedDecoder<'a> {
+    type Member = Member<'a>;
+
+    fn read_primitive(
+        &mut self,
+        reader: &mut &'a [u8],
+        primitive_type: PrimitiveType,
+    ) -> Result<Member<'a>, ParseError> {
+        Ok(Member::Primitive(reader.parse_typed(primitive_type)?))
+    }
+
+    fn read_member(&mut self, reader: &mut &'a [u8]) -> Result<Member<'a>, ParseError> {
+        let record_type = reader.parse()?;
+        self.read_record(reader, record_type)
+    }
+
+    fn element_count(member: &Member<'a>) -> usize {
+        match member {
+            Member::Nulls(null_count) => *null_count,
+            _ => 1,
+        }
+    }
+
+    // Libraries are read along with the record after them.
+    fn is_library(_: &Member<'a>) -> bool {
+        false
+    }
+}
+
+impl<'a> BorrowedDecoder<'a> {
+    fn read_records(&mut self, reader: &mut &'a [u8]) -> Result<i32, ParseError> {
+        let mut root_id = None;
+
+        loop {
+            match reader.parse()? {
+                RecordType::SerializedStreamHeader => {
+                    let header: SerializationHeader = reader.parse()?;
+                    root_id = Some(header.root_id);
+                }
+                RecordType::MessageEnd => break,
+                record_type => {
+                    self.read_record(reader, record_type)?;
+                }
+            }
+        }
+
+        root_id.ok_or(ParseError::MissingHeader)
+    }
+
+    // Reads a record in member position. Objects written inline are stored like any other
+    // object and returned as a reference to themselves. Only the parts of records that hold
+    // strings are read here, so that they can borrow from the input.
+    fn read_record(
+        &mut self,
+        reader: &mut &'a [u8],
+        record_type: RecordType,
+    ) -> Result<Member<'a>, ParseError> {
+        Ok(match record_type {
+            RecordType::MemberReference => Member::Reference(reader.parse()?),
+            RecordType::ObjectNull => Member::Nulls(1),
+            RecordType::ObjectNullMultiple256 => Member::Nulls(reader.parse::<u8>()? as usize),
+            RecordType::ObjectNullMultiple => Member::Nulls(parse_count(reader)?),
+            RecordType::MemberTypedPrimitive => {
+                let primitive_type = reader.parse()?;
+                self.read_primitive(reader, primitive_type)?
+            }
+            RecordType::BinaryObjectString => {
+                let object_id = reader.parse()?;
+                let value = read_str(reader)?;
+
+                self.objects.insert(object_id, StoredObject::String(value));
+                Member::String(value)
+            }
+            RecordType::BinaryLibrary => {
+                let library_id = reader.parse()?;
+                let library_name = read_str(reader)?;
+
+                self.libraries.insert(library_id, library_name);
+
+                // A library is always followed by the record that uses it.
+                self.read_member(reader)?
+            }
+            RecordType::ClassWithMembersAndTypes
+            | RecordType::SystemClassWithMembersAndTypes
+            | RecordType::ClassWithMembers
+            | RecordType::SystemClassWithMembers => {
+                let object_id = reader.parse()?;
+                let name = read_str(reader)?;
+                let member_count = parse_count(reader)?;
+                let member_names: Vec<_> = (0..member_count)
+                    .map(|_| read_str(reader))
+                    .collect::<Result<_, _>>()?;
+                let member_type_info = match record_type {
+                    RecordType::ClassWithMembersAndTypes
+                    | RecordType::SystemClassWithMembersAndTypes => {
+                        reader.parse_sized(member_count)?
+                    }
+                    _ => MemberTypeInfo::untyped(member_count),
+                };
+                let library_id = match record_type {
+                    RecordType::ClassWithMembersAndTypes | RecordType::ClassWithMembers => {
+                        Some(reader.parse()?)
+                    }
+                    _ => None,
+                };
+                let mut names = HashSet::new();
+
+                if let Some(name) = member_names.iter().find(|name| !names.insert(**name)) {
+                    return Err(ParseError::DuplicateMember(name.to_string()));
+                }
+
+                let metadata = Rc::new(Metadata {
+                    library_id,
+                    name,
+                    member_names,
+                    member_type_info,
+                });
+
+                self.metadata.insert(object_id, metadata.clone());
+                self.read_class(reader, object_id, metadata)?
+            }
+            RecordType::ClassWithId => {
+                let object_id = reader.parse()?;
+                let metadata_id = reader.parse()?;
+                let metadata = self
+                    .metadata
+                    .get(&metadata_id)
+                    .ok_or(ParseError::MissingMetadata(metadata_id))?
+                    .clone();
+
+                self.read_class(reader, object_id, metadata)?
+            }
+            RecordType::ArraySinglePrimitive => {
+                let array_info: ArrayInfo = reader.parse()?;
+                let primitive_type = reader.parse()?;
+                let array =
+                    read_primitive_array(reader, primitive_type, array_info.length as usize)?;
+
+                self.objects
+                    .insert(array_info.object_id, StoredObject::PrimitiveArray(array));
+                Member::Reference(array_info.object_id)
+            }
+            RecordType::ArraySingleString => {
+                let array_info: ArrayInfo = reader.parse()?;
+                let members = read_elements(reader, array_info.length as usize, None, self)?;
+
+                self.objects
+                    .insert(array_info.object_id, StoredObject::StringArray(members));
+                Member::Reference(array_info.object_id)
+            }
+            RecordType::ArraySingleObject => {
+                let array_info: ArrayInfo = reader.parse()?;
+                let members = read_elements(reader, array_info.length as usize, None, self)?;
+
+                self.objects
+                    .insert(array_info.object_id, StoredObject::ObjectArray(members));
+                Member::Reference(array_info.object_id)
+            }
+            RecordType::BinaryArray => {
+                let header_start = *reader;
+                let (array, length) = BinaryArray::parse_header(reader)?;
+                let header = &header_start[..header_start.len() - reader.len()];
+                let object = match (array.binary_type, array.additional_info) {
+                    (_, Some(AdditionalInfo::Primitive(primitive_type))) => {
+                        StoredObject::PrimitiveArray(read_primitive_array(
+                            reader,
+                            primitive_type,
+                            length,
+                        )?)
+                    }
+                    (BinaryType::String, _) => {
+                        StoredObject::StringArray(read_elements(reader, length, None, self)?)
+                    }
+                    // Only single-dimensional class arrays keep their element type, as in
+                    // owned streams.
+                    (_, Some(AdditionalInfo::Class(info)))
+                        if array.binary_array_type == BinaryArrayType::Single =>
+                    {
+                        StoredObject::ClassArray {
+                            library_id: Some(info.library_id),
+                            name: header_str(header, info.type_name.len(), 4)?,
+                            members: read_elements(reader, length, None, self)?,
+                        }
+                    }
+                    (_, Some(AdditionalInfo::SystemClass(name)))
+                        if array.binary_array_type == BinaryArrayType::Single =>
+                    {
+                        StoredObject::ClassArray {
+                            library_id: None,
+                            name: header_str(header, name.len(), 0)?,
+                            members: read_elements(reader, length, None, self)?,
+                        }
+                    }
+                    _ => StoredObject::ObjectArray(read_elements(reader, length, None, self)?),
+                };
+
+                self.objects.insert(array.object_id, object);
+                Member::Reference(array.object_id)
+            }
+            RecordType::SerializedStreamHeader
+            | RecordType::MessageEnd
+            | RecordType::MethodCall
+            | RecordType::MethodReturn => return Err(ParseError::UnexpectedRecord(record_type)),
+        })
+    }
+
+    fn read_class(
+        &mut self,
+        reader: &mut &'a [u8],
+        object_id: i32,
+        metadata: Rc<Metadata<'a>>,
+    ) -> Result<Member<'a>, ParseError> {
+        let members = read_references(reader, &metadata.member_type_info, self)?;
+
+        self.objects
+            .insert(object_id, StoredObject::Class { metadata, members });
+        Ok(Member::Reference(object_id))
+    }
+
+    fn resolve_class(&self, object_id: i32) -> Result<BorrowedClass<'a>, ParseError> {
+        let (metadata, members) = match self.objects.get(&object_id) {
+            Some(StoredObject::Class { metadata, members }) => (metadata, members),
+            _ => return Err(ParseError::MissingObject(object_id)),
+        };
+        let library_name = self.library_name(metadata.library_id)?;
+        let mut fields = IndexMap::with_capacity(members.len());
+
+        for (name, member) in metadata.member_names.iter().zip(members) {
+            fields.insert(*name, self.resolve_member(member)?);
+        }
+
+        Ok(BorrowedClass {
+            library_name,
+            name: metadata.name,
+            fields,
+        })
+    }
+
+    fn library_name(&self, library_id: Option<i32>) -> Result<Option<&'a str>, ParseError> {
+        library_id
+            .map(|library_id| {
+                self.libraries
+                    .get(&library_id)
+                    .copied()
+                    .ok_or(ParseError::MissingLibrary(library_id))
+            })
+            .transpose()
+    }
+
+    fn resolve_member(&self, member: &Member<'a>) -> Result<BorrowedField<'a>, ParseError> {
+        let id = match member {
+            Member::Primitive(value) => return Ok(BorrowedField::Primitive(value.clone())),
+            Member::String(value) => return Ok(BorrowedField::String(value)),
+            Member::Nulls(_) => return Ok(BorrowedField::Null),
+            Member::Reference(id) => *id,
+        };
+
+        // Borrowed streams are trees like owned ones, so a reference back to an object that is
+        // still being resolved is left empty.
+        if !self.resolving.borrow_mut().insert(id) {
+            return Ok(BorrowedField::Null);
+        }
+
+        let field = match self.objects.get(&id) {
+            Some(StoredObject::Class { .. }) => BorrowedField::Class(self.resolve_class(id)?),
+            Some(StoredObject::String(value)) => BorrowedField::String(value),
+            Some(StoredObject::PrimitiveArray(array)) => array.clone(),
+            Some(StoredObject::StringArray(members)) => BorrowedField::StringArray(
+                self.resolve_elements(members)?
+                    .into_iter()
+                    .map(|element| match element {
+                        BorrowedField::String(value) => Some(value),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            Some(StoredObject::ObjectArray(members)) => {
+                BorrowedField::ObjectArray(self.resolve_elements(members)?)
+            }
+            Some(StoredObject::ClassArray {
+                library_id,
+                name,
+                members,
+            }) => BorrowedField::ClassArray(BorrowedClassArray {
+                library_name: self.library_name(*library_id)?,
+                name,
+                elements: self.resolve_elements(members)?,
+            }),
+            None => return Err(ParseError::MissingObject(id)),
+        };
+
+        self.resolving.borrow_mut().remove(&id);
+
+        Ok(field)
+    }
+
+    fn resolve_elements(
+        &self,
+        members: &[Member<'a>],
+    ) -> Result<Vec<BorrowedField<'a>>, ParseError> {
+        let mut elements = vec![];
+
+        for member in members {
+            match member {
+                Member::Nulls(null_count) => {
+                    elements.extend((0..*null_count).map(|_| BorrowedField::Null))
+                }
+                other => elements.push(self.resolve_member(other)?),
+            }
+        }
+
+        Ok(elements)
+    }
+}
+
+fn read_primitive_array<'a>(
+    reader: &mut &'a [u8],
+    primitive_type: PrimitiveType,
+    length: usize,
+) -> Result<BorrowedField<'a>, ParseError> {
+    let Some(element_size) = PrimitiveArray::element_size(primitive_type) else {
+        return Ok(BorrowedField::PrimitiveArray(PrimitiveArray::read_from(
+            reader,
+            primitive_type,
+            length,
+        )?));
+    };
+    let bytes = take(reader, length.saturating_mul(element_size))?;
+
+    Ok(match primitive_type {
+        PrimitiveType::Byte => BorrowedField::Bytes(bytes),
+        other => BorrowedField::PrimitiveArray(PrimitiveArray::from_le_bytes(other, bytes)),
+    })
+}
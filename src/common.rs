@@ -1,12 +1,13 @@
 use crate::{
     enums::{AdditionalInfo, BinaryType, MessageFlagEnum, Primitive, PrimitiveType},
-    parse::{Parse, ParseError, ParseFrom, ParseFromSized, ParseSized, ParseTyped},
+    io::{self, Read, Write},
+    parse::{parse_count, Parse, ParseError, ParseFrom, ParseFromSized, ParseSized, ParseTyped},
     unparse::{Unparse, UnparseTo},
 };
-use std::io::{self, Read, Write};
+use alloc::{string::String, vec, vec::Vec};
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ClassInfo {
+pub struct ClassInfo {
     pub object_id: i32,
     pub name: String,
     pub member_count: i32,
@@ -17,7 +18,7 @@ impl<R: Read> ParseFrom<R> for ClassInfo {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
         let object_id = reader.parse()?;
         let name = reader.parse()?;
-        let member_count = reader.parse()?;
+        let member_count = parse_count(reader)?;
 
         let mut member_names = vec![];
 
@@ -28,27 +29,38 @@ impl<R: Read> ParseFrom<R> for ClassInfo {
         Ok(Self {
             object_id,
             name,
-            member_count,
+            member_count: member_count as i32,
             member_names,
         })
     }
 }
 
 impl<W: Write> UnparseTo<W> for ClassInfo {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self.object_id)?;
-        writer.unparse(self.name)?;
-        writer.unparse(self.member_count)?;
-        writer.unparse(self.member_names)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&self.object_id)?;
+        writer.unparse(&self.name)?;
+        writer.unparse(&self.member_count)?;
+        writer.unparse(&self.member_names)
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub(crate) struct MemberTypeInfo {
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct MemberTypeInfo {
     pub member_types: Vec<BinaryType>,
     pub additional_info: Vec<AdditionalInfo>,
 }
 
+impl MemberTypeInfo {
+    // Records without member type information carry every member value as a record of its
+    // own, exactly like members of type `Object`.
+    pub(crate) fn untyped(member_count: usize) -> Self {
+        Self {
+            member_types: vec![BinaryType::Object; member_count],
+            additional_info: vec![],
+        }
+    }
+}
+
 impl<R: Read> ParseFromSized<R> for MemberTypeInfo {
     fn parse_from_sized(reader: &mut R, member_count: usize) -> Result<Self, ParseError> {
         let member_types: Vec<BinaryType> = reader.parse_sized(member_count)?;
@@ -68,36 +80,39 @@ impl<R: Read> ParseFromSized<R> for MemberTypeInfo {
 }
 
 impl<W: Write> UnparseTo<W> for MemberTypeInfo {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self.member_types)?;
-        writer.unparse(self.additional_info)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&self.member_types)?;
+        writer.unparse(&self.additional_info)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ArrayInfo {
+pub struct ArrayInfo {
     pub object_id: i32,
     pub length: i32,
 }
 
 impl<R: Read> ParseFrom<R> for ArrayInfo {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+        let object_id = reader.parse()?;
+        let length = parse_count(reader)?;
+
         Ok(Self {
-            object_id: reader.parse()?,
-            length: reader.parse()?,
+            object_id,
+            length: length as i32,
         })
     }
 }
 
 impl<W: Write> UnparseTo<W> for ArrayInfo {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self.object_id)?;
-        writer.unparse(self.length)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&self.object_id)?;
+        writer.unparse(&self.length)
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ClassTypeInfo {
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ClassTypeInfo {
     pub type_name: String,
     pub library_id: i32,
 }
@@ -112,14 +127,14 @@ impl<R: Read> ParseFrom<R> for ClassTypeInfo {
 }
 
 impl<W: Write> UnparseTo<W> for ClassTypeInfo {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self.type_name)?;
-        writer.unparse(self.library_id)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&self.type_name)?;
+        writer.unparse(&self.library_id)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ValueWithCode(pub Primitive);
+pub struct ValueWithCode(pub Primitive);
 
 impl<R: Read> ParseFrom<R> for ValueWithCode {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
@@ -132,34 +147,33 @@ impl<R: Read> ParseFrom<R> for ValueWithCode {
 }
 
 impl<W: Write> UnparseTo<W> for ValueWithCode {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self.0.get_type())?;
-        writer.unparse(self.0)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&self.0.get_type())?;
+        writer.unparse(&self.0)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct StringValueWithCode(pub String);
+pub struct StringValueWithCode(pub String);
 
 impl<R: Read> ParseFrom<R> for StringValueWithCode {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
-        assert_eq!(
-            reader.parse::<u8>()?,
-            BinaryType::String as u8
-        );
-        Ok(Self(reader.parse()?))
+        match reader.parse()? {
+            BinaryType::String => Ok(Self(reader.parse()?)),
+            binary_type => Err(ParseError::UnexpectedBinaryType(binary_type)),
+        }
     }
 }
 
 impl<W: Write> UnparseTo<W> for StringValueWithCode {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(BinaryType::String)?;
-        writer.unparse(self.0)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&BinaryType::String)?;
+        writer.unparse(&self.0)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ArrayOfValueWithCode(pub Vec<ValueWithCode>);
+pub struct ArrayOfValueWithCode(pub Vec<ValueWithCode>);
 
 impl<R: Read> ParseFrom<R> for ArrayOfValueWithCode {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
@@ -172,14 +186,14 @@ impl<R: Read> ParseFrom<R> for ArrayOfValueWithCode {
 }
 
 impl<W: Write> UnparseTo<W> for ArrayOfValueWithCode {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self.0.len() as i32)?;
-        writer.unparse(self.0)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&(self.0.len() as i32))?;
+        writer.unparse(&self.0)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct MessageFlags {
+pub struct MessageFlags {
     pub no_args: bool,
     pub args_inline: bool,
     pub args_is_array: bool,
@@ -222,7 +236,7 @@ impl<R: Read> ParseFrom<R> for MessageFlags {
 }
 
 impl<W: Write> UnparseTo<W> for MessageFlags {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
         let mut int: u32 = 0;
 
         if self.no_args {
@@ -271,6 +285,6 @@ impl<W: Write> UnparseTo<W> for MessageFlags {
             int |= MessageFlagEnum::GenericMethod
         }
 
-        writer.unparse(int)
+        writer.unparse(&int)
     }
 }
@@ -0,0 +1,231 @@
+use crate::{
+    array::PrimitiveArray,
+    enums::{Primitive, PrimitiveType},
+    stream::{Class, ClassArray, Field},
+    time::{DateTime, TimeSpan},
+};
+use chrono::{Duration, NaiveDateTime};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConvertError {
+    #[error("missing member {0}")]
+    MissingMember(String),
+    #[error("expected {expected}, found {found}")]
+    TypeMismatch {
+        expected: &'static str,
+        found: String,
+    },
+}
+
+impl ConvertError {
+    pub(crate) fn mismatch(expected: &'static str, found: &Field) -> Self {
+        Self::TypeMismatch {
+            expected,
+            found: found.describe(),
+        }
+    }
+}
+
+impl Field {
+    // How the field's type is named in error messages.
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            Field::Primitive(primitive) => format!("{:?}", primitive.get_type()),
+            Field::PrimitiveArray(array) => format!("{:?}[]", array.get_type()),
+            Field::Class(class) => class.name.clone(),
+            Field::ObjectArray(_) => "Object[]".to_string(),
+            Field::ClassArray(array) => format!("{}[]", array.name),
+            Field::StreamedArray(array) => format!("{:?}[]", array.get_type()),
+            Field::Shared(_, value) => value.describe(),
+            Field::Reference(id) => format!("reference to object {id}"),
+            Field::Null => "null".to_string(),
+        }
+    }
+}
+
+/// A Rust type that maps onto a .NET class, usually implemented with `#[derive(NrbfClass)]`.
+pub trait NrbfClass: Sized {
+    fn into_class(self) -> Class;
+    fn from_class(class: Class) -> Result<Self, ConvertError>;
+}
+
+/// A Rust type that can be stored in a class member.
+pub trait NrbfField: Sized {
+    fn into_field(self) -> Field;
+    fn from_field(field: Field) -> Result<Self, ConvertError>;
+
+    /// The primitive type this type is stored as, if any. A `Vec` of such a type is written
+    /// as a primitive array even when it is empty.
+    fn primitive_type() -> Option<PrimitiveType> {
+        None
+    }
+}
+
+macro_rules! primitive_field {
+    ($type:ty, $primitive_type:ident) => {
+        impl NrbfField for $type {
+            fn into_field(self) -> Field {
+                Field::Primitive(Primitive::$primitive_type(self))
+            }
+
+            fn from_field(field: Field) -> Result<Self, ConvertError> {
+                match unshared(field) {
+                    Field::Primitive(Primitive::$primitive_type(value)) => Ok(value),
+                    other => Err(ConvertError::mismatch(
+                        stringify!($primitive_type),
+                        &other,
+                    )),
+                }
+            }
+
+            fn primitive_type() -> Option<PrimitiveType> {
+                Some(PrimitiveType::$primitive_type)
+            }
+        }
+    };
+}
+
+primitive_field!(bool, Boolean);
+primitive_field!(u8, Byte);
+primitive_field!(char, Char);
+primitive_field!(f64, Double);
+primitive_field!(i16, Int16);
+primitive_field!(i32, Int32);
+primitive_field!(i64, Int64);
+primitive_field!(i8, SByte);
+primitive_field!(f32, Single);
+primitive_field!(TimeSpan, TimeSpan);
+primitive_field!(DateTime, DateTime);
+primitive_field!(u16, UInt16);
+primitive_field!(u32, UInt32);
+primitive_field!(u64, UInt64);
+primitive_field!(String, String);
+
+impl NrbfField for NaiveDateTime {
+    /// # Panics
+    ///
+    /// If the time is out of the range of a [`DateTime`]. Convert with `DateTime::try_from`
+    /// first where that can happen.
+    fn into_field(self) -> Field {
+        DateTime::try_from(self)
+            .expect("time out of range of a DateTime")
+            .into_field()
+    }
+
+    fn from_field(field: Field) -> Result<Self, ConvertError> {
+        let value = DateTime::from_field(field)?;
+
+        value.to_naive().ok_or_else(|| ConvertError::TypeMismatch {
+            expected: "NaiveDateTime",
+            found: value.to_string(),
+        })
+    }
+
+    fn primitive_type() -> Option<PrimitiveType> {
+        DateTime::primitive_type()
+    }
+}
+
+impl NrbfField for Duration {
+    /// # Panics
+    ///
+    /// If the duration is out of the range of a [`TimeSpan`]. Convert with `TimeSpan::try_from`
+    /// first where that can happen.
+    fn into_field(self) -> Field {
+        TimeSpan::try_from(self)
+            .expect("duration out of range of a TimeSpan")
+            .into_field()
+    }
+
+    fn from_field(field: Field) -> Result<Self, ConvertError> {
+        Ok(TimeSpan::from_field(field)?.to_duration())
+    }
+
+    fn primitive_type() -> Option<PrimitiveType> {
+        TimeSpan::primitive_type()
+    }
+}
+
+impl NrbfField for Field {
+    fn into_field(self) -> Field {
+        self
+    }
+
+    fn from_field(field: Field) -> Result<Self, ConvertError> {
+        Ok(field)
+    }
+}
+
+impl NrbfField for Class {
+    fn into_field(self) -> Field {
+        Field::Class(self)
+    }
+
+    fn from_field(field: Field) -> Result<Self, ConvertError> {
+        match unshared(field) {
+            Field::Class(class) => Ok(class),
+            other => Err(ConvertError::mismatch("class", &other)),
+        }
+    }
+}
+
+impl<T: NrbfField> NrbfField for Option<T> {
+    fn into_field(self) -> Field {
+        self.map_or(Field::Null, T::into_field)
+    }
+
+    fn from_field(field: Field) -> Result<Self, ConvertError> {
+        match unshared(field) {
+            Field::Null | Field::Primitive(Primitive::Null) => Ok(None),
+            other => Ok(Some(T::from_field(other)?)),
+        }
+    }
+
+    // Only string arrays can hold nulls.
+    fn primitive_type() -> Option<PrimitiveType> {
+        T::primitive_type().filter(|primitive_type| *primitive_type == PrimitiveType::String)
+    }
+}
+
+impl<T: NrbfField> NrbfField for Vec<T> {
+    fn into_field(self) -> Field {
+        Field::from_elements(
+            self.into_iter().map(T::into_field).collect(),
+            T::primitive_type(),
+        )
+    }
+
+    fn from_field(field: Field) -> Result<Self, ConvertError> {
+        match unshared(field) {
+            Field::PrimitiveArray(PrimitiveArray::String(values)) => values
+                .into_iter()
+                .map(|value| {
+                    T::from_field(value.map_or(Field::Null, |value| {
+                        Field::Primitive(Primitive::String(value))
+                    }))
+                })
+                .collect(),
+            Field::PrimitiveArray(array) => Vec::<Primitive>::from(array)
+                .into_iter()
+                .map(|primitive| T::from_field(Field::Primitive(primitive)))
+                .collect(),
+            Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }) => {
+                elements.into_iter().map(T::from_field).collect()
+            }
+            Field::StreamedArray(array) => {
+                Self::from_field(Field::PrimitiveArray(array.to_primitive_array()))
+            }
+            other => Err(ConvertError::mismatch("array", &other)),
+        }
+    }
+}
+
+// Objects decoded with `keep_references` are wrapped in `Field::Shared`, which conversions look
+// through.
+fn unshared(field: Field) -> Field {
+    match field {
+        Field::Shared(_, value) => unshared(*value),
+        field => field,
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    array::PrimitiveArray,
+    enums::Primitive,
+    parse::ParseError,
+    ser::{DICTIONARY_NAME, ENUM_VALUE_NAME},
+    stream::{Class, ClassArray, Field, Stream},
+};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use std::{fmt::Display, io::Read, vec};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DeserializeError {
+    #[error("failed to decode stream")]
+    ParseError(#[from] ParseError),
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl de::Error for DeserializeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+pub fn from_stream<T: DeserializeOwned>(stream: Stream) -> Result<T, DeserializeError> {
+    T::deserialize(FieldDeserializer(Field::Class(stream.root)))
+}
+
+pub fn from_reader<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<T, DeserializeError> {
+    from_stream(Stream::decode(reader)?)
+}
+
+fn visit_primitive<'de, V: Visitor<'de>>(
+    primitive: Primitive,
+    visitor: V,
+) -> Result<V::Value, DeserializeError> {
+    match primitive {
+        Primitive::Boolean(value) => visitor.visit_bool(value),
+        Primitive::Byte(value) => visitor.visit_u8(value),
+        Primitive::Char(value) => visitor.visit_char(value),
+        Primitive::Decimal(value) => visitor.visit_string(value),
+        Primitive::Double(value) => visitor.visit_f64(value),
+        Primitive::Int16(value) => visitor.visit_i16(value),
+        Primitive::Int32(value) => visitor.visit_i32(value),
+        Primitive::Int64(value) => visitor.visit_i64(value),
+        Primitive::SByte(value) => visitor.visit_i8(value),
+        Primitive::Single(value) => visitor.visit_f32(value),
+        Primitive::TimeSpan(value) => visitor.visit_string(value.to_string()),
+        Primitive::DateTime(value) => visitor.visit_string(value.to_string()),
+        Primitive::UInt16(value) => visitor.visit_u16(value),
+        Primitive::UInt32(value) => visitor.visit_u32(value),
+        Primitive::UInt64(value) => visitor.visit_u64(value),
+        Primitive::Null => visitor.visit_unit(),
+        Primitive::String(value) => visitor.visit_string(value),
+    }
+}
+
+fn into_entries(mut class: Class) -> Vec<(Field, Field)> {
+    if !class.name.starts_with(DICTIONARY_NAME) {
+        return class
+            .fields
+            .into_iter()
+            .map(|(name, value)| (Field::Primitive(Primitive::String(name)), value))
+            .collect();
+    }
+
+    let pairs = match class.fields.shift_remove("KeyValuePairs") {
+        Some(
+            Field::ObjectArray(pairs)
+            | Field::ClassArray(ClassArray {
+                elements: pairs, ..
+            }),
+        ) => pairs,
+        _ => vec![],
+    };
+
+    pairs
+        .into_iter()
+        .filter_map(|pair| match pair {
+            Field::Class(mut pair) => Some((
+                pair.fields.shift_remove("key").unwrap_or(Field::Null),
+                pair.fields.shift_remove("value").unwrap_or(Field::Null),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+struct FieldDeserializer(Field);
+
+impl<'de> de::Deserializer<'de> for FieldDeserializer {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        match self.0 {
+            Field::Primitive(primitive) => visit_primitive(primitive, visitor),
+            Field::PrimitiveArray(array) => {
+                let elements: Vec<Primitive> = array.into();
+
+                visitor.visit_seq(SeqAccess(
+                    elements
+                        .into_iter()
+                        .map(Field::Primitive)
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                ))
+            }
+            Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }) => {
+                visitor.visit_seq(SeqAccess(elements.into_iter()))
+            }
+            Field::StreamedArray(array) => {
+                FieldDeserializer(Field::PrimitiveArray(array.to_primitive_array()))
+                    .deserialize_any(visitor)
+            }
+            Field::Class(class) => visitor.visit_map(MapAccess {
+                entries: into_entries(class).into_iter(),
+                value: None,
+            }),
+            Field::Shared(_, value) => FieldDeserializer(*value).deserialize_any(visitor),
+            Field::Reference(id) => Err(de::Error::custom(format!(
+                "cannot deserialize a reference to object {id}"
+            ))),
+            Field::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        match self.0 {
+            Field::Null | Field::Primitive(Primitive::Null) => visitor.visit_none(),
+            other => visitor.visit_some(FieldDeserializer(other)),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        match self.0 {
+            Field::PrimitiveArray(PrimitiveArray::Byte(bytes)) => visitor.visit_byte_buf(bytes),
+            other => FieldDeserializer(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        let value = match self.0 {
+            Field::Class(mut class) => class
+                .fields
+                .shift_remove(ENUM_VALUE_NAME)
+                .unwrap_or(Field::Null),
+            other => other,
+        };
+
+        match value {
+            Field::Primitive(Primitive::String(variant)) => {
+                visitor.visit_enum(IntoDeserializer::<DeserializeError>::into_deserializer(
+                    variant,
+                ))
+            }
+            Field::Primitive(Primitive::Int32(index)) => {
+                visitor.visit_enum(IntoDeserializer::<DeserializeError>::into_deserializer(
+                    index as u32,
+                ))
+            }
+            other => Err(de::Error::custom(format!(
+                "expected an enum value, found {:?}",
+                other
+            ))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqAccess(vec::IntoIter<Field>);
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeserializeError> {
+        self.0
+            .next()
+            .map(|element| seed.deserialize(FieldDeserializer(element)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct MapAccess {
+    entries: vec::IntoIter<(Field, Field)>,
+    value: Option<Field>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeserializeError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(FieldDeserializer(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, DeserializeError> {
+        seed.deserialize(FieldDeserializer(self.value.take().unwrap_or(Field::Null)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
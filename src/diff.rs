@@ -0,0 +1,169 @@
+use crate::{
+    array::PrimitiveArray,
+    enums::Primitive,
+    stream::{Class, Field, Stream},
+};
+
+/// A difference between two streams, found by [`Stream::diff`].
+///
+/// Paths start at `root` and name class members with `.` and array elements with `[index]`,
+/// like `root.World.Entities[42].Health`.
+#[derive(Debug, Clone)]
+pub enum Change {
+    /// A class member or array element that only the new stream has.
+    Added { path: String, value: Field },
+    /// A class member or array element that only the old stream has.
+    Removed { path: String, value: Field },
+    /// A value that differs between the streams. Values of different kinds, and classes of
+    /// different types, are reported whole rather than member by member.
+    Changed {
+        path: String,
+        old: Field,
+        new: Field,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Added { path, .. } | Self::Removed { path, .. } | Self::Changed { path, .. } => {
+                path
+            }
+        }
+    }
+}
+
+impl Stream {
+    /// Compares two streams structurally, matching objects by where they are in the object
+    /// graph rather than by object id.
+    ///
+    /// Objects that appear more than once are compared everywhere they appear. References,
+    /// which only decoding with [`DecodeOptions::keep_references`](crate::DecodeOptions) gives,
+    /// are equal when they refer to the same id.
+    pub fn diff(&self, other: &Stream) -> Vec<Change> {
+        let mut changes = vec![];
+
+        diff_class(&self.root, &other.root, "root", &mut changes);
+        changes
+    }
+}
+
+fn diff_class(old: &Class, new: &Class, path: &str, changes: &mut Vec<Change>) {
+    for (name, old_value) in &old.fields {
+        let member_path = format!("{path}.{name}");
+
+        match new.fields.get(name) {
+            Some(new_value) => diff_field(old_value, new_value, member_path, changes),
+            None => changes.push(Change::Removed {
+                path: member_path,
+                value: old_value.clone(),
+            }),
+        }
+    }
+
+    for (name, new_value) in &new.fields {
+        if !old.fields.contains_key(name) {
+            changes.push(Change::Added {
+                path: format!("{path}.{name}"),
+                value: new_value.clone(),
+            });
+        }
+    }
+}
+
+fn diff_field(old: &Field, new: &Field, path: String, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Field::Shared(_, old), new) => diff_field(old, new, path, changes),
+        (old, Field::Shared(_, new)) => diff_field(old, new, path, changes),
+        (Field::StreamedArray(old), new) => diff_field(
+            &Field::PrimitiveArray(old.to_primitive_array()),
+            new,
+            path,
+            changes,
+        ),
+        (old, Field::StreamedArray(new)) => diff_field(
+            old,
+            &Field::PrimitiveArray(new.to_primitive_array()),
+            path,
+            changes,
+        ),
+        (Field::Class(old), Field::Class(new))
+            if old.name == new.name && old.library_name == new.library_name =>
+        {
+            diff_class(old, new, &path, changes)
+        }
+        (Field::ObjectArray(old), Field::ObjectArray(new)) => {
+            diff_elements(old, new, &path, changes)
+        }
+        (Field::ClassArray(old), Field::ClassArray(new))
+            if old.name == new.name && old.library_name == new.library_name =>
+        {
+            diff_elements(&old.elements, &new.elements, &path, changes)
+        }
+        (Field::PrimitiveArray(old), Field::PrimitiveArray(new))
+            if old.get_type() == new.get_type() =>
+        {
+            diff_elements(&elements(old), &elements(new), &path, changes)
+        }
+        (Field::Primitive(old), Field::Primitive(new)) if same_primitive(old, new) => {}
+        (Field::Reference(old), Field::Reference(new)) if old == new => {}
+        (Field::Null | Field::Primitive(Primitive::Null), Field::Null)
+        | (Field::Null, Field::Primitive(Primitive::Null)) => {}
+        (old, new) => changes.push(Change::Changed {
+            path,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+    }
+}
+
+fn diff_elements(old: &[Field], new: &[Field], path: &str, changes: &mut Vec<Change>) {
+    for (index, (old, new)) in old.iter().zip(new).enumerate() {
+        diff_field(old, new, format!("{path}[{index}]"), changes);
+    }
+
+    for (index, value) in old.iter().enumerate().skip(new.len()) {
+        changes.push(Change::Removed {
+            path: format!("{path}[{index}]"),
+            value: value.clone(),
+        });
+    }
+
+    for (index, value) in new.iter().enumerate().skip(old.len()) {
+        changes.push(Change::Added {
+            path: format!("{path}[{index}]"),
+            value: value.clone(),
+        });
+    }
+}
+
+// Missing strings in string arrays become `Field::Null`, like they do when decoding.
+fn elements(array: &PrimitiveArray) -> Vec<Field> {
+    match array {
+        PrimitiveArray::String(values) => values
+            .iter()
+            .map(|value| {
+                value.clone().map_or(Field::Null, |value| {
+                    Field::Primitive(Primitive::String(value))
+                })
+            })
+            .collect(),
+        array => Vec::<Primitive>::from(array.clone())
+            .into_iter()
+            .map(Field::Primitive)
+            .collect(),
+    }
+}
+
+// NaN is equal to itself here, so that an unchanged NaN isn't reported.
+fn same_primitive(old: &Primitive, new: &Primitive) -> bool {
+    match (old, new) {
+        (Primitive::Double(old), Primitive::Double(new)) => {
+            old.to_bits() == new.to_bits() || old == new
+        }
+        (Primitive::Single(old), Primitive::Single(new)) => {
+            old.to_bits() == new.to_bits() || old == new
+        }
+        (old, new) => old == new,
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    array::PrimitiveArray,
+    enums::Primitive,
+    stream::{Class, ClassArray, Field, Stream},
+};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+// Strings are cut short after this many characters in node labels.
+const STRING_LENGTH: usize = 40;
+
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    /// Show at most this many elements of each array, followed by a count of the rest.
+    pub max_array_items: Option<usize>,
+    /// Only draw objects at most this many edges away from the root. Deeper objects are named
+    /// in their parent's label instead.
+    pub max_depth: Option<usize>,
+}
+
+impl Stream {
+    /// Draws the object graph in the Graphviz DOT language.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    /// Draws the object graph in the Graphviz DOT language.
+    ///
+    /// Each class and object array is a node labelled with its type, its object id when it has
+    /// one, and its primitive members. Members that hold objects are edges labelled with the
+    /// member name. Edges for [`Field::Reference`]s are dashed, and those that lead back to an
+    /// object the edge is inside of, closing a cycle, are also red. Objects are only shared, and
+    /// cycles only kept, when decoded with
+    /// [`DecodeOptions::keep_references`](crate::DecodeOptions).
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let mut graph = Graph {
+            options,
+            out: String::from("digraph nrbf {\n    node [shape=box, fontname=\"monospace\"];\n"),
+            anonymous: 0,
+            defined: HashSet::from([Stream::ROOT_ID]),
+            referenced: BTreeSet::new(),
+            ancestors: vec![],
+        };
+
+        graph.class(
+            &self.root,
+            node_name(Stream::ROOT_ID),
+            Some(Stream::ROOT_ID),
+            0,
+        );
+
+        // Objects that are referenced but not drawn, because they are too deep or missing.
+        for id in &graph.referenced {
+            if !graph.defined.contains(id) {
+                let _ = writeln!(
+                    graph.out,
+                    "    {} [label=\"#{id}\", style=dashed];",
+                    node_name(*id)
+                );
+            }
+        }
+
+        graph.out.push_str("}\n");
+        graph.out
+    }
+}
+
+struct Graph {
+    options: DotOptions,
+    out: String,
+    anonymous: usize,
+    // Ids of the objects drawn, or about to be drawn.
+    defined: HashSet<i32>,
+    referenced: BTreeSet<i32>,
+    // Ids of the objects the current node is inside of, for finding cycles.
+    ancestors: Vec<i32>,
+}
+
+// A node and its outgoing edges, written once all of its members are known.
+struct Node<'a> {
+    name: String,
+    id: Option<i32>,
+    lines: Vec<String>,
+    edges: Vec<String>,
+    children: Vec<Child<'a>>,
+}
+
+// A member that holds an object, drawn as its own node after its parent.
+struct Child<'a> {
+    field: &'a Field,
+    name: String,
+    id: Option<i32>,
+}
+
+impl Graph {
+    fn node(&mut self, field: &Field, name: String, id: Option<i32>, depth: usize) {
+        if let Field::Class(class) = field {
+            return self.class(class, name, id, depth);
+        }
+
+        let mut node = Node::new(name, id);
+
+        self.ancestors.extend(id);
+
+        let title = match field {
+            Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }) => {
+                let shown = self.items(elements.len());
+
+                for (index, value) in elements.iter().enumerate().take(shown) {
+                    self.member(&mut node, &format!("[{index}]"), value, depth);
+                }
+
+                if shown < elements.len() {
+                    node.lines
+                        .push(format!("… {} more", elements.len() - shown));
+                }
+
+                match field {
+                    Field::ClassArray(array) => format!("{}[{}]", array.name, elements.len()),
+                    _ => format!("Object[{}]", elements.len()),
+                }
+            }
+            Field::PrimitiveArray(array) => {
+                node.lines.push(self.elements(array, array.len()));
+                format!("{:?}[{}]", array.get_type(), array.len())
+            }
+            Field::StreamedArray(array) => {
+                node.lines
+                    .push(self.elements(&array.prefix(self.items(array.len())), array.len()));
+                format!("{:?}[{}]", array.get_type(), array.len())
+            }
+            Field::Primitive(primitive) => {
+                node.lines.push(primitive_text(primitive));
+                format!("{:?}", primitive.get_type())
+            }
+            Field::Class(_) | Field::Shared(_, _) | Field::Reference(_) | Field::Null => {
+                unreachable!("classes are drawn above, and only objects are drawn as nodes")
+            }
+        };
+
+        self.draw(&title, node, depth);
+    }
+
+    fn class(&mut self, class: &Class, name: String, id: Option<i32>, depth: usize) {
+        let mut node = Node::new(name, id);
+
+        self.ancestors.extend(id);
+
+        for (member, value) in &class.fields {
+            self.member(&mut node, member, value, depth);
+        }
+
+        self.draw(&class.name, node, depth);
+    }
+
+    // Writes a node and its edges, then the nodes of the objects its members hold. The node's id
+    // is already in `ancestors`, and is removed once its children are drawn.
+    fn draw(&mut self, title: &str, node: Node, depth: usize) {
+        let mut label = escape(title);
+
+        if let Some(id) = node.id {
+            let _ = write!(label, " #{id}");
+        }
+
+        label.push_str("\\n");
+
+        for line in &node.lines {
+            label.push_str(&escape(line));
+            label.push_str("\\l");
+        }
+
+        let _ = writeln!(self.out, "    {} [label=\"{label}\"];", node.name);
+
+        for edge in &node.edges {
+            let _ = writeln!(self.out, "    {edge};");
+        }
+
+        for child in node.children {
+            self.node(child.field, child.name, child.id, depth + 1);
+        }
+
+        if node.id.is_some() {
+            self.ancestors.pop();
+        }
+    }
+
+    fn member<'a>(&mut self, node: &mut Node<'a>, member: &str, value: &'a Field, depth: usize) {
+        let (value, id) = match value {
+            Field::Shared(id, _) if self.defined.contains(id) => {
+                return self.reference(node, member, *id);
+            }
+            Field::Shared(id, value) => (&**value, Some(*id)),
+            value => (value, None),
+        };
+
+        match value {
+            Field::Primitive(primitive) if id.is_none() => node
+                .lines
+                .push(format!("{member} = {}", primitive_text(primitive))),
+            Field::PrimitiveArray(array) if id.is_none() => node.lines.push(format!(
+                "{member} = {:?}[{}] {}",
+                array.get_type(),
+                array.len(),
+                self.elements(array, array.len())
+            )),
+            Field::StreamedArray(array) if id.is_none() => node.lines.push(format!(
+                "{member} = {:?}[{}] {}",
+                array.get_type(),
+                array.len(),
+                self.elements(&array.prefix(self.items(array.len())), array.len())
+            )),
+            Field::Null => node.lines.push(format!("{member} = null")),
+            Field::Reference(id) => self.reference(node, member, *id),
+            value if self.options.max_depth.is_some_and(|max| depth >= max) => node
+                .lines
+                .push(format!("{member} → {} …", value.describe())),
+            value => {
+                let name = match id {
+                    Some(id) => {
+                        self.defined.insert(id);
+                        node_name(id)
+                    }
+                    None => {
+                        self.anonymous += 1;
+                        format!("o{}", self.anonymous)
+                    }
+                };
+
+                node.edges.push(format!(
+                    "{} -> {name} [label=\"{}\"]",
+                    node.name,
+                    escape(member)
+                ));
+                node.children.push(Child {
+                    field: value,
+                    name,
+                    id,
+                });
+            }
+        }
+    }
+
+    fn reference(&mut self, node: &mut Node, member: &str, id: i32) {
+        let style = if self.ancestors.contains(&id) {
+            "style=dashed, color=red, constraint=false"
+        } else {
+            "style=dashed"
+        };
+
+        self.referenced.insert(id);
+        node.edges.push(format!(
+            "{} -> {} [label=\"{}\", {style}]",
+            node.name,
+            node_name(id),
+            escape(member)
+        ));
+    }
+
+    // Lists the elements that are shown of an array of `length` elements. `array` may hold only
+    // those, so that a streamed array is never produced in full.
+    fn elements(&self, array: &PrimitiveArray, length: usize) -> String {
+        let shown = self.items(length);
+        let mut text = String::from("{");
+
+        for index in 0..shown {
+            if index > 0 {
+                text.push_str(", ");
+            }
+
+            text.push_str(&primitive_text(
+                &array.get(index).unwrap_or(Primitive::Null),
+            ));
+        }
+
+        if shown < length {
+            let _ = write!(text, ", … {} more", length - shown);
+        }
+
+        text.push('}');
+        text
+    }
+
+    fn items(&self, length: usize) -> usize {
+        self.options
+            .max_array_items
+            .map_or(length, |max| max.min(length))
+    }
+}
+
+impl Node<'_> {
+    fn new(name: String, id: Option<i32>) -> Self {
+        Self {
+            name,
+            id,
+            lines: vec![],
+            edges: vec![],
+            children: vec![],
+        }
+    }
+}
+
+fn node_name(id: i32) -> String {
+    // Negative ids are possible in hand-built streams, and `-` can't appear in a DOT id.
+    if id < 0 {
+        format!("m{}", id.unsigned_abs())
+    } else {
+        format!("n{id}")
+    }
+}
+
+// Long strings are cut short, so that one member can't stretch the whole node.
+fn primitive_text(primitive: &Primitive) -> String {
+    match primitive {
+        Primitive::String(value) => match value.char_indices().nth(STRING_LENGTH) {
+            Some((end, _)) => format!("{:?}…", &value[..end]),
+            None => format!("{value:?}"),
+        },
+        primitive => primitive.to_string(),
+    }
+}
+
+// Escapes text for a quoted DOT string, where a backslash starts an escape sequence.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
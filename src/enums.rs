@@ -1,23 +1,28 @@
 use crate::{
     common::ClassTypeInfo,
-    parse::{Parse, ParseError, ParseFrom, ParseFromTyped, ParseTyped},
+    io::{self, Read, Write},
+    parse::{
+        parse_count, Parse, ParseContext, ParseError, ParseFrom, ParseFromContext, ParseFromTyped,
+        ParseTyped, ParseWithContext,
+    },
     records::{
         ArraySingleObject, ArraySinglePrimitive, ArraySingleString, BinaryArray, BinaryLibrary,
         BinaryMethodCall, BinaryMethodReturn, BinaryObjectString, ClassWithId, ClassWithMembers,
         ClassWithMembersAndTypes, SerializationHeader, SystemClassWithMembers,
         SystemClassWithMembersAndTypes,
     },
+    time::{DateTime, TimeSpan},
     unparse::{Unparse, UnparseTo},
 };
-use chrono::{NaiveDateTime, NaiveTime};
-use num_enum::TryFromPrimitive;
-use std::{
-    io::{self, Read, Write},
+use alloc::{string::String, vec, vec::Vec};
+use core::{
+    fmt,
     ops::{BitAnd, BitOrAssign},
 };
+use num_enum::TryFromPrimitive;
 
 #[repr(u8)]
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, TryFromPrimitive)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, TryFromPrimitive)]
 pub enum PrimitiveType {
     Boolean = 1,
     Byte = 2,
@@ -47,8 +52,8 @@ impl<R: Read> ParseFrom<R> for PrimitiveType {
 }
 
 impl<W: Write> UnparseTo<W> for PrimitiveType {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self as u8)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&(*self as u8))
     }
 }
 
@@ -64,8 +69,8 @@ pub enum Primitive {
     Int64(i64),
     SByte(i8),
     Single(f32),
-    TimeSpan(NaiveTime),
-    DateTime(NaiveDateTime),
+    TimeSpan(TimeSpan),
+    DateTime(DateTime),
     UInt16(u16),
     UInt32(u32),
     UInt64(u64),
@@ -74,7 +79,7 @@ pub enum Primitive {
 }
 
 impl Primitive {
-    pub(crate) fn get_type(&self) -> PrimitiveType {
+    pub fn get_type(&self) -> PrimitiveType {
         match self {
             Primitive::Boolean(_) => PrimitiveType::Boolean,
             Primitive::Byte(_) => PrimitiveType::Byte,
@@ -97,6 +102,32 @@ impl Primitive {
     }
 }
 
+/// Writes the value as it reads in text output: chars and strings are quoted and escaped, and
+/// [`Primitive::Null`] is `null`.
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Primitive::Boolean(value) => write!(f, "{value}"),
+            Primitive::Byte(value) => write!(f, "{value}"),
+            Primitive::Char(value) => write!(f, "{value:?}"),
+            Primitive::Decimal(value) => f.write_str(value),
+            Primitive::Double(value) => write!(f, "{value}"),
+            Primitive::Int16(value) => write!(f, "{value}"),
+            Primitive::Int32(value) => write!(f, "{value}"),
+            Primitive::Int64(value) => write!(f, "{value}"),
+            Primitive::SByte(value) => write!(f, "{value}"),
+            Primitive::Single(value) => write!(f, "{value}"),
+            Primitive::TimeSpan(value) => write!(f, "{value}"),
+            Primitive::DateTime(value) => write!(f, "{value}"),
+            Primitive::UInt16(value) => write!(f, "{value}"),
+            Primitive::UInt32(value) => write!(f, "{value}"),
+            Primitive::UInt64(value) => write!(f, "{value}"),
+            Primitive::Null => f.write_str("null"),
+            Primitive::String(value) => write!(f, "{value:?}"),
+        }
+    }
+}
+
 impl<R: Read> ParseFromTyped<R, PrimitiveType> for Primitive {
     fn parse_from_typed(reader: &mut R, primitive_type: PrimitiveType) -> Result<Self, ParseError> {
         Ok(match primitive_type {
@@ -122,31 +153,31 @@ impl<R: Read> ParseFromTyped<R, PrimitiveType> for Primitive {
 }
 
 impl<W: Write> UnparseTo<W> for Primitive {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
         match self {
-            Self::Boolean(value) => writer.unparse(value as u8),
-            Self::Byte(value) => writer.unparse(value),
-            Self::Char(value) => writer.unparse(value),
-            Self::Decimal(value) => writer.unparse(value),
-            Self::Double(value) => writer.unparse(value),
-            Self::Int16(value) => writer.unparse(value),
-            Self::Int32(value) => writer.unparse(value),
-            Self::Int64(value) => writer.unparse(value),
-            Self::SByte(value) => writer.unparse(value),
-            Self::Single(value) => writer.unparse(value),
-            Self::TimeSpan(value) => writer.unparse(value),
-            Self::DateTime(value) => writer.unparse(value),
-            Self::UInt16(value) => writer.unparse(value),
-            Self::UInt32(value) => writer.unparse(value),
-            Self::UInt64(value) => writer.unparse(value),
+            Self::Boolean(value) => writer.unparse(&(*value as u8)),
+            Self::Byte(value) => writer.unparse(&value),
+            Self::Char(value) => writer.unparse(&value),
+            Self::Decimal(value) => writer.unparse(&value),
+            Self::Double(value) => writer.unparse(&value),
+            Self::Int16(value) => writer.unparse(&value),
+            Self::Int32(value) => writer.unparse(&value),
+            Self::Int64(value) => writer.unparse(&value),
+            Self::SByte(value) => writer.unparse(&value),
+            Self::Single(value) => writer.unparse(&value),
+            Self::TimeSpan(value) => writer.unparse(&value),
+            Self::DateTime(value) => writer.unparse(&value),
+            Self::UInt16(value) => writer.unparse(&value),
+            Self::UInt32(value) => writer.unparse(&value),
+            Self::UInt64(value) => writer.unparse(&value),
             Self::Null => Ok(()),
-            Self::String(value) => writer.unparse(value),
+            Self::String(value) => writer.unparse(&value),
         }
     }
 }
 
 #[repr(u8)]
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, TryFromPrimitive)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, TryFromPrimitive)]
 pub enum BinaryType {
     Primitive_ = 0,
     String = 1,
@@ -167,8 +198,8 @@ impl<R: Read> ParseFrom<R> for BinaryType {
 }
 
 impl<W: Write> UnparseTo<W> for BinaryType {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self as u8)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&(*self as u8))
     }
 }
 
@@ -206,14 +237,13 @@ impl<R: Read> ParseFrom<R> for RecordType {
 }
 
 impl<W: Write> UnparseTo<W> for RecordType {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self as u8)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&(*self as u8))
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-#[allow(dead_code)]
-pub(crate) enum Record {
+pub enum Record {
     SerializationHeader(SerializationHeader),
     ClassWithId(ClassWithId),
     SystemClassWithMembers(SystemClassWithMembers),
@@ -237,17 +267,88 @@ pub(crate) enum Record {
     MethodReturn(BinaryMethodReturn),
 }
 
-impl<R: Read> ParseFromTyped<R, RecordType> for Record {
-    fn parse_from_typed(reader: &mut R, record_type: RecordType) -> Result<Self, ParseError> {
+impl Record {
+    /// Untyped primitives have no record type of their own and report `MemberTypedPrimitive`.
+    pub fn record_type(&self) -> RecordType {
+        match self {
+            Self::SerializationHeader(_) => RecordType::SerializedStreamHeader,
+            Self::ClassWithId(_) => RecordType::ClassWithId,
+            Self::SystemClassWithMembers(_) => RecordType::SystemClassWithMembers,
+            Self::ClassWithMembers(_) => RecordType::ClassWithMembers,
+            Self::SystemClassWithMembersAndTypes(_) => RecordType::SystemClassWithMembersAndTypes,
+            Self::ClassWithMembersAndTypes(_) => RecordType::ClassWithMembersAndTypes,
+            Self::BinaryObjectString(_) => RecordType::BinaryObjectString,
+            Self::BinaryArray(_) => RecordType::BinaryArray,
+            Self::MemberPrimitiveUnTyped(_) | Self::MemberTypedPrimitive { .. } => {
+                RecordType::MemberTypedPrimitive
+            }
+            Self::MemberReference { .. } => RecordType::MemberReference,
+            Self::ObjectNull => RecordType::ObjectNull,
+            Self::MessageEnd => RecordType::MessageEnd,
+            Self::ObjectNullMultiple256 { .. } => RecordType::ObjectNullMultiple256,
+            Self::ObjectNullMultiple { .. } => RecordType::ObjectNullMultiple,
+            Self::BinaryLibrary(_) => RecordType::BinaryLibrary,
+            Self::ArraySinglePrimitive(_) => RecordType::ArraySinglePrimitive,
+            Self::ArraySingleObject(_) => RecordType::ArraySingleObject,
+            Self::ArraySingleString(_) => RecordType::ArraySingleString,
+            Self::MethodCall(_) => RecordType::MethodCall,
+            Self::MethodReturn(_) => RecordType::MethodReturn,
+        }
+    }
+
+    /// The id of the object this record defines, if it defines one.
+    pub fn object_id(&self) -> Option<i32> {
+        match self {
+            Self::ClassWithId(class) => Some(class.object_id),
+            Self::SystemClassWithMembers(class) => Some(class.class_info.object_id),
+            Self::ClassWithMembers(class) => Some(class.class_info.object_id),
+            Self::SystemClassWithMembersAndTypes(class) => Some(class.class_info.object_id),
+            Self::ClassWithMembersAndTypes(class) => Some(class.class_info.object_id),
+            Self::BinaryObjectString(string) => Some(string.object_id),
+            Self::BinaryArray(array) => Some(array.object_id),
+            Self::ArraySinglePrimitive(array) => Some(array.array_info.object_id),
+            Self::ArraySingleObject(array) => Some(array.array_info.object_id),
+            Self::ArraySingleString(array) => Some(array.array_info.object_id),
+            _ => None,
+        }
+    }
+
+    /// The member and element records held by a class or array, which may include objects
+    /// written inline.
+    pub fn members(&self) -> &[Record] {
+        match self {
+            Self::ClassWithId(class) => &class.member_references,
+            Self::SystemClassWithMembers(class) => &class.member_references,
+            Self::ClassWithMembers(class) => &class.member_references,
+            Self::SystemClassWithMembersAndTypes(class) => &class.member_references,
+            Self::ClassWithMembersAndTypes(class) => &class.member_references,
+            Self::BinaryArray(array) => &array.members,
+            Self::ArraySingleObject(array) => &array.members,
+            Self::ArraySingleString(array) => &array.members,
+            _ => &[],
+        }
+    }
+}
+
+impl<R: Read> ParseFromContext<R> for Record {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
+        let record_type = reader.parse()?;
+
         Ok(match record_type {
             RecordType::SerializedStreamHeader => Self::SerializationHeader(reader.parse()?),
-            RecordType::ClassWithId => Self::ClassWithId(reader.parse()?),
+            RecordType::ClassWithId => Self::ClassWithId(reader.parse_with(context)?),
+            RecordType::SystemClassWithMembers => {
+                Self::SystemClassWithMembers(reader.parse_with(context)?)
+            }
+            RecordType::ClassWithMembers => Self::ClassWithMembers(reader.parse_with(context)?),
             RecordType::SystemClassWithMembersAndTypes => {
-                Self::SystemClassWithMembersAndTypes(reader.parse()?)
+                Self::SystemClassWithMembersAndTypes(reader.parse_with(context)?)
+            }
+            RecordType::ClassWithMembersAndTypes => {
+                Self::ClassWithMembersAndTypes(reader.parse_with(context)?)
             }
-            RecordType::ClassWithMembersAndTypes => Self::ClassWithMembersAndTypes(reader.parse()?),
             RecordType::BinaryObjectString => Self::BinaryObjectString(reader.parse()?),
-            RecordType::BinaryArray => Self::BinaryArray(reader.parse()?),
+            RecordType::BinaryArray => Self::BinaryArray(reader.parse_with(context)?),
             RecordType::MemberTypedPrimitive => {
                 let primitive_type = reader.parse()?;
                 Self::MemberTypedPrimitive {
@@ -263,81 +364,79 @@ impl<R: Read> ParseFromTyped<R, RecordType> for Record {
                 null_count: reader.parse()?,
             },
             RecordType::ObjectNullMultiple => Self::ObjectNullMultiple {
-                null_count: reader.parse()?,
+                null_count: parse_count(reader)? as i32,
             },
             RecordType::BinaryLibrary => Self::BinaryLibrary(reader.parse()?),
             RecordType::ArraySinglePrimitive => Self::ArraySinglePrimitive(reader.parse()?),
-            RecordType::ArraySingleString => Self::ArraySingleString(reader.parse()?),
+            RecordType::ArraySingleObject => Self::ArraySingleObject(reader.parse_with(context)?),
+            RecordType::ArraySingleString => Self::ArraySingleString(reader.parse_with(context)?),
             RecordType::MethodCall => Self::MethodCall(reader.parse()?),
             RecordType::MethodReturn => Self::MethodReturn(reader.parse()?),
-            other => Err(ParseError::NotEnoughInfo(other))?,
         })
     }
 }
 
 impl<R: Read> ParseFrom<R> for Vec<Record> {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+        let mut context = ParseContext::default();
         let mut records = vec![];
 
         loop {
-            let record_type = reader.parse()?;
-            let record = reader.parse_typed(record_type)?;
-            let is_message_end = record == Record::MessageEnd;
+            let record = reader.parse_with(&mut context)?;
+            let finished = matches!(record, Record::MessageEnd);
 
             records.push(record);
 
-            if is_message_end {
-                break;
+            if finished {
+                return Ok(records);
             }
         }
-
-        Ok(records)
     }
 }
 
 impl<W: Write> UnparseTo<W> for Record {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
         match self {
-            Self::SerializationHeader(value) => writer.unparse(value),
-            Self::ClassWithId(value) => writer.unparse(value),
-            Self::SystemClassWithMembers(value) => writer.unparse(value),
-            Self::ClassWithMembers(value) => writer.unparse(value),
-            Self::SystemClassWithMembersAndTypes(value) => writer.unparse(value),
-            Self::ClassWithMembersAndTypes(value) => writer.unparse(value),
-            Self::BinaryObjectString(value) => writer.unparse(value),
-            Self::BinaryArray(value) => writer.unparse(value),
+            Self::SerializationHeader(value) => writer.unparse(&value),
+            Self::ClassWithId(value) => writer.unparse(&value),
+            Self::SystemClassWithMembers(value) => writer.unparse(&value),
+            Self::ClassWithMembers(value) => writer.unparse(&value),
+            Self::SystemClassWithMembersAndTypes(value) => writer.unparse(&value),
+            Self::ClassWithMembersAndTypes(value) => writer.unparse(&value),
+            Self::BinaryObjectString(value) => writer.unparse(&value),
+            Self::BinaryArray(value) => writer.unparse(&value),
             Self::MemberReference { id } => {
-                writer.unparse(RecordType::MemberReference)?;
-                writer.unparse(id)
+                writer.unparse(&RecordType::MemberReference)?;
+                writer.unparse(&id)
             }
-            Self::ObjectNull => writer.unparse(RecordType::ObjectNull),
-            Self::MessageEnd => writer.unparse(RecordType::MessageEnd),
+            Self::ObjectNull => writer.unparse(&RecordType::ObjectNull),
+            Self::MessageEnd => writer.unparse(&RecordType::MessageEnd),
             Self::ObjectNullMultiple256 { null_count } => {
-                writer.unparse(RecordType::ObjectNullMultiple256)?;
-                writer.unparse(null_count)
+                writer.unparse(&RecordType::ObjectNullMultiple256)?;
+                writer.unparse(&null_count)
             }
             Self::ObjectNullMultiple { null_count } => {
-                writer.unparse(RecordType::ObjectNullMultiple)?;
-                writer.unparse(null_count)
+                writer.unparse(&RecordType::ObjectNullMultiple)?;
+                writer.unparse(&null_count)
             }
-            Self::BinaryLibrary(value) => writer.unparse(value),
-            Self::ArraySinglePrimitive(value) => writer.unparse(value),
-            Self::ArraySingleObject(value) => writer.unparse(value),
-            Self::ArraySingleString(value) => writer.unparse(value),
-            Self::MethodCall(value) => writer.unparse(value),
-            Self::MethodReturn(value) => writer.unparse(value),
-            Self::MemberPrimitiveUnTyped(value) => writer.unparse(value),
+            Self::BinaryLibrary(value) => writer.unparse(&value),
+            Self::ArraySinglePrimitive(value) => writer.unparse(&value),
+            Self::ArraySingleObject(value) => writer.unparse(&value),
+            Self::ArraySingleString(value) => writer.unparse(&value),
+            Self::MethodCall(value) => writer.unparse(&value),
+            Self::MethodReturn(value) => writer.unparse(&value),
+            Self::MemberPrimitiveUnTyped(value) => writer.unparse(&value),
             Self::MemberTypedPrimitive { value } => {
-                writer.unparse(RecordType::MemberTypedPrimitive)?;
-                writer.unparse(value.get_type())?;
-                writer.unparse(value)
+                writer.unparse(&RecordType::MemberTypedPrimitive)?;
+                writer.unparse(&value.get_type())?;
+                writer.unparse(&value)
             }
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub(crate) enum AdditionalInfo {
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum AdditionalInfo {
     Primitive(PrimitiveType),
     SystemClass(String),
     Class(ClassTypeInfo),
@@ -363,12 +462,12 @@ impl<R: Read> ParseFromTyped<R, BinaryType> for Option<AdditionalInfo> {
 }
 
 impl<W: Write> UnparseTo<W> for AdditionalInfo {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
         match self {
-            Self::Primitive(value) => writer.unparse(value),
-            Self::PrimitiveArray(value) => writer.unparse(value),
-            Self::Class(value) => writer.unparse(value),
-            Self::SystemClass(value) => writer.unparse(value),
+            Self::Primitive(value) => writer.unparse(&value),
+            Self::PrimitiveArray(value) => writer.unparse(&value),
+            Self::Class(value) => writer.unparse(&value),
+            Self::SystemClass(value) => writer.unparse(&value),
         }
     }
 }
@@ -393,8 +492,8 @@ impl<R: Read> ParseFrom<R> for BinaryArrayType {
 }
 
 impl<W: Write> UnparseTo<W> for BinaryArrayType {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(self as u8)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&(*self as u8))
     }
 }
 
@@ -0,0 +1,132 @@
+//! The reader and writer traits the record codec is built on.
+//!
+//! With the `std` feature they are implemented for every `std::io::Read` and `std::io::Write`,
+//! and [`Error`] is `std::io::Error`. Without it they are implemented for byte slices and
+//! `Vec<u8>`, and can be implemented for any other source or sink of bytes.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use core::fmt;
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind};
+
+pub trait Read {
+    /// Fills `buf` completely, failing with [`ErrorKind::UnexpectedEof`] if the source runs out.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error>;
+}
+
+pub trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error>;
+
+    fn flush(&mut self) -> Result<(), Error>;
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read + ?Sized> Read for R {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        std::io::Read::read_exact(self, buf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> Write for W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        std::io::Write::write_all(self, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        std::io::Write::flush(self)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    UnexpectedEof,
+    WriteZero,
+    InvalidData,
+    Other,
+}
+
+/// The error returned by readers and writers when `std` is not available.
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+}
+
+#[cfg(not(feature = "std"))]
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self { kind }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.kind {
+            ErrorKind::UnexpectedEof => "unexpected end of input",
+            ErrorKind::WriteZero => "failed to write the whole buffer",
+            ErrorKind::InvalidData => "invalid data",
+            ErrorKind::Other => "other error",
+        })
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl core::error::Error for Error {}
+
+#[cfg(not(feature = "std"))]
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        (**self).read_exact(buf)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Read for &[u8] {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        if buf.len() > self.len() {
+            *self = &self[self.len()..];
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        let (head, tail) = self.split_at(buf.len());
+        buf.copy_from_slice(head);
+        *self = tail;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        (**self).write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        (**self).flush()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,521 @@
+//! A lossless JSON form of [`Stream`], for reading and editing streams as text.
+//!
+//! [`Stream::to_json`] and [`Stream::from_json`] convert between the two, and
+//! `Stream::from_json(&stream.to_json())` gives back an equal stream. Keys that start with `$`
+//! hold type information; a class member whose name starts with `$` has another `$` put in
+//! front of it.
+//!
+//! The stream is written as its root class. Classes are objects:
+//!
+//! ```json
+//! { "$type": "Game.Player", "$library": "Game, Version=1.0.0.0", "Name": "Ann", "Level": 3 }
+//! ```
+//!
+//! `$library` is left out for system classes. Member values are written as follows:
+//!
+//! - `Int32`, `Double`, `Boolean` and `String` values are plain JSON values. Numbers without a
+//!   fraction or exponent are `Int32`s, and all others are `Double`s.
+//! - Other primitives are objects with the type name after a `$` as their only key:
+//!   `{ "$Int16": 5 }`, `{ "$Single": 0.5 }`, `{ "$Char": "x" }`, `{ "$Decimal": "1.50" }`.
+//!   A `TimeSpan` is a count of ticks, `{ "$TimeSpan": 600000000 }`, and a `DateTime` is its
+//!   ticks and kind, `{ "$DateTime": { "ticks": 638000000000000000, "kind": "Utc" } }`. Doubles
+//!   and singles that are not finite are written as `"NaN"`, `"Infinity"` or `"-Infinity"`.
+//! - Object arrays are JSON arrays of member values.
+//! - Primitive arrays name their element type and list their elements without tags:
+//!   `{ "$array": "Int16", "$items": [1, 2, 3] }`. Missing elements of string arrays are `null`.
+//! - Arrays of one class name the class in the same way, with its library if it has one:
+//!   `{ "$array": "Game.Item", "$library": "Game", "$items": [...] }`.
+//! - `null` is an empty member.
+//!
+//! Objects that appear more than once in the stream, see [`Field::Shared`], are written where
+//! they first appear with an extra `"$id"` key, and everywhere else as `{ "$ref": id }`.
+//! `{ "$ref": 1 }` refers to the root. Shared object arrays are written in the same form as
+//! primitive arrays, `{ "$array": "Object", "$id": 2, "$items": [...] }`, and shared strings as
+//! `{ "$String": "text", "$id": 3 }`.
+
+use crate::{
+    array::PrimitiveArray,
+    enums::{Primitive, PrimitiveType},
+    stream::{Class, ClassArray, Field, Stream},
+    time::{DateTime, DateTimeKind, TimeSpan},
+};
+use indexmap::IndexMap;
+use serde_json::{json, Map, Number, Value};
+use thiserror::Error;
+
+/// A JSON value that does not describe a stream, with the path of the member it was found at.
+#[derive(Error, Debug)]
+#[error("{path}: {message}")]
+pub struct JsonError {
+    pub path: String,
+    pub message: String,
+}
+
+const TYPE: &str = "$type";
+const LIBRARY: &str = "$library";
+const ID: &str = "$id";
+const REFERENCE: &str = "$ref";
+const ARRAY: &str = "$array";
+const ITEMS: &str = "$items";
+
+impl Stream {
+    pub fn to_json(&self) -> Value {
+        class_to_json(&self.root, None)
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, JsonError> {
+        let root = match value {
+            Value::Object(object) if object.contains_key(TYPE) => class_from_json(object, "root")?,
+            _ => return Err(error("root", "expected a class")),
+        };
+
+        match root {
+            Field::Class(root) => Ok(Self { root }),
+            Field::Shared(Self::ROOT_ID, root) => match *root {
+                Field::Class(root) => Ok(Self { root }),
+                _ => unreachable!(),
+            },
+            _ => Err(error(
+                "root",
+                format!("the root's {ID} must be {}", Self::ROOT_ID),
+            )),
+        }
+    }
+}
+
+impl Primitive {
+    /// The value as plain JSON, without its type, as it is written for the elements of a
+    /// primitive array.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Primitive::Boolean(value) => (*value).into(),
+            Primitive::Byte(value) => (*value).into(),
+            Primitive::Char(value) => value.to_string().into(),
+            Primitive::Decimal(value) => value.clone().into(),
+            Primitive::Double(value) => float_to_json(*value),
+            Primitive::Int16(value) => (*value).into(),
+            Primitive::Int32(value) => (*value).into(),
+            Primitive::Int64(value) => (*value).into(),
+            Primitive::SByte(value) => (*value).into(),
+            // The shortest decimal form of the single, so that 0.1 is not written as
+            // 0.10000000149011612.
+            Primitive::Single(value) => float_to_json(value.to_string().parse().unwrap()),
+            Primitive::TimeSpan(value) => value.ticks.into(),
+            Primitive::DateTime(value) => json!({
+                "ticks": value.ticks,
+                "kind": format!("{:?}", value.kind),
+            }),
+            Primitive::UInt16(value) => (*value).into(),
+            Primitive::UInt32(value) => (*value).into(),
+            Primitive::UInt64(value) => (*value).into(),
+            Primitive::Null => Value::Null,
+            Primitive::String(value) => value.clone().into(),
+        }
+    }
+}
+
+fn error(path: &str, message: impl Into<String>) -> JsonError {
+    JsonError {
+        path: path.to_owned(),
+        message: message.into(),
+    }
+}
+
+fn class_to_json(class: &Class, id: Option<i32>) -> Value {
+    let mut object = Map::new();
+
+    object.insert(TYPE.into(), class.name.clone().into());
+
+    if let Some(library_name) = &class.library_name {
+        object.insert(LIBRARY.into(), library_name.clone().into());
+    }
+
+    if let Some(id) = id {
+        object.insert(ID.into(), id.into());
+    }
+
+    for (name, field) in &class.fields {
+        let name = if name.starts_with('$') {
+            format!("${name}")
+        } else {
+            name.clone()
+        };
+
+        object.insert(name, field_to_json(field));
+    }
+
+    Value::Object(object)
+}
+
+fn field_to_json(field: &Field) -> Value {
+    match field {
+        Field::Primitive(primitive) => primitive_to_json(primitive),
+        Field::PrimitiveArray(array) => array_to_json(array, None),
+        Field::StreamedArray(array) => array_to_json(&array.to_primitive_array(), None),
+        Field::Class(class) => class_to_json(class, None),
+        Field::ObjectArray(elements) => elements.iter().map(field_to_json).collect(),
+        Field::ClassArray(array) => class_array_to_json(array, None),
+        Field::Shared(id, value) => match value.as_ref() {
+            Field::Class(class) => class_to_json(class, Some(*id)),
+            Field::PrimitiveArray(array) => array_to_json(array, Some(*id)),
+            Field::StreamedArray(array) => array_to_json(&array.to_primitive_array(), Some(*id)),
+            Field::ObjectArray(elements) => json!({
+                ARRAY: "Object",
+                ID: id,
+                ITEMS: elements.iter().map(field_to_json).collect::<Vec<_>>(),
+            }),
+            Field::ClassArray(array) => class_array_to_json(array, Some(*id)),
+            Field::Primitive(Primitive::String(value)) => json!({ "$String": value, ID: id }),
+            other => field_to_json(other),
+        },
+        Field::Reference(id) => json!({ REFERENCE: id }),
+        Field::Null => Value::Null,
+    }
+}
+
+fn class_array_to_json(array: &ClassArray, id: Option<i32>) -> Value {
+    let mut object = Map::new();
+
+    object.insert(ARRAY.into(), array.name.clone().into());
+
+    if let Some(library_name) = &array.library_name {
+        object.insert(LIBRARY.into(), library_name.clone().into());
+    }
+
+    if let Some(id) = id {
+        object.insert(ID.into(), id.into());
+    }
+
+    object.insert(
+        ITEMS.into(),
+        array.elements.iter().map(field_to_json).collect(),
+    );
+    Value::Object(object)
+}
+
+fn primitive_to_json(primitive: &Primitive) -> Value {
+    match primitive {
+        Primitive::Boolean(_) | Primitive::Int32(_) | Primitive::Null | Primitive::String(_) => {
+            primitive.to_json()
+        }
+        Primitive::Double(value) if value.is_finite() => primitive.to_json(),
+        other => {
+            let mut object = Map::new();
+
+            object.insert(format!("${:?}", other.get_type()), primitive.to_json());
+            Value::Object(object)
+        }
+    }
+}
+
+fn array_to_json(array: &PrimitiveArray, id: Option<i32>) -> Value {
+    let mut object = Map::new();
+
+    object.insert(ARRAY.into(), format!("{:?}", array.get_type()).into());
+
+    if let Some(id) = id {
+        object.insert(ID.into(), id.into());
+    }
+
+    object.insert(
+        ITEMS.into(),
+        Vec::<Primitive>::from(array.clone())
+            .iter()
+            .map(Primitive::to_json)
+            .collect(),
+    );
+    Value::Object(object)
+}
+
+fn float_to_json(value: f64) -> Value {
+    match Number::from_f64(value) {
+        Some(number) => Value::Number(number),
+        None if value.is_nan() => "NaN".into(),
+        None if value > 0.0 => "Infinity".into(),
+        None => "-Infinity".into(),
+    }
+}
+
+fn field_from_json(value: &Value, path: &str) -> Result<Field, JsonError> {
+    Ok(match value {
+        Value::Null => Field::Null,
+        Value::Bool(value) => Field::Primitive(Primitive::Boolean(*value)),
+        Value::Number(number) if number.is_f64() => {
+            Field::Primitive(Primitive::Double(number.as_f64().unwrap()))
+        }
+        Value::Number(number) => {
+            match number.as_i64().and_then(|value| i32::try_from(value).ok()) {
+                Some(value) => Field::Primitive(Primitive::Int32(value)),
+                None => {
+                    return Err(error(
+                        path,
+                        format!(
+                        "{number} is too large for an Int32, write it as {{\"$Int64\": {number}}}"
+                    ),
+                    ))
+                }
+            }
+        }
+        Value::String(value) => Field::Primitive(Primitive::String(value.clone())),
+        Value::Array(elements) => Field::ObjectArray(elements_from_json(elements, path)?),
+        Value::Object(object) if object.contains_key(REFERENCE) => {
+            if object.len() > 1 {
+                return Err(error(path, format!("{REFERENCE} cannot have other keys")));
+            }
+
+            Field::Reference(id_from_json(&object[REFERENCE], path)?)
+        }
+        Value::Object(object) if object.contains_key(TYPE) => class_from_json(object, path)?,
+        Value::Object(object) if object.contains_key(ARRAY) => array_from_json(object, path)?,
+        Value::Object(object) => tagged_from_json(object, path)?,
+    })
+}
+
+fn elements_from_json(elements: &[Value], path: &str) -> Result<Vec<Field>, JsonError> {
+    elements
+        .iter()
+        .enumerate()
+        .map(|(index, element)| field_from_json(element, &format!("{path}[{index}]")))
+        .collect()
+}
+
+fn id_from_json(value: &Value, path: &str) -> Result<i32, JsonError> {
+    value
+        .as_i64()
+        .and_then(|id| i32::try_from(id).ok())
+        .ok_or_else(|| error(path, format!("expected an object id, found {value}")))
+}
+
+// Wraps the object if it has an id.
+fn shared(object: &Map<String, Value>, field: Field, path: &str) -> Result<Field, JsonError> {
+    Ok(match object.get(ID) {
+        Some(id) => Field::Shared(id_from_json(id, path)?, Box::new(field)),
+        None => field,
+    })
+}
+
+fn class_from_json(object: &Map<String, Value>, path: &str) -> Result<Field, JsonError> {
+    let name = match &object[TYPE] {
+        Value::String(name) => name.clone(),
+        other => return Err(error(path, format!("expected a type name, found {other}"))),
+    };
+    let library_name = library_from_json(object, path)?;
+    let mut fields = IndexMap::with_capacity(object.len());
+
+    for (key, value) in object {
+        let name = match key.strip_prefix('$') {
+            None => key.as_str(),
+            Some(name) if name.starts_with('$') => name,
+            Some(_) if [TYPE, LIBRARY, ID].contains(&key.as_str()) => continue,
+            Some(_) => return Err(error(path, format!("unknown key {key}"))),
+        };
+
+        fields.insert(
+            name.to_owned(),
+            field_from_json(value, &format!("{path}.{name}"))?,
+        );
+    }
+
+    shared(
+        object,
+        Field::Class(Class {
+            library_name,
+            name,
+            fields,
+        }),
+        path,
+    )
+}
+
+fn library_from_json(object: &Map<String, Value>, path: &str) -> Result<Option<String>, JsonError> {
+    match object.get(LIBRARY) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(library_name)) => Ok(Some(library_name.clone())),
+        Some(other) => Err(error(
+            path,
+            format!("expected a library name, found {other}"),
+        )),
+    }
+}
+
+fn array_from_json(object: &Map<String, Value>, path: &str) -> Result<Field, JsonError> {
+    let items = match object.get(ITEMS) {
+        Some(Value::Array(items)) => items,
+        _ => return Err(error(path, format!("expected {ITEMS} to be an array"))),
+    };
+
+    if let Some(key) = object
+        .keys()
+        .find(|key| ![ARRAY, LIBRARY, ID, ITEMS].contains(&key.as_str()))
+    {
+        return Err(error(path, format!("unknown key {key}")));
+    }
+
+    let class_array = object[ARRAY]
+        .as_str()
+        .is_some_and(|name| name != "Object" && primitive_type(name).is_none());
+
+    if object.contains_key(LIBRARY) && !class_array {
+        return Err(error(
+            path,
+            format!("only class arrays can have a {LIBRARY}"),
+        ));
+    }
+
+    let field = match object[ARRAY].as_str() {
+        Some("Object") => Field::ObjectArray(elements_from_json(items, path)?),
+        Some(name) if class_array => Field::ClassArray(ClassArray {
+            library_name: library_from_json(object, path)?,
+            name: name.to_owned(),
+            elements: elements_from_json(items, path)?,
+        }),
+        Some(name) => {
+            let primitive_type = primitive_type(name)
+                .ok_or_else(|| error(path, format!("unknown array type {name}")))?;
+            let elements = items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    let path = format!("{path}[{index}]");
+
+                    match (primitive_type, item) {
+                        (PrimitiveType::String, Value::Null) => Ok(Primitive::Null),
+                        _ => primitive_from_json(primitive_type, item, &path),
+                    }
+                })
+                .collect::<Result<_, _>>()?;
+
+            Field::PrimitiveArray(PrimitiveArray::into_field(elements, primitive_type))
+        }
+        None => return Err(error(path, format!("expected {ARRAY} to be a type name"))),
+    };
+
+    shared(object, field, path)
+}
+
+fn tagged_from_json(object: &Map<String, Value>, path: &str) -> Result<Field, JsonError> {
+    let mut tags = object.iter().filter(|(key, _)| key.as_str() != ID);
+    let (Some((tag, value)), None) = (tags.next(), tags.next()) else {
+        return Err(error(
+            path,
+            format!("expected a value, found {}", Value::Object(object.clone())),
+        ));
+    };
+    let primitive_type = tag
+        .strip_prefix('$')
+        .and_then(primitive_type)
+        .ok_or_else(|| error(path, format!("unknown key {tag}")))?;
+    let field = Field::Primitive(primitive_from_json(primitive_type, value, path)?);
+
+    if object.contains_key(ID) && primitive_type != PrimitiveType::String {
+        return Err(error(
+            path,
+            format!("{primitive_type:?} values cannot be shared"),
+        ));
+    }
+
+    shared(object, field, path)
+}
+
+fn primitive_type(name: &str) -> Option<PrimitiveType> {
+    Some(match name {
+        "Boolean" => PrimitiveType::Boolean,
+        "Byte" => PrimitiveType::Byte,
+        "Char" => PrimitiveType::Char,
+        "Decimal" => PrimitiveType::Decimal,
+        "Double" => PrimitiveType::Double,
+        "Int16" => PrimitiveType::Int16,
+        "Int32" => PrimitiveType::Int32,
+        "Int64" => PrimitiveType::Int64,
+        "SByte" => PrimitiveType::SByte,
+        "Single" => PrimitiveType::Single,
+        "TimeSpan" => PrimitiveType::TimeSpan,
+        "DateTime" => PrimitiveType::DateTime,
+        "UInt16" => PrimitiveType::UInt16,
+        "UInt32" => PrimitiveType::UInt32,
+        "UInt64" => PrimitiveType::UInt64,
+        "Null" => PrimitiveType::Null,
+        "String" => PrimitiveType::String,
+        _ => return None,
+    })
+}
+
+// Reads a primitive written without its type.
+fn primitive_from_json(
+    primitive_type: PrimitiveType,
+    value: &Value,
+    path: &str,
+) -> Result<Primitive, JsonError> {
+    let mismatch = || error(path, format!("expected {primitive_type:?}, found {value}"));
+
+    Ok(match primitive_type {
+        PrimitiveType::Boolean => Primitive::Boolean(value.as_bool().ok_or_else(mismatch)?),
+        PrimitiveType::Byte => Primitive::Byte(integer(value).ok_or_else(mismatch)?),
+        PrimitiveType::Char => {
+            let mut chars = value.as_str().ok_or_else(mismatch)?.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(value), None) => Primitive::Char(value),
+                _ => return Err(mismatch()),
+            }
+        }
+        PrimitiveType::Decimal => Primitive::Decimal(value.as_str().ok_or_else(mismatch)?.into()),
+        PrimitiveType::Double => Primitive::Double(float(value).ok_or_else(mismatch)?),
+        PrimitiveType::Int16 => Primitive::Int16(integer(value).ok_or_else(mismatch)?),
+        PrimitiveType::Int32 => Primitive::Int32(integer(value).ok_or_else(mismatch)?),
+        PrimitiveType::Int64 => Primitive::Int64(integer(value).ok_or_else(mismatch)?),
+        PrimitiveType::SByte => Primitive::SByte(integer(value).ok_or_else(mismatch)?),
+        // Parsed from the decimal form as written, so that it rounds straight to a single.
+        PrimitiveType::Single => Primitive::Single(match value {
+            Value::Number(number) => number.to_string().parse().map_err(|_| mismatch())?,
+            other => float(other).ok_or_else(mismatch)? as f32,
+        }),
+        PrimitiveType::TimeSpan => {
+            Primitive::TimeSpan(TimeSpan::new(integer(value).ok_or_else(mismatch)?))
+        }
+        PrimitiveType::DateTime => {
+            let ticks = value
+                .get("ticks")
+                .and_then(integer)
+                .filter(|ticks| (0..=DateTime::MAX_TICKS).contains(ticks))
+                .ok_or_else(mismatch)?;
+            let kind = match value.get("kind").and_then(Value::as_str) {
+                Some("Unspecified") => DateTimeKind::Unspecified,
+                Some("Utc") => DateTimeKind::Utc,
+                Some("Local") => DateTimeKind::Local,
+                Some("LocalAmbiguousDst") => DateTimeKind::LocalAmbiguousDst,
+                _ => return Err(mismatch()),
+            };
+
+            Primitive::DateTime(DateTime::new(ticks, kind))
+        }
+        PrimitiveType::UInt16 => Primitive::UInt16(integer(value).ok_or_else(mismatch)?),
+        PrimitiveType::UInt32 => Primitive::UInt32(integer(value).ok_or_else(mismatch)?),
+        PrimitiveType::UInt64 => Primitive::UInt64(integer(value).ok_or_else(mismatch)?),
+        PrimitiveType::Null => match value {
+            Value::Null => Primitive::Null,
+            _ => return Err(mismatch()),
+        },
+        PrimitiveType::String => Primitive::String(value.as_str().ok_or_else(mismatch)?.into()),
+    })
+}
+
+fn integer<T: TryFrom<i64> + TryFrom<u64>>(value: &Value) -> Option<T> {
+    match value.as_i64() {
+        Some(value) => T::try_from(value).ok(),
+        None => T::try_from(value.as_u64()?).ok(),
+    }
+}
+
+fn float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(value) => match value.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,444 @@
+use crate::{
+    array::PrimitiveArray,
+    common::{ArrayInfo, ClassInfo, MemberTypeInfo},
+    enums::{AdditionalInfo, Primitive, PrimitiveType, Record, RecordType},
+    parse::{
+        parse_count, Parse, ParseContext, ParseError, ParseSized, ParseTyped, ParseWithContext,
+    },
+    records::{
+        read_elements, read_references, BinaryArray, BinaryMethodCall, BinaryMethodReturn,
+        BinaryObjectString, ReadMember,
+    },
+    stream::{Field, StreamDecoder},
+};
+use indexmap::IndexMap;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+/// A class read on its own, with the objects it refers to left unread.
+#[derive(Debug, Clone)]
+pub struct LazyClass {
+    pub library_name: Option<String>,
+    pub name: String,
+    pub fields: IndexMap<String, LazyField>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LazyField {
+    Primitive(Primitive),
+    /// Another object, read with [`LazyStream::get`] or [`LazyStream::load`].
+    Object(i32),
+    Null,
+}
+
+#[derive(Debug, Clone)]
+pub enum LazyObject {
+    Class(LazyClass),
+    String(String),
+    PrimitiveArray(PrimitiveArray),
+    ObjectArray(Vec<LazyField>),
+}
+
+/// Reads objects out of a stream only when they are asked for.
+///
+/// Opening the stream scans it once to find the byte range of each object's record, skipping
+/// over the data of fixed-width primitive arrays instead of reading it, including arrays
+/// written inside other records. Readers without their own buffering, like `File`, should be
+/// wrapped in a `BufReader`.
+pub struct LazyStream<R: Read + Seek> {
+    reader: R,
+    // Objects written inline share the range of the record that contains them.
+    index: HashMap<i32, Range<u64>>,
+    context: ParseContext,
+    definitions: StreamDecoder,
+}
+
+// What the scan keeps of a record in member position, which is just enough to follow the
+// member grammar.
+enum Scanned {
+    Value,
+    Nulls(usize),
+    Library,
+    End,
+}
+
+// Keeps track of the position while scanning, so that the reader is never asked for it.
+struct Tracked<'r, R> {
+    reader: &'r mut R,
+    position: u64,
+}
+
+impl<R: Read> Read for Tracked<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Tracked<'_, R> {
+    // Moves past the elements of a primitive array, only reading them when their size varies.
+    fn skip_primitive_array(
+        &mut self,
+        primitive_type: PrimitiveType,
+        length: usize,
+    ) -> Result<(), ParseError> {
+        let Some(element_size) = PrimitiveArray::element_size(primitive_type) else {
+            PrimitiveArray::read_from(self, primitive_type, length)?;
+            return Ok(());
+        };
+        let byte_length = i64::try_from(length.saturating_mul(element_size))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "array too large"))?;
+
+        self.reader.seek(SeekFrom::Current(byte_length))?;
+        self.position += byte_length as u64;
+        Ok(())
+    }
+}
+
+// Reads the stream once when it is opened, noting where each object is and defining its
+// libraries and classes. Records are followed with the grammar the `Record` parsers use, so
+// that primitive arrays are skipped wherever they are written, even inside other records.
+#[derive(Default)]
+struct Scanner {
+    index: HashMap<i32, Range<u64>>,
+    context: ParseContext,
+    definitions: StreamDecoder,
+    // The objects in the record being scanned, which all share its range.
+    objects: Vec<i32>,
+}
+
+impl<'r, R: Read + Seek> ReadMember<Tracked<'r, R>> for Scanner {
+    type Member = Scanned;
+
+    fn read_primitive(
+        &mut self,
+        reader: &mut Tracked<'r, R>,
+        primitive_type: PrimitiveType,
+    ) -> Result<Scanned, ParseError> {
+        reader.parse_typed::<Primitive>(primitive_type)?;
+        Ok(Scanned::Value)
+    }
+
+    fn read_member(&mut self, reader: &mut Tracked<'r, R>) -> Result<Scanned, ParseError> {
+        let record_type = reader.parse()?;
+        self.scan(reader, record_type)
+    }
+
+    fn element_count(member: &Scanned) -> usize {
+        match member {
+            Scanned::Nulls(null_count) => *null_count,
+            Scanned::Library => 0,
+            _ => 1,
+        }
+    }
+
+    fn is_library(member: &Scanned) -> bool {
+        matches!(member, Scanned::Library)
+    }
+}
+
+impl Scanner {
+    fn scan<R: Read + Seek>(
+        &mut self,
+        reader: &mut Tracked<'_, R>,
+        record_type: RecordType,
+    ) -> Result<Scanned, ParseError> {
+        Ok(match record_type {
+            RecordType::SerializedStreamHeader => {
+                let header = Record::SerializationHeader(reader.parse()?);
+
+                self.definitions.define(&header)?;
+                Scanned::Value
+            }
+            RecordType::BinaryLibrary => {
+                let library = Record::BinaryLibrary(reader.parse()?);
+
+                self.definitions.define(&library)?;
+                Scanned::Library
+            }
+            RecordType::ClassWithMembersAndTypes
+            | RecordType::SystemClassWithMembersAndTypes
+            | RecordType::ClassWithMembers
+            | RecordType::SystemClassWithMembers => {
+                let class_info: ClassInfo = reader.parse()?;
+                let member_type_info: MemberTypeInfo = match record_type {
+                    RecordType::ClassWithMembersAndTypes
+                    | RecordType::SystemClassWithMembersAndTypes => {
+                        reader.parse_sized(class_info.member_names.len())?
+                    }
+                    _ => MemberTypeInfo::untyped(class_info.member_names.len()),
+                };
+                let library_id = match record_type {
+                    RecordType::ClassWithMembersAndTypes | RecordType::ClassWithMembers => {
+                        Some(reader.parse()?)
+                    }
+                    _ => None,
+                };
+
+                self.objects.push(class_info.object_id);
+                self.context
+                    .member_type_infos
+                    .insert(class_info.object_id, member_type_info.clone());
+                self.definitions.define_class(class_info, library_id)?;
+                read_references(reader, &member_type_info, self)?;
+                Scanned::Value
+            }
+            RecordType::ClassWithId => {
+                let object_id = reader.parse()?;
+                let metadata_id = reader.parse()?;
+                let member_type_info = self
+                    .context
+                    .member_type_infos
+                    .get(&metadata_id)
+                    .ok_or(ParseError::MissingMetadata(metadata_id))?
+                    .clone();
+
+                self.objects.push(object_id);
+                read_references(reader, &member_type_info, self)?;
+                Scanned::Value
+            }
+            RecordType::BinaryObjectString => {
+                let string: BinaryObjectString = reader.parse()?;
+
+                self.objects.push(string.object_id);
+                Scanned::Value
+            }
+            RecordType::ArraySinglePrimitive => {
+                let array_info: ArrayInfo = reader.parse()?;
+                let primitive_type = reader.parse()?;
+
+                self.objects.push(array_info.object_id);
+                reader.skip_primitive_array(primitive_type, array_info.length as usize)?;
+                Scanned::Value
+            }
+            RecordType::ArraySingleObject | RecordType::ArraySingleString => {
+                let array_info: ArrayInfo = reader.parse()?;
+
+                self.objects.push(array_info.object_id);
+                read_elements(reader, array_info.length as usize, None, self)?;
+                Scanned::Value
+            }
+            RecordType::BinaryArray => {
+                let (array, length) = BinaryArray::parse_header(reader)?;
+
+                self.objects.push(array.object_id);
+
+                match array.additional_info {
+                    Some(AdditionalInfo::Primitive(primitive_type)) => {
+                        reader.skip_primitive_array(primitive_type, length)?
+                    }
+                    additional_info => {
+                        read_elements(reader, length, additional_info.as_ref(), self)?;
+                    }
+                }
+
+                Scanned::Value
+            }
+            RecordType::MemberTypedPrimitive => {
+                let primitive_type = reader.parse()?;
+
+                reader.parse_typed::<Primitive>(primitive_type)?;
+                Scanned::Value
+            }
+            RecordType::MemberReference => {
+                reader.parse::<i32>()?;
+                Scanned::Value
+            }
+            RecordType::ObjectNull => Scanned::Nulls(1),
+            RecordType::ObjectNullMultiple256 => Scanned::Nulls(reader.parse::<u8>()? as usize),
+            RecordType::ObjectNullMultiple => Scanned::Nulls(parse_count(reader)?),
+            RecordType::MethodCall => {
+                reader.parse::<BinaryMethodCall>()?;
+                Scanned::Value
+            }
+            RecordType::MethodReturn => {
+                reader.parse::<BinaryMethodReturn>()?;
+                Scanned::Value
+            }
+            RecordType::MessageEnd => Scanned::End,
+        })
+    }
+}
+
+impl<R: Read + Seek> LazyStream<R> {
+    pub fn open(mut reader: R) -> Result<Self, ParseError> {
+        let position = reader.stream_position()?;
+        let mut tracked = Tracked {
+            reader: &mut reader,
+            position,
+        };
+        let mut scanner = Scanner::default();
+
+        loop {
+            let start = tracked.position;
+            let record_type = tracked.parse()?;
+
+            if let Scanned::End = scanner.scan(&mut tracked, record_type)? {
+                break;
+            }
+
+            for object_id in scanner.objects.drain(..) {
+                scanner.index.insert(object_id, start..tracked.position);
+            }
+        }
+
+        scanner.definitions.root_id()?;
+
+        Ok(Self {
+            reader,
+            index: scanner.index,
+            context: scanner.context,
+            definitions: scanner.definitions,
+        })
+    }
+
+    pub fn root_id(&self) -> Result<i32, ParseError> {
+        self.definitions.root_id()
+    }
+
+    pub fn root(&mut self) -> Result<LazyClass, ParseError> {
+        let root_id = self.root_id()?;
+
+        match self.get(root_id)? {
+            LazyObject::Class(class) => Ok(class),
+            _ => Err(ParseError::MissingObject(root_id)),
+        }
+    }
+
+    /// Byte range of the record an object is stored in.
+    pub fn range(&self, id: i32) -> Option<Range<u64>> {
+        self.index.get(&id).cloned()
+    }
+
+    /// Decodes a single object, leaving the objects it refers to as [`LazyField::Object`].
+    pub fn get(&mut self, id: i32) -> Result<LazyObject, ParseError> {
+        let record = self.read(id)?;
+        let record = if record.object_id() == Some(id) {
+            record
+        } else {
+            find(&record, id)
+                .ok_or(ParseError::MissingObject(id))?
+                .clone()
+        };
+
+        Ok(match record {
+            Record::BinaryObjectString(string) => LazyObject::String(string.value),
+            Record::ArraySinglePrimitive(array) => LazyObject::PrimitiveArray(array.members),
+            Record::ArraySingleObject(_)
+            | Record::ArraySingleString(_)
+            | Record::BinaryArray(_) => LazyObject::ObjectArray(lazy_elements(record.members())),
+            Record::ClassWithId(class) => {
+                LazyObject::Class(self.lazy_class(class.metadata_id, &class.member_references)?)
+            }
+            other => match other.object_id() {
+                Some(object_id) => LazyObject::Class(self.lazy_class(object_id, other.members())?),
+                None => return Err(ParseError::UnexpectedRecord(other.record_type())),
+            },
+        })
+    }
+
+    /// Decodes an object together with everything it refers to.
+    pub fn load(&mut self, id: i32) -> Result<Field, ParseError> {
+        let mut decoder = self.definitions.definitions();
+        let mut pending = vec![id];
+        let mut read = HashSet::new();
+
+        while let Some(id) = pending.pop() {
+            if !read.insert(self.range(id).ok_or(ParseError::MissingObject(id))?.start) {
+                continue;
+            }
+
+            let record = self.read(id)?;
+
+            references(&record, &mut pending);
+            decoder.push(record)?;
+        }
+
+        decoder.resolve(id, false)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read(&mut self, id: i32) -> Result<Record, ParseError> {
+        let range = self.range(id).ok_or(ParseError::MissingObject(id))?;
+
+        self.reader.seek(SeekFrom::Start(range.start))?;
+        self.reader.parse_with(&mut self.context)
+    }
+
+    fn lazy_class(&self, metadata_id: i32, members: &[Record]) -> Result<LazyClass, ParseError> {
+        let metadata = self.definitions.metadata(metadata_id)?;
+
+        Ok(LazyClass {
+            library_name: metadata.library_name.clone(),
+            name: metadata.name.clone(),
+            fields: metadata
+                .member_names
+                .iter()
+                .cloned()
+                .zip(
+                    members
+                        .iter()
+                        .filter(|member| !matches!(member, Record::BinaryLibrary(_)))
+                        .map(lazy_field),
+                )
+                .collect(),
+        })
+    }
+}
+
+fn find(record: &Record, id: i32) -> Option<&Record> {
+    if record.object_id() == Some(id) {
+        return Some(record);
+    }
+
+    record.members().iter().find_map(|member| find(member, id))
+}
+
+fn references(record: &Record, ids: &mut Vec<i32>) {
+    for member in record.members() {
+        match member {
+            Record::MemberReference { id } => ids.push(*id),
+            other => references(other, ids),
+        }
+    }
+}
+
+fn lazy_field(member: &Record) -> LazyField {
+    match member {
+        Record::MemberPrimitiveUnTyped(value) | Record::MemberTypedPrimitive { value } => {
+            LazyField::Primitive(value.clone())
+        }
+        Record::BinaryObjectString(string) => {
+            LazyField::Primitive(Primitive::String(string.value.clone()))
+        }
+        Record::MemberReference { id } => LazyField::Object(*id),
+        other => other.object_id().map_or(LazyField::Null, LazyField::Object),
+    }
+}
+
+fn lazy_elements(members: &[Record]) -> Vec<LazyField> {
+    let mut elements = vec![];
+
+    for member in members {
+        match member {
+            Record::ObjectNullMultiple256 { null_count } => {
+                elements.extend((0..*null_count).map(|_| LazyField::Null))
+            }
+            Record::ObjectNullMultiple { null_count } => {
+                elements.extend((0..*null_count).map(|_| LazyField::Null))
+            }
+            // Defined when the stream was opened.
+            Record::BinaryLibrary(_) => {}
+            other => elements.push(lazy_field(other)),
+        }
+    }
+
+    elements
+}
@@ -1,9 +1,76 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub(crate) mod array;
+#[cfg(feature = "std")]
+pub(crate) mod borrowed;
 pub(crate) mod common;
+#[cfg(feature = "std")]
+pub(crate) mod convert;
+#[cfg(feature = "serde")]
+pub(crate) mod de;
+#[cfg(feature = "std")]
+pub(crate) mod diff;
+#[cfg(feature = "std")]
+pub(crate) mod dot;
 pub(crate) mod enums;
+pub mod io;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "std")]
+pub(crate) mod lazy;
 pub(crate) mod parse;
-pub(crate) mod records;
+#[cfg(feature = "std")]
+pub(crate) mod path;
+#[cfg(feature = "std")]
+pub(crate) mod query;
+pub(crate) mod reader;
+pub mod records;
+#[cfg(feature = "serde")]
+pub(crate) mod ser;
+#[cfg(feature = "std")]
 pub(crate) mod stream;
+pub(crate) mod time;
 pub(crate) mod unparse;
+#[cfg(feature = "std")]
+pub(crate) mod visit;
+pub(crate) mod writer;
 
+pub use array::PrimitiveArray;
+#[cfg(feature = "std")]
+pub use borrowed::{BorrowedClass, BorrowedClassArray, BorrowedField, BorrowedStream};
+#[cfg(feature = "std")]
+pub use convert::{ConvertError, NrbfClass, NrbfField};
+#[cfg(feature = "serde")]
+pub use de::{from_reader, from_stream, DeserializeError};
+#[cfg(feature = "std")]
+pub use diff::Change;
+#[cfg(feature = "std")]
+pub use dot::DotOptions;
 pub use enums::Primitive;
-pub use stream::{Class, Field, PrimitiveArray, Stream};
+#[cfg(feature = "json")]
+pub use json::JsonError;
+#[cfg(feature = "std")]
+pub use lazy::{LazyClass, LazyField, LazyObject, LazyStream};
+#[cfg(feature = "derive")]
+pub use ms_nrbf_derive::NrbfClass;
+pub use parse::ParseError;
+#[cfg(feature = "std")]
+pub use path::PathError;
+#[cfg(feature = "std")]
+pub use query::{Match, Query, QueryError};
+#[cfg(feature = "async")]
+pub use reader::AsyncRecordReader;
+pub use reader::RecordReader;
+#[cfg(feature = "serde")]
+pub use ser::{to_stream, to_writer, to_writer_with, Naming, SerializeError};
+#[cfg(feature = "std")]
+pub use stream::{
+    ArrayElement, Class, ClassArray, DecodeOptions, EncodeOptions, Field, LibraryLayout, Stream,
+    StreamedArray,
+};
+pub use time::{DateTime, DateTimeKind, OutOfRangeError, TimeSpan};
+#[cfg(feature = "std")]
+pub use visit::{VisitContext, Visitor, VisitorMut};
+pub use writer::RecordWriter;
@@ -1,10 +1,16 @@
-use crate::enums::{BinaryArrayType, BinaryType, PrimitiveType, RecordType};
-use chrono::{NaiveDateTime, NaiveTime};
-use num_enum::TryFromPrimitiveError;
-use std::{
+use crate::{
+    common::MemberTypeInfo,
+    enums::{BinaryArrayType, BinaryType, PrimitiveType, RecordType},
     io::{self, Read},
-    string::FromUtf8Error,
 };
+use alloc::{
+    collections::BTreeMap,
+    string::{FromUtf8Error, String},
+    vec,
+    vec::Vec,
+};
+use core::str::Utf8Error;
+use num_enum::TryFromPrimitiveError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,6 +19,8 @@ pub enum ParseError {
     IoError(#[from] io::Error),
     #[error("failed to parse string")]
     StringError(#[from] FromUtf8Error),
+    #[error("failed to parse string")]
+    StrError(#[from] Utf8Error),
     #[error("failed to parse primitive type")]
     InvalidPrimitiveType(#[from] TryFromPrimitiveError<PrimitiveType>),
     #[error("failed to parse binary type")]
@@ -27,8 +35,31 @@ pub enum ParseError {
     InvalidTimeSpan,
     #[error("failed to parse datetime")]
     InvalidDateTime,
+    #[error("invalid count: {0}")]
+    InvalidCount(i32),
+    #[error("more elements than the array length of {0}")]
+    TooManyElements(usize),
+    #[error("member {0} is declared more than once")]
+    DuplicateMember(String),
     #[error("not enough info to parse: {0:?}")]
     NotEnoughInfo(RecordType),
+    #[error("missing class metadata for object {0}")]
+    MissingMetadata(i32),
+    #[error("missing serialization header")]
+    MissingHeader,
+    #[error("missing object {0}")]
+    MissingObject(i32),
+    #[error("missing library {0}")]
+    MissingLibrary(i32),
+    #[error("unexpected record: {0:?}")]
+    UnexpectedRecord(RecordType),
+    #[error("unexpected binary type: {0:?}")]
+    UnexpectedBinaryType(BinaryType),
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ParseContext {
+    pub member_type_infos: BTreeMap<i32, MemberTypeInfo>,
 }
 
 pub(crate) trait ParseFrom<R: Read>
@@ -38,6 +69,13 @@ where
     fn parse_from(reader: &mut R) -> Result<Self, ParseError>;
 }
 
+pub(crate) trait ParseFromContext<R: Read>
+where
+    Self: Sized,
+{
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError>;
+}
+
 pub(crate) trait ParseFromSized<R: Read>
 where
     Self: Sized,
@@ -122,66 +160,55 @@ impl<R: Read> ParseFrom<R> for f64 {
 
 impl<R: Read> ParseFrom<R> for char {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
-        let mut byte: u32 = reader.parse::<u8>()? as u32;
-
-        let iterations = {
-            if byte & 0x80 == 0 {
-                0
-            } else if byte & 0xE0 == 0xC0 {
-                1
-            } else if byte & 0xF0 == 0xE0 {
-                2
-            } else {
-                3
-            }
+        let mut bytes = [0; 4];
+        bytes[0] = reader.parse()?;
+
+        let length = match bytes[0] {
+            byte if byte & 0x80 == 0 => 1,
+            byte if byte & 0xE0 == 0xC0 => 2,
+            byte if byte & 0xF0 == 0xE0 => 3,
+            _ => 4,
         };
 
-        for _ in 0..iterations {
-            byte <<= 8;
-            byte |= reader.parse::<u8>()? as u32;
-        }
+        reader.read_exact(&mut bytes[1..length])?;
 
-        char::from_u32(byte).ok_or(ParseError::InvalidChar)
+        core::str::from_utf8(&bytes[..length])
+            .ok()
+            .and_then(|value| value.chars().next())
+            .ok_or(ParseError::InvalidChar)
     }
 }
 
-impl<R: Read> ParseFrom<R> for String {
-    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
-        let mut length: usize = 0;
-
-        for i in 0..5 {
-            let byte = reader.parse::<u8>()?;
-            length += ((byte & 0x7F) << (7 * i)) as usize;
-            if byte & 0x80 == 0 {
-                break;
-            }
-        }
+// Counts are written as `i32`, so a corrupt stream can hold a negative one.
+pub(crate) fn parse_count<R: Read>(reader: &mut R) -> Result<usize, ParseError> {
+    let count: i32 = reader.parse()?;
 
-        let mut string_buf = vec![0; length];
-        reader.read_exact(string_buf.as_mut_slice())?;
-
-        Ok(String::from_utf8(string_buf)?)
-    }
+    usize::try_from(count).map_err(|_| ParseError::InvalidCount(count))
 }
 
-impl<R: Read> ParseFrom<R> for NaiveTime {
-    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
-        let hundred_nanoseconds = (reader.parse::<i64>()?).unsigned_abs();
-        let nano = (hundred_nanoseconds * 100) as u32;
-        let sec = (hundred_nanoseconds / 1000000000) as u32;
-        let min = sec / 60;
-        let hour = min / 60;
+// Strings are prefixed with their length in bytes as a 7-bit encoded integer.
+pub(crate) fn parse_length<R: Read>(reader: &mut R) -> Result<usize, ParseError> {
+    let mut length: usize = 0;
 
-        NaiveTime::from_hms_nano_opt(hour, min, sec, nano).ok_or(ParseError::InvalidTimeSpan)
+    for i in 0..5 {
+        let byte = reader.parse::<u8>()?;
+        length |= ((byte & 0x7F) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            break;
+        }
     }
+
+    Ok(length)
 }
 
-impl<R: Read> ParseFrom<R> for NaiveDateTime {
+impl<R: Read> ParseFrom<R> for String {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
-        let hundred_nanoseconds = (reader.parse::<u64>()? & 0xFFFFFFFFFFFFFFFC) as i64;
+        let length = parse_length(reader)?;
+
+        let mut string_buf = vec![0; length];
+        reader.read_exact(string_buf.as_mut_slice())?;
 
-        NaiveDateTime::from_timestamp_micros(hundred_nanoseconds / 10)
-            .ok_or(ParseError::InvalidDateTime)
+        Ok(String::from_utf8(string_buf)?)
     }
 }
 
@@ -207,6 +234,22 @@ impl<R: Read> Parse<R> for R {
     }
 }
 
+pub(crate) trait ParseWithContext<R: Read> {
+    fn parse_with<T: ParseFromContext<R>>(
+        &mut self,
+        context: &mut ParseContext,
+    ) -> Result<T, ParseError>;
+}
+
+impl<R: Read> ParseWithContext<R> for R {
+    fn parse_with<T: ParseFromContext<R>>(
+        &mut self,
+        context: &mut ParseContext,
+    ) -> Result<T, ParseError> {
+        T::parse_from_context(self, context)
+    }
+}
+
 pub(crate) trait ParseSized<R: Read> {
     fn parse_sized<T: ParseFromSized<R>>(&mut self, size: usize) -> Result<T, ParseError>;
 }
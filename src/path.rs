@@ -0,0 +1,492 @@
+use crate::{
+    array::PrimitiveArray,
+    convert::NrbfField,
+    enums::{Primitive, PrimitiveType},
+    stream::{Class, ClassArray, Field, Stream},
+};
+use std::{borrow::Cow, mem};
+use thiserror::Error;
+
+/// A path that doesn't lead to a value, or a value that can't be stored where it leads.
+///
+/// Paths in errors are cut off after the segment where the problem was found.
+#[derive(Error, Debug)]
+pub enum PathError {
+    #[error("invalid path {0:?}")]
+    Invalid(String),
+    #[error("{path}: no such member")]
+    MissingMember { path: String },
+    #[error("{path}: index out of bounds for an array of length {length}")]
+    OutOfBounds { path: String, length: usize },
+    #[error("{path}: expected {expected}, found {found}")]
+    TypeMismatch {
+        path: String,
+        expected: String,
+        found: String,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Segment<'p> {
+    Member(&'p str),
+    Index(usize),
+}
+
+impl Segment<'_> {
+    // What a value has to be for this segment to lead into it.
+    fn expected(&self) -> String {
+        match self {
+            Self::Member(_) => "a class".to_owned(),
+            Self::Index(_) => "an array".to_owned(),
+        }
+    }
+}
+
+/// A parsed path, with where each segment ends in the text.
+pub(crate) struct Path<'p> {
+    text: &'p str,
+    segments: Vec<(Segment<'p>, usize)>,
+}
+
+// Why a segment couldn't be followed.
+enum Miss {
+    Member,
+    Index { length: usize },
+    // The value is not a class or array that the segment can lead into.
+    Type,
+}
+
+impl<'p> Path<'p> {
+    fn parse(text: &'p str) -> Result<Self, PathError> {
+        let path = Self::parse_relative(text)?;
+
+        match path.segments.first() {
+            Some((Segment::Member(_), _)) => Ok(path),
+            _ => Err(PathError::Invalid(text.to_owned())),
+        }
+    }
+
+    /// Parses a path that is followed from some value rather than from the root, and so can
+    /// start with an index.
+    pub(crate) fn parse_relative(text: &'p str) -> Result<Self, PathError> {
+        let invalid = || PathError::Invalid(text.to_owned());
+        let mut segments = vec![];
+        let mut rest = text;
+
+        while !rest.is_empty() {
+            let segment = if let Some(index) = rest.strip_prefix('[') {
+                let (index, after) = index.split_once(']').ok_or_else(invalid)?;
+
+                rest = after;
+                Segment::Index(index.parse().map_err(|_| invalid())?)
+            } else {
+                let member = if segments.is_empty() {
+                    rest
+                } else {
+                    rest.strip_prefix('.').ok_or_else(invalid)?
+                };
+                let end = member.find(['.', '[', ']']).unwrap_or(member.len());
+
+                if end == 0 {
+                    return Err(invalid());
+                }
+
+                rest = &member[end..];
+                Segment::Member(&member[..end])
+            };
+
+            segments.push((segment, text.len() - rest.len()));
+        }
+
+        if segments.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self { text, segments })
+    }
+
+    // The text of the path up to and including the segment at `index`.
+    fn prefix(&self, index: usize) -> String {
+        self.text[..self.segments[index].1].to_owned()
+    }
+
+    fn missing_member(&self, index: usize) -> PathError {
+        PathError::MissingMember {
+            path: self.prefix(index),
+        }
+    }
+
+    fn out_of_bounds(&self, index: usize, length: usize) -> PathError {
+        PathError::OutOfBounds {
+            path: self.prefix(index),
+            length,
+        }
+    }
+
+    // The value at segment `index` can't be led into by the segment after it.
+    fn mismatch(&self, index: usize, field: &Field) -> PathError {
+        PathError::TypeMismatch {
+            path: self.prefix(index),
+            expected: self.segments[index + 1].0.expected(),
+            found: field.describe(),
+        }
+    }
+
+    // The segment at `index` couldn't be followed from `field`.
+    fn miss(&self, index: usize, miss: Miss, field: &Field) -> PathError {
+        match miss {
+            Miss::Member => self.missing_member(index),
+            Miss::Index { length } => self.out_of_bounds(index, length),
+            Miss::Type => self.mismatch(index - 1, unshared(field)),
+        }
+    }
+}
+
+impl Stream {
+    /// The value at a path. Elements of primitive arrays are returned as owned primitives.
+    ///
+    /// Paths name class members with `.` and array elements with `[index]`, starting from a
+    /// member of the root class, like `Player.Inventory[3].Count`. Unlike the paths of
+    /// [`Change`](crate::Change)s, they don't start with `root`. Shared objects are looked into
+    /// like any other, but [`Field::Reference`]s are not followed.
+    pub fn get(&self, path: &str) -> Result<Cow<'_, Field>, PathError> {
+        let path = Path::parse(path)?;
+        let mut field = Cow::Borrowed(member(&self.root, &path, 0)?);
+
+        for index in 1..path.segments.len() {
+            let next = follow(&field, path.segments[index].0);
+
+            field = next.map_err(|miss| path.miss(index, miss, &field))?;
+        }
+
+        Ok(field)
+    }
+
+    /// Replaces the value at a path, returning the old one. See [`Stream::get`] for the form
+    /// of paths.
+    ///
+    /// A primitive is converted to the type of the primitive it replaces, so that setting a
+    /// `UInt16` member to `5` keeps it a `UInt16`. The conversion fails with
+    /// [`PathError::TypeMismatch`] if the value is out of range or of an unrelated type, or
+    /// if a primitive would be replaced by anything else. Other values are replaced as they
+    /// are.
+    pub fn set(&mut self, path: &str, value: impl NrbfField) -> Result<Field, PathError> {
+        let path = Path::parse(path)?;
+        let value = value.into_field();
+        let last = path.segments.len() - 1;
+
+        if last == 0 {
+            let field = self
+                .root
+                .fields
+                .get_mut(member_name(&path, 0))
+                .ok_or_else(|| path.missing_member(0))?;
+
+            return replace(field, value, &path);
+        }
+
+        let parent = parent_mut(&mut self.root, &path)?;
+
+        match (&mut *parent, path.segments[last].0) {
+            (Field::Class(class), Segment::Member(name)) => {
+                let field = class
+                    .fields
+                    .get_mut(name)
+                    .ok_or_else(|| path.missing_member(last))?;
+
+                replace(field, value, &path)
+            }
+            (
+                Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }),
+                Segment::Index(index),
+            ) => {
+                let length = elements.len();
+                let field = elements
+                    .get_mut(index)
+                    .ok_or_else(|| path.out_of_bounds(last, length))?;
+
+                replace(field, value, &path)
+            }
+            (Field::PrimitiveArray(array), Segment::Index(index)) => {
+                let element_type = array.get_type();
+                let old = array
+                    .get(index)
+                    .ok_or_else(|| path.out_of_bounds(last, array.len()))?;
+                let value = match (value, element_type) {
+                    (Field::Null, PrimitiveType::String) => Primitive::Null,
+                    (value, _) => cast_field(value, element_type, &path)?,
+                };
+
+                array.replace(index, value);
+                Ok(Field::Primitive(old))
+            }
+            (parent, _) => Err(path.mismatch(last - 1, parent)),
+        }
+    }
+
+    /// Removes a class member or array element, returning its value. Later array elements
+    /// move down to fill the gap. See [`Stream::get`] for the form of paths.
+    pub fn remove(&mut self, path: &str) -> Result<Field, PathError> {
+        let path = Path::parse(path)?;
+        let last = path.segments.len() - 1;
+
+        if last == 0 {
+            return self
+                .root
+                .fields
+                .shift_remove(member_name(&path, 0))
+                .ok_or_else(|| path.missing_member(0));
+        }
+
+        let parent = parent_mut(&mut self.root, &path)?;
+
+        match (&mut *parent, path.segments[last].0) {
+            (Field::Class(class), Segment::Member(name)) => class
+                .fields
+                .shift_remove(name)
+                .ok_or_else(|| path.missing_member(last)),
+            (
+                Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }),
+                Segment::Index(index),
+            ) => {
+                if index >= elements.len() {
+                    return Err(path.out_of_bounds(last, elements.len()));
+                }
+
+                Ok(elements.remove(index))
+            }
+            (Field::PrimitiveArray(array), Segment::Index(index)) => {
+                let length = array.len();
+
+                array
+                    .remove(index)
+                    .map(Field::Primitive)
+                    .ok_or_else(|| path.out_of_bounds(last, length))
+            }
+            (parent, _) => Err(path.mismatch(last - 1, parent)),
+        }
+    }
+}
+
+// Parsing guarantees that the first segment is a member.
+fn member_name<'p>(path: &Path<'p>, index: usize) -> &'p str {
+    match path.segments[index].0 {
+        Segment::Member(name) => name,
+        Segment::Index(_) => unreachable!(),
+    }
+}
+
+fn member<'a>(class: &'a Class, path: &Path, index: usize) -> Result<&'a Field, PathError> {
+    class
+        .fields
+        .get(member_name(path, index))
+        .ok_or_else(|| path.missing_member(index))
+}
+
+fn unshared(field: &Field) -> &Field {
+    match field {
+        Field::Shared(_, value) => unshared(value),
+        field => field,
+    }
+}
+
+fn unshared_mut(field: &mut Field) -> &mut Field {
+    match field {
+        Field::Shared(_, value) => unshared_mut(value),
+        field => field,
+    }
+}
+
+/// Follows a path from a value rather than from the root. Paths that lead nowhere give `None`.
+pub(crate) fn lookup<'a>(field: &'a Field, path: &Path) -> Option<Cow<'a, Field>> {
+    let mut field = Cow::Borrowed(field);
+
+    for (segment, _) in &path.segments {
+        field = follow(&field, *segment).ok()?;
+    }
+
+    Some(field)
+}
+
+// Only primitive array elements are owned, and they have no children, so following a segment
+// from an owned value never has to clone anything big.
+fn follow<'a>(field: &Cow<'a, Field>, segment: Segment) -> Result<Cow<'a, Field>, Miss> {
+    match field {
+        Cow::Borrowed(field) => child(field, segment),
+        Cow::Owned(field) => child(field, segment).map(|field| Cow::Owned(field.into_owned())),
+    }
+}
+
+fn child<'a>(field: &'a Field, segment: Segment) -> Result<Cow<'a, Field>, Miss> {
+    match (unshared(field), segment) {
+        (Field::Class(class), Segment::Member(name)) => class
+            .fields
+            .get(name)
+            .map(Cow::Borrowed)
+            .ok_or(Miss::Member),
+        (
+            Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }),
+            Segment::Index(index),
+        ) => elements.get(index).map(Cow::Borrowed).ok_or(Miss::Index {
+            length: elements.len(),
+        }),
+        (Field::PrimitiveArray(array), Segment::Index(index)) => element(array, index),
+        (Field::StreamedArray(array), Segment::Index(index)) => {
+            element(&array.to_primitive_array(), index)
+        }
+        _ => Err(Miss::Type),
+    }
+}
+
+fn element(array: &PrimitiveArray, index: usize) -> Result<Cow<'static, Field>, Miss> {
+    array
+        .get(index)
+        .map(|primitive| Cow::Owned(Field::Primitive(primitive)))
+        .ok_or(Miss::Index {
+            length: array.len(),
+        })
+}
+
+// Follows every segment but the last, to the class or array that the last one leads into.
+// Streamed arrays on the way are turned into primitive arrays so that they can be edited.
+fn parent_mut<'a>(root: &'a mut Class, path: &Path) -> Result<&'a mut Field, PathError> {
+    let mut field = root
+        .fields
+        .get_mut(member_name(path, 0))
+        .ok_or_else(|| path.missing_member(0))?;
+
+    for index in 1..path.segments.len() {
+        field = unshared_mut(field);
+
+        if let Field::StreamedArray(array) = field {
+            *field = Field::PrimitiveArray(array.to_primitive_array());
+        }
+
+        if index == path.segments.len() - 1 {
+            break;
+        }
+
+        field = match (field, path.segments[index].0) {
+            (Field::Class(class), Segment::Member(name)) => class
+                .fields
+                .get_mut(name)
+                .ok_or_else(|| path.missing_member(index))?,
+            (
+                Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }),
+                Segment::Index(element),
+            ) => {
+                let length = elements.len();
+
+                elements
+                    .get_mut(element)
+                    .ok_or_else(|| path.out_of_bounds(index, length))?
+            }
+            (Field::PrimitiveArray(array), Segment::Index(element)) => {
+                let primitive = array
+                    .get(element)
+                    .ok_or_else(|| path.out_of_bounds(index, array.len()))?;
+
+                return Err(path.mismatch(index, &Field::Primitive(primitive)));
+            }
+            (field, _) => return Err(path.mismatch(index - 1, field)),
+        };
+    }
+
+    Ok(field)
+}
+
+fn replace(field: &mut Field, value: Field, path: &Path) -> Result<Field, PathError> {
+    let field = unshared_mut(field);
+    let value = match (&*field, value) {
+        (Field::Primitive(Primitive::String(_)), Field::Null) => Field::Null,
+        (Field::Primitive(Primitive::Null), value) => value,
+        (Field::Primitive(old), value) => {
+            Field::Primitive(cast_field(value, old.get_type(), path)?)
+        }
+        (_, value) => value,
+    };
+
+    Ok(mem::replace(field, value))
+}
+
+fn cast_field(
+    value: Field,
+    primitive_type: PrimitiveType,
+    path: &Path,
+) -> Result<Primitive, PathError> {
+    let mismatch = |found: String| PathError::TypeMismatch {
+        path: path.text.to_owned(),
+        expected: format!("{primitive_type:?}"),
+        found,
+    };
+
+    match value {
+        Field::Primitive(primitive) => {
+            let found = format!("{primitive:?}");
+
+            cast(primitive, primitive_type).ok_or_else(|| mismatch(found))
+        }
+        value => Err(mismatch(value.describe())),
+    }
+}
+
+// Converts between integer types when the value is in range, from integers to floating point
+// and decimal types, from floating point values without a fraction to integers, from `Double`
+// to `Single` and `Decimal`, from `Single` to `Double`, and between `Char` and one character
+// `String`s.
+pub(crate) fn cast(value: Primitive, primitive_type: PrimitiveType) -> Option<Primitive> {
+    if value.get_type() == primitive_type {
+        return Some(value);
+    }
+
+    let integer = match value {
+        Primitive::Byte(value) => i128::from(value),
+        Primitive::SByte(value) => i128::from(value),
+        Primitive::Int16(value) => i128::from(value),
+        Primitive::Int32(value) => i128::from(value),
+        Primitive::Int64(value) => i128::from(value),
+        Primitive::UInt16(value) => i128::from(value),
+        Primitive::UInt32(value) => i128::from(value),
+        Primitive::UInt64(value) => i128::from(value),
+        Primitive::Double(value) if primitive_type == PrimitiveType::Single => {
+            return Some(Primitive::Single(value as f32));
+        }
+        Primitive::Single(value) if primitive_type == PrimitiveType::Double => {
+            return Some(Primitive::Double(value.into()));
+        }
+        Primitive::Double(value) if primitive_type == PrimitiveType::Decimal => {
+            return value
+                .is_finite()
+                .then(|| Primitive::Decimal(value.to_string()));
+        }
+        Primitive::Double(value) if value.fract() == 0.0 => value as i128,
+        Primitive::Single(value) if value.fract() == 0.0 => value as i128,
+        Primitive::Char(value) if primitive_type == PrimitiveType::String => {
+            return Some(Primitive::String(value.to_string()));
+        }
+        Primitive::String(value) if primitive_type == PrimitiveType::Char => {
+            let mut chars = value.chars();
+
+            return match (chars.next(), chars.next()) {
+                (Some(value), None) => Some(Primitive::Char(value)),
+                _ => None,
+            };
+        }
+        _ => return None,
+    };
+
+    Some(match primitive_type {
+        PrimitiveType::Byte => Primitive::Byte(integer.try_into().ok()?),
+        PrimitiveType::SByte => Primitive::SByte(integer.try_into().ok()?),
+        PrimitiveType::Int16 => Primitive::Int16(integer.try_into().ok()?),
+        PrimitiveType::Int32 => Primitive::Int32(integer.try_into().ok()?),
+        PrimitiveType::Int64 => Primitive::Int64(integer.try_into().ok()?),
+        PrimitiveType::UInt16 => Primitive::UInt16(integer.try_into().ok()?),
+        PrimitiveType::UInt32 => Primitive::UInt32(integer.try_into().ok()?),
+        PrimitiveType::UInt64 => Primitive::UInt64(integer.try_into().ok()?),
+        PrimitiveType::Double => Primitive::Double(integer as f64),
+        PrimitiveType::Single => Primitive::Single(integer as f32),
+        PrimitiveType::Decimal => Primitive::Decimal(integer.to_string()),
+        _ => return None,
+    })
+}
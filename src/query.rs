@@ -0,0 +1,591 @@
+use crate::{
+    enums::Primitive,
+    path::{self, Path},
+    stream::{ClassArray, Field, Stream},
+};
+use std::{borrow::Cow, cmp::Ordering, str::FromStr};
+use thiserror::Error;
+
+/// A query that doesn't parse, with the byte offset in the query where the problem is.
+#[derive(Error, Debug)]
+#[error("{message} at position {position}")]
+pub struct QueryError {
+    pub position: usize,
+    pub message: String,
+}
+
+/// A search over the values in a stream, run with [`Stream::query`].
+///
+/// A query is a type name followed by an optional condition:
+///
+/// ```text
+/// Game.Item where Durability < 10
+/// String where . contains "://"
+/// Game.* where Owner.Name == "Ann" and not (Level >= 3 or Hidden)
+/// ```
+///
+/// The type name matches class names, primitive type names like `Int32` or `String`, arrays
+/// like `Int32[]` or `Object[]`, and `null`. `*` in it matches any run of characters, so `*`
+/// on its own matches every value.
+///
+/// Conditions compare a value to a literal with `==`, `!=`, `<`, `<=`, `>`, `>=`, `contains`,
+/// `startswith` or `endswith`, and are combined with `and`, `or`, `not` and parentheses. The
+/// value is found by following a path from the matched value, written like the paths of
+/// [`Stream::get`] but allowed to start with an index, or is the matched value itself for `.`.
+/// A path on its own is true when it leads to a value that isn't null.
+///
+/// Literals are numbers, double-quoted strings, `true`, `false` and `null`. Numbers compare
+/// with numeric primitives, booleans with `Boolean`s, and strings with `String`, `Char`,
+/// `Decimal`, `DateTime` and `TimeSpan` values in their text form, so that
+/// `Created > "2024-01-01"` works. Comparisons between other types, or with paths that lead
+/// nowhere, are false.
+#[derive(Debug, Clone)]
+pub struct Query {
+    selector: String,
+    condition: Option<Condition>,
+}
+
+/// A value found by a [`Query`], with its path in the form [`Stream::get`] takes.
+#[derive(Debug, Clone)]
+pub struct Match<'a> {
+    pub path: String,
+    /// Elements of primitive arrays are owned, everything else is borrowed from the stream.
+    pub value: Cow<'a, Field>,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+    Exists(Operand),
+    Compare(Operand, Operator, Literal),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    This,
+    // Checked when the query is parsed, and parsed again for every value it is followed from.
+    Path(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone)]
+enum Literal {
+    Null,
+    Boolean(bool),
+    Number(Number),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Integer(i128),
+    Float(f64),
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, QueryError> {
+        Parser {
+            tokens: tokenize(text)?,
+            next: 0,
+            end: text.len(),
+        }
+        .query()
+    }
+
+    fn selects(&self, field: &Field) -> bool {
+        type_name(field).is_some_and(|name| glob(&self.selector, &name))
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.holds(field))
+    }
+
+    // Looks for matches in a value and everything inside it.
+    fn search<'a>(&self, field: &'a Field, path: String, matches: &mut Vec<Match<'a>>) {
+        let field = match field {
+            Field::Shared(_, value) => return self.search(value, path, matches),
+            field => field,
+        };
+
+        if self.selects(field) {
+            matches.push(Match {
+                path: path.clone(),
+                value: Cow::Borrowed(field),
+            });
+        }
+
+        let array = match field {
+            Field::Class(class) => {
+                for (name, value) in &class.fields {
+                    self.search(value, format!("{path}.{name}"), matches);
+                }
+
+                return;
+            }
+            Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }) => {
+                for (index, element) in elements.iter().enumerate() {
+                    self.search(element, format!("{path}[{index}]"), matches);
+                }
+
+                return;
+            }
+            Field::PrimitiveArray(array) => Cow::Borrowed(array),
+            Field::StreamedArray(array) => Cow::Owned(array.to_primitive_array()),
+            _ => return,
+        };
+
+        // Skips arrays whose elements can't match without looking at each one.
+        let element_type = format!("{:?}", array.get_type());
+
+        if !glob(&self.selector, &element_type) && !glob(&self.selector, "null") {
+            return;
+        }
+
+        for index in 0..array.len() {
+            let element = Field::Primitive(array.get(index).unwrap_or(Primitive::Null));
+
+            if self.selects(&element) {
+                matches.push(Match {
+                    path: format!("{path}[{index}]"),
+                    value: Cow::Owned(element),
+                });
+            }
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
+
+impl Stream {
+    /// Finds every value inside the root class that a query matches, in the order they appear
+    /// in. Shared objects are searched where they are defined, and references aren't followed.
+    pub fn query(&self, query: &Query) -> Vec<Match<'_>> {
+        let mut matches = vec![];
+
+        for (name, value) in &self.root.fields {
+            query.search(value, name.clone(), &mut matches);
+        }
+
+        matches
+    }
+}
+
+impl Condition {
+    fn holds(&self, field: &Field) -> bool {
+        match self {
+            Self::And(left, right) => left.holds(field) && right.holds(field),
+            Self::Or(left, right) => left.holds(field) || right.holds(field),
+            Self::Not(condition) => !condition.holds(field),
+            Self::Exists(operand) => operand.resolve(field).is_some_and(|value| !is_null(&value)),
+            Self::Compare(operand, operator, literal) => operand
+                .resolve(field)
+                .is_some_and(|value| compare(&value, *operator, literal)),
+        }
+    }
+}
+
+impl Operand {
+    fn resolve<'a>(&self, field: &'a Field) -> Option<Cow<'a, Field>> {
+        match self {
+            Self::This => Some(Cow::Borrowed(field)),
+            Self::Path(text) => path::lookup(field, &Path::parse_relative(text).ok()?),
+        }
+    }
+}
+
+fn type_name(field: &Field) -> Option<String> {
+    Some(match field {
+        Field::Primitive(Primitive::Null) | Field::Null => "null".to_owned(),
+        Field::Primitive(primitive) => format!("{:?}", primitive.get_type()),
+        Field::PrimitiveArray(array) => format!("{:?}[]", array.get_type()),
+        Field::Class(class) => class.name.clone(),
+        Field::ObjectArray(_) => "Object[]".to_owned(),
+        Field::ClassArray(array) => format!("{}[]", array.name),
+        Field::StreamedArray(array) => format!("{:?}[]", array.get_type()),
+        Field::Shared(_, value) => return type_name(value),
+        Field::Reference(_) => return None,
+    })
+}
+
+// Matches a name against a pattern in which `*` stands for any run of characters.
+fn glob(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<_> = parts.collect();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(start) => rest = &rest[start + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+fn is_null(field: &Field) -> bool {
+    match field {
+        Field::Shared(_, value) => is_null(value),
+        field => matches!(field, Field::Null | Field::Primitive(Primitive::Null)),
+    }
+}
+
+fn compare(field: &Field, operator: Operator, literal: &Literal) -> bool {
+    let field = match field {
+        Field::Shared(_, value) => return compare(value, operator, literal),
+        field => field,
+    };
+    let primitive = match field {
+        Field::Primitive(primitive) => Some(primitive),
+        _ => None,
+    };
+
+    let ordering = match (literal, primitive) {
+        (Literal::Null, _) => {
+            return match operator {
+                Operator::Equal => is_null(field),
+                Operator::NotEqual => !is_null(field),
+                _ => false,
+            };
+        }
+        (Literal::Boolean(literal), Some(Primitive::Boolean(value))) => {
+            return match operator {
+                Operator::Equal => value == literal,
+                Operator::NotEqual => value != literal,
+                _ => false,
+            };
+        }
+        (Literal::Number(literal), Some(primitive)) => match (number(primitive), literal) {
+            (Some(Number::Integer(value)), Number::Integer(literal)) => Some(value.cmp(literal)),
+            (Some(value), literal) => value.as_f64().partial_cmp(&literal.as_f64()),
+            (None, _) => return false,
+        },
+        (Literal::String(literal), Some(primitive)) => {
+            let Some(value) = text(primitive) else {
+                return false;
+            };
+
+            match operator {
+                Operator::Contains => return value.contains(literal.as_str()),
+                Operator::StartsWith => return value.starts_with(literal.as_str()),
+                Operator::EndsWith => return value.ends_with(literal.as_str()),
+                _ => Some(value.as_str().cmp(literal)),
+            }
+        }
+        _ => return false,
+    };
+
+    match operator {
+        Operator::Equal => ordering == Some(Ordering::Equal),
+        Operator::NotEqual => ordering.is_some_and(|ordering| ordering != Ordering::Equal),
+        Operator::Less => ordering == Some(Ordering::Less),
+        Operator::LessOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Operator::Greater => ordering == Some(Ordering::Greater),
+        Operator::GreaterOrEqual => {
+            matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+        }
+        Operator::Contains | Operator::StartsWith | Operator::EndsWith => false,
+    }
+}
+
+impl Number {
+    fn as_f64(&self) -> f64 {
+        match *self {
+            Self::Integer(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+}
+
+fn number(primitive: &Primitive) -> Option<Number> {
+    Some(match primitive {
+        Primitive::Byte(value) => Number::Integer((*value).into()),
+        Primitive::SByte(value) => Number::Integer((*value).into()),
+        Primitive::Int16(value) => Number::Integer((*value).into()),
+        Primitive::Int32(value) => Number::Integer((*value).into()),
+        Primitive::Int64(value) => Number::Integer((*value).into()),
+        Primitive::UInt16(value) => Number::Integer((*value).into()),
+        Primitive::UInt32(value) => Number::Integer((*value).into()),
+        Primitive::UInt64(value) => Number::Integer((*value).into()),
+        Primitive::Double(value) => Number::Float(*value),
+        Primitive::Single(value) => Number::Float((*value).into()),
+        Primitive::Decimal(value) => Number::Float(value.parse().ok()?),
+        _ => return None,
+    })
+}
+
+fn text(primitive: &Primitive) -> Option<String> {
+    Some(match primitive {
+        Primitive::String(value) => value.clone(),
+        Primitive::Char(value) => value.to_string(),
+        Primitive::Decimal(value) => value.clone(),
+        Primitive::DateTime(value) => value.to_string(),
+        Primitive::TimeSpan(value) => value.to_string(),
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'q> {
+    Word(&'q str),
+    String(String),
+    Operator(&'q str),
+    Open,
+    Close,
+}
+
+// Splits a query into tokens, each with its byte offset.
+fn tokenize(text: &str) -> Result<Vec<(Token<'_>, usize)>, QueryError> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => {
+                chars.next();
+                Token::Open
+            }
+            ')' => {
+                chars.next();
+                Token::Close
+            }
+            '"' => {
+                let mut value = String::new();
+
+                chars.next();
+
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, 't')) => value.push('\t'),
+                            Some((_, c)) => value.push(c),
+                            None => return Err(error(start, "unterminated string")),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => return Err(error(start, "unterminated string")),
+                    }
+                }
+
+                Token::String(value)
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+
+                let end = match chars.peek() {
+                    Some(&(end, '=')) => {
+                        chars.next();
+                        end + 1
+                    }
+                    _ => start + 1,
+                };
+
+                match &text[start..end] {
+                    "=" | "!" => return Err(error(start, "expected `==` or `!=`")),
+                    operator => Token::Operator(operator),
+                }
+            }
+            _ => {
+                let mut end = text.len();
+
+                while let Some(&(index, c)) = chars.peek() {
+                    if c.is_whitespace() || "()\"=!<>".contains(c) {
+                        end = index;
+                        break;
+                    }
+
+                    chars.next();
+                }
+
+                Token::Word(&text[start..end])
+            }
+        };
+
+        tokens.push((token, start));
+    }
+
+    Ok(tokens)
+}
+
+fn error(position: usize, message: impl Into<String>) -> QueryError {
+    QueryError {
+        position,
+        message: message.into(),
+    }
+}
+
+struct Parser<'q> {
+    tokens: Vec<(Token<'q>, usize)>,
+    next: usize,
+    end: usize,
+}
+
+impl<'q> Parser<'q> {
+    fn peek(&self) -> Option<&Token<'q>> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.end, |(_, position)| *position)
+    }
+
+    fn advance(&mut self) -> Option<Token<'q>> {
+        let token = self.peek().cloned();
+
+        self.next += 1;
+        token
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let found = self.peek() == Some(&Token::Word(word));
+
+        if found {
+            self.next += 1;
+        }
+
+        found
+    }
+
+    fn query(mut self) -> Result<Query, QueryError> {
+        let selector = match self.advance() {
+            Some(Token::Word(word)) if word != "where" => word.to_owned(),
+            _ => return Err(error(0, "expected a type name")),
+        };
+        let condition = if self.eat_word("where") {
+            Some(self.or()?)
+        } else {
+            None
+        };
+
+        if self.next < self.tokens.len() {
+            return Err(error(
+                self.position(),
+                "expected `where` or the end of the query",
+            ));
+        }
+
+        Ok(Query {
+            selector,
+            condition,
+        })
+    }
+
+    fn or(&mut self) -> Result<Condition, QueryError> {
+        let mut condition = self.and()?;
+
+        while self.eat_word("or") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, QueryError> {
+        let mut condition = self.not()?;
+
+        while self.eat_word("and") {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, QueryError> {
+        if self.eat_word("not") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+
+        if self.peek() == Some(&Token::Open) {
+            self.next += 1;
+
+            let condition = self.or()?;
+            let position = self.position();
+
+            return match self.advance() {
+                Some(Token::Close) => Ok(condition),
+                _ => Err(error(position, "expected `)`")),
+            };
+        }
+
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Condition, QueryError> {
+        let position = self.position();
+        let operand = match self.advance() {
+            Some(Token::Word(".")) => Operand::This,
+            Some(Token::Word(path)) => {
+                Path::parse_relative(path).map_err(|_| error(position, "invalid path"))?;
+                Operand::Path(path.to_owned())
+            }
+            _ => return Err(error(position, "expected a path or `.`")),
+        };
+        let operator = match self.peek() {
+            Some(Token::Operator(operator)) => match *operator {
+                "==" => Operator::Equal,
+                "!=" => Operator::NotEqual,
+                "<" => Operator::Less,
+                "<=" => Operator::LessOrEqual,
+                ">" => Operator::Greater,
+                _ => Operator::GreaterOrEqual,
+            },
+            Some(Token::Word("contains")) => Operator::Contains,
+            Some(Token::Word("startswith")) => Operator::StartsWith,
+            Some(Token::Word("endswith")) => Operator::EndsWith,
+            _ => return Ok(Condition::Exists(operand)),
+        };
+
+        self.next += 1;
+
+        let position = self.position();
+        let literal = match self.advance() {
+            Some(Token::String(value)) => Literal::String(value),
+            Some(Token::Word("null")) => Literal::Null,
+            Some(Token::Word("true")) => Literal::Boolean(true),
+            Some(Token::Word("false")) => Literal::Boolean(false),
+            Some(Token::Word(word)) => Literal::Number(match word.parse() {
+                Ok(value) => Number::Integer(value),
+                Err(_) => Number::Float(
+                    word.parse()
+                        .map_err(|_| error(position, "expected a literal"))?,
+                ),
+            }),
+            _ => return Err(error(position, "expected a literal")),
+        };
+
+        Ok(Condition::Compare(operand, operator, literal))
+    }
+}
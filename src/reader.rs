@@ -0,0 +1,149 @@
+use crate::{
+    enums::Record,
+    io::Read,
+    parse::{ParseContext, ParseError, ParseWithContext},
+};
+#[cfg(feature = "async")]
+use {
+    std::{future::poll_fn, io, pin::Pin, task::Poll},
+    tokio::io::{AsyncRead, ReadBuf},
+};
+
+/// Reads a stream one record at a time, stopping after `MessageEnd` or the first error.
+///
+/// Class metadata is remembered between records so that `ClassWithId` records can be read,
+/// but nothing else is kept in memory.
+pub struct RecordReader<R: Read> {
+    reader: R,
+    context: ParseContext,
+    finished: bool,
+}
+
+impl<R: Read> RecordReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            context: ParseContext::default(),
+            finished: false,
+        }
+    }
+
+    pub fn read_record(&mut self) -> Result<Record, ParseError> {
+        self.reader.parse_with(&mut self.context)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = Result<Record, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let record = self.read_record();
+        self.finished = matches!(record, Ok(Record::MessageEnd) | Err(_));
+        Some(record)
+    }
+}
+
+/// The async counterpart of [`RecordReader`].
+///
+/// Bytes are read ahead into a buffer and each record is parsed with the same grammar as the
+/// blocking reader. A record that runs past the end of the buffer is parsed again once the
+/// buffer has doubled in size, or sooner if the reader has no more bytes ready, so a large record
+/// is parsed a logarithmic number of times rather than once per read.
+#[cfg(feature = "async")]
+pub struct AsyncRecordReader<R: AsyncRead + Unpin> {
+    reader: R,
+    buffer: Vec<u8>,
+    context: ParseContext,
+    finished: bool,
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRecordReader<R> {
+    const MIN_READ: usize = 8 * 1024;
+
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: vec![],
+            context: ParseContext::default(),
+            finished: false,
+        }
+    }
+
+    pub async fn read_record(&mut self) -> Result<Record, ParseError> {
+        loop {
+            let mut remaining = self.buffer.as_slice();
+
+            match remaining.parse_with(&mut self.context) {
+                Ok(record) => {
+                    let consumed = self.buffer.len() - remaining.len();
+                    self.buffer.drain(..consumed);
+                    return Ok(record);
+                }
+                Err(ParseError::IoError(error)) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                    let target = (self.buffer.len() * 2).max(Self::MIN_READ);
+
+                    if self.fill(target).await? == 0 {
+                        return Err(ParseError::IoError(error));
+                    }
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    // Reads until the buffer holds `target` bytes, the reader ends, or the reader has no more
+    // bytes ready after at least one was read. Returns how many bytes were read.
+    async fn fill(&mut self, target: usize) -> io::Result<usize> {
+        let start = self.buffer.len();
+
+        poll_fn(|cx| loop {
+            let filled = self.buffer.len();
+
+            if filled >= target {
+                return Poll::Ready(Ok(filled - start));
+            }
+
+            self.buffer.resize(target, 0);
+
+            let mut read_buf = ReadBuf::new(&mut self.buffer[filled..]);
+            let poll = Pin::new(&mut self.reader).poll_read(cx, &mut read_buf);
+            let read = read_buf.filled().len();
+
+            self.buffer.truncate(filled + read);
+
+            match poll {
+                Poll::Ready(Ok(())) if read == 0 => return Poll::Ready(Ok(filled - start)),
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending if filled > start => return Poll::Ready(Ok(filled - start)),
+                Poll::Pending => return Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Returns the next record, or `None` after `MessageEnd` or the first error.
+    pub async fn next(&mut self) -> Option<Result<Record, ParseError>> {
+        if self.finished {
+            return None;
+        }
+
+        let record = self.read_record().await;
+        self.finished = matches!(record, Ok(Record::MessageEnd) | Err(_));
+        Some(record)
+    }
+
+    /// Returns the inner reader. Bytes that were read ahead but not yet parsed are lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
@@ -1,40 +1,146 @@
 use crate::{
+    array::PrimitiveArray,
+    io::{self, Read, Write},
+    parse::{
+        Parse, ParseContext, ParseError, ParseFrom, ParseFromContext, ParseSized,
+        ParseTyped, ParseWithContext,
+    },
+    unparse::{Unparse, UnparseTo},
+};
+use alloc::{string::String, vec, vec::Vec};
+
+pub use crate::{
     common::{
-        ArrayInfo, ArrayOfValueWithCode, ClassInfo, MemberTypeInfo, MessageFlags,
+        ArrayInfo, ArrayOfValueWithCode, ClassInfo, ClassTypeInfo, MemberTypeInfo, MessageFlags,
         StringValueWithCode, ValueWithCode,
     },
-    enums::{
-        AdditionalInfo, BinaryArrayType, BinaryType, Primitive, PrimitiveType, Record, RecordType,
-    },
-    parse::{Parse, ParseError, ParseFrom, ParseFromSized, ParseSized, ParseTyped},
-    unparse::{Unparse, UnparseTo},
+    enums::{AdditionalInfo, BinaryArrayType, BinaryType, PrimitiveType, Record, RecordType},
 };
-use std::io::{self, Read, Write};
 
-fn read_references<R: Read>(
+// Reads the records inside an object record: a class's members or an array's elements. The
+// `Record` parsers read them with their `ParseContext`, and the borrowed decoder with one that
+// keeps strings pointing into the input, so both share the grammar below.
+pub(crate) trait ReadMember<R: Read> {
+    type Member;
+
+    fn read_primitive(
+        &mut self,
+        reader: &mut R,
+        primitive_type: PrimitiveType,
+    ) -> Result<Self::Member, ParseError>;
+
+    fn read_member(&mut self, reader: &mut R) -> Result<Self::Member, ParseError>;
+
+    // How many elements the member stands for, which is more than one for a run of nulls and
+    // none for a library.
+    fn element_count(member: &Self::Member) -> usize;
+
+    // Libraries can be written just before the member that first uses them, without taking
+    // the place of a member.
+    fn is_library(member: &Self::Member) -> bool;
+}
+
+impl<R: Read> ReadMember<R> for ParseContext {
+    type Member = Record;
+
+    fn read_primitive(
+        &mut self,
+        reader: &mut R,
+        primitive_type: PrimitiveType,
+    ) -> Result<Record, ParseError> {
+        Ok(Record::MemberPrimitiveUnTyped(
+            reader.parse_typed(primitive_type)?,
+        ))
+    }
+
+    fn read_member(&mut self, reader: &mut R) -> Result<Record, ParseError> {
+        reader.parse_with(self)
+    }
+
+    fn element_count(member: &Record) -> usize {
+        match member {
+            Record::ObjectNullMultiple256 { null_count } => *null_count as usize,
+            Record::ObjectNullMultiple { null_count } => *null_count as usize,
+            Record::BinaryLibrary(_) => 0,
+            _ => 1,
+        }
+    }
+
+    fn is_library(member: &Record) -> bool {
+        matches!(member, Record::BinaryLibrary(_))
+    }
+}
+
+pub(crate) fn read_references<R: Read, M: ReadMember<R>>(
     reader: &mut R,
-    additional_info: &Vec<AdditionalInfo>,
-) -> Result<Vec<Record>, ParseError> {
+    member_type_info: &MemberTypeInfo,
+    members: &mut M,
+) -> Result<Vec<M::Member>, ParseError> {
     let mut member_references = vec![];
+    let mut additional_info = member_type_info.additional_info.iter();
+
+    for member_type in &member_type_info.member_types {
+        let info = match member_type {
+            BinaryType::Primitive_
+            | BinaryType::SystemClass
+            | BinaryType::Class
+            | BinaryType::PrimitiveArray => additional_info.next(),
+            _ => None,
+        };
 
-    for info in additional_info {
-        member_references.push(match info {
-            AdditionalInfo::Primitive(primitive_type) => {
-                Record::MemberPrimitiveUnTyped(reader.parse_typed(*primitive_type)?)
-            }
-            _ => {
-                // TODO: This probably doesn't work, I should check this. Will I? I don't know.
-                let record_type = reader.parse()?;
-                reader.parse_typed(record_type)?
+        loop {
+            let member = match info {
+                Some(AdditionalInfo::Primitive(primitive_type)) => {
+                    members.read_primitive(reader, *primitive_type)?
+                }
+                _ => members.read_member(reader)?,
+            };
+            let is_library = M::is_library(&member);
+
+            member_references.push(member);
+
+            if !is_library {
+                break;
             }
-        })
+        }
     }
 
     Ok(member_references)
 }
 
+pub(crate) fn read_elements<R: Read, M: ReadMember<R>>(
+    reader: &mut R,
+    length: usize,
+    additional_info: Option<&AdditionalInfo>,
+    members: &mut M,
+) -> Result<Vec<M::Member>, ParseError> {
+    let mut elements = vec![];
+    let mut read = 0;
+
+    while read < length {
+        let member = match additional_info {
+            Some(AdditionalInfo::Primitive(primitive_type)) => {
+                members.read_primitive(reader, *primitive_type)?
+            }
+            _ => members.read_member(reader)?,
+        };
+
+        let count = M::element_count(&member);
+
+        // A run of nulls must not go past the end of the array.
+        if count > length - read {
+            return Err(ParseError::TooManyElements(length));
+        }
+
+        read += count;
+        elements.push(member);
+    }
+
+    Ok(elements)
+}
+
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct SerializationHeader {
+pub struct SerializationHeader {
     pub root_id: i32,
     pub header_id: i32,
     pub major_version: i32,
@@ -53,17 +159,17 @@ impl<R: Read> ParseFrom<R> for SerializationHeader {
 }
 
 impl<W: Write> UnparseTo<W> for SerializationHeader {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::SerializedStreamHeader)?;
-        writer.unparse(self.root_id)?;
-        writer.unparse(self.header_id)?;
-        writer.unparse(self.major_version)?;
-        writer.unparse(self.minor_version)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::SerializedStreamHeader)?;
+        writer.unparse(&self.root_id)?;
+        writer.unparse(&self.header_id)?;
+        writer.unparse(&self.major_version)?;
+        writer.unparse(&self.minor_version)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct BinaryLibrary {
+pub struct BinaryLibrary {
     pub library_id: i32,
     pub library_name: String,
 }
@@ -78,31 +184,32 @@ impl<R: Read> ParseFrom<R> for BinaryLibrary {
 }
 
 impl<W: Write> UnparseTo<W> for BinaryLibrary {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::BinaryLibrary)?;
-        writer.unparse(self.library_id)?;
-        writer.unparse(self.library_name)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::BinaryLibrary)?;
+        writer.unparse(&self.library_id)?;
+        writer.unparse(&self.library_name)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ClassWithMembersAndTypes {
+pub struct ClassWithMembersAndTypes {
     pub class_info: ClassInfo,
     pub member_type_info: MemberTypeInfo,
     pub library_id: i32,
     pub member_references: Vec<Record>,
 }
 
-impl<R: Read> ParseFrom<R> for ClassWithMembersAndTypes {
-    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+impl<R: Read> ParseFromContext<R> for ClassWithMembersAndTypes {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
         let class_info: ClassInfo = reader.parse()?;
-        let member_type_info: MemberTypeInfo =
-            reader.parse_sized(class_info.member_count as usize)?;
+        let member_type_info: MemberTypeInfo = reader.parse_sized(class_info.member_names.len())?;
         let library_id = reader.parse()?;
-        let member_references = read_references(
-            reader,
-            &member_type_info.additional_info,
-        )?;
+
+        context
+            .member_type_infos
+            .insert(class_info.object_id, member_type_info.clone());
+
+        let member_references = read_references(reader, &member_type_info, context)?;
 
         Ok(Self {
             class_info,
@@ -114,88 +221,96 @@ impl<R: Read> ParseFrom<R> for ClassWithMembersAndTypes {
 }
 
 impl<W: Write> UnparseTo<W> for ClassWithMembersAndTypes {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::ClassWithMembersAndTypes)?;
-        writer.unparse(self.class_info)?;
-        writer.unparse(self.member_type_info)?;
-        writer.unparse(self.library_id)?;
-        writer.unparse(self.member_references)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::ClassWithMembersAndTypes)?;
+        writer.unparse(&self.class_info)?;
+        writer.unparse(&self.member_type_info)?;
+        writer.unparse(&self.library_id)?;
+        writer.unparse(&self.member_references)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ArraySinglePrimitive {
+pub struct ArraySinglePrimitive {
     pub array_info: ArrayInfo,
-    pub primitive_type: PrimitiveType,
-    pub members: Vec<Primitive>,
+    pub members: PrimitiveArray,
 }
 
 impl<R: Read> ParseFrom<R> for ArraySinglePrimitive {
     fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
         let array_info: ArrayInfo = reader.parse()?;
         let primitive_type = reader.parse()?;
-        let mut members = vec![];
-
-        for _ in 0..array_info.length {
-            members.push(reader.parse_typed(primitive_type)?)
-        }
+        let members =
+            PrimitiveArray::read_from(reader, primitive_type, array_info.length as usize)?;
 
         Ok(Self {
             array_info,
-            primitive_type,
             members,
         })
     }
 }
 
 impl<W: Write> UnparseTo<W> for ArraySinglePrimitive {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::ArraySinglePrimitive)?;
-        writer.unparse(self.array_info)?;
-        writer.unparse(self.primitive_type)?;
-        writer.unparse(self.members)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::ArraySinglePrimitive)?;
+        writer.unparse(&self.array_info)?;
+        writer.unparse(&self.members.get_type())?;
+        writer.unparse(&self.members)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ClassWithId {
+pub struct ClassWithId {
     pub object_id: i32,
     pub metadata_id: i32,
+    pub member_references: Vec<Record>,
 }
 
-impl<R: Read> ParseFrom<R> for ClassWithId {
-    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+impl<R: Read> ParseFromContext<R> for ClassWithId {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
+        let object_id = reader.parse()?;
+        let metadata_id = reader.parse()?;
+        let member_type_info = context
+            .member_type_infos
+            .get(&metadata_id)
+            .ok_or(ParseError::MissingMetadata(metadata_id))?
+            .clone();
+        let member_references = read_references(reader, &member_type_info, context)?;
+
         Ok(Self {
-            object_id: reader.parse()?,
-            metadata_id: reader.parse()?,
+            object_id,
+            metadata_id,
+            member_references,
         })
     }
 }
 
 impl<W: Write> UnparseTo<W> for ClassWithId {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::ClassWithId)?;
-        writer.unparse(self.object_id)?;
-        writer.unparse(self.metadata_id)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::ClassWithId)?;
+        writer.unparse(&self.object_id)?;
+        writer.unparse(&self.metadata_id)?;
+        writer.unparse(&self.member_references)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct SystemClassWithMembersAndTypes {
+pub struct SystemClassWithMembersAndTypes {
     pub class_info: ClassInfo,
     pub member_type_info: MemberTypeInfo,
     pub member_references: Vec<Record>,
 }
 
-impl<R: Read> ParseFrom<R> for SystemClassWithMembersAndTypes {
-    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+impl<R: Read> ParseFromContext<R> for SystemClassWithMembersAndTypes {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
         let class_info: ClassInfo = reader.parse()?;
-        let member_type_info: MemberTypeInfo =
-            reader.parse_sized(class_info.member_count as usize)?;
-        let member_references = read_references(
-            reader,
-            &member_type_info.additional_info,
-        )?;
+        let member_type_info: MemberTypeInfo = reader.parse_sized(class_info.member_names.len())?;
+
+        context
+            .member_type_infos
+            .insert(class_info.object_id, member_type_info.clone());
+
+        let member_references = read_references(reader, &member_type_info, context)?;
 
         Ok(Self {
             class_info,
@@ -206,16 +321,16 @@ impl<R: Read> ParseFrom<R> for SystemClassWithMembersAndTypes {
 }
 
 impl<W: Write> UnparseTo<W> for SystemClassWithMembersAndTypes {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::SystemClassWithMembersAndTypes)?;
-        writer.unparse(self.class_info)?;
-        writer.unparse(self.member_type_info)?;
-        writer.unparse(self.member_references)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::SystemClassWithMembersAndTypes)?;
+        writer.unparse(&self.class_info)?;
+        writer.unparse(&self.member_type_info)?;
+        writer.unparse(&self.member_references)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct BinaryObjectString {
+pub struct BinaryObjectString {
     pub object_id: i32,
     pub value: String,
 }
@@ -230,31 +345,33 @@ impl<R: Read> ParseFrom<R> for BinaryObjectString {
 }
 
 impl<W: Write> UnparseTo<W> for BinaryObjectString {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::BinaryObjectString)?;
-        writer.unparse(self.object_id)?;
-        writer.unparse(self.value)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::BinaryObjectString)?;
+        writer.unparse(&self.object_id)?;
+        writer.unparse(&self.value)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct BinaryArray {
+pub struct BinaryArray {
     pub object_id: i32,
     pub binary_array_type: BinaryArrayType,
     pub rank: i32,
     pub lengths: Vec<i32>,
     pub lower_bounds: Option<Vec<i32>>,
     pub binary_type: BinaryType,
-    pub additional_info: Vec<AdditionalInfo>,
+    pub additional_info: Option<AdditionalInfo>,
     pub members: Vec<Record>,
 }
 
-impl<R: Read> ParseFrom<R> for BinaryArray {
-    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+impl BinaryArray {
+    // Reads everything before the elements, leaving `members` empty, and returns how many
+    // elements follow. Multi-dimensional arrays are flattened in row-major order.
+    pub(crate) fn parse_header<R: Read>(reader: &mut R) -> Result<(Self, usize), ParseError> {
         let object_id = reader.parse()?;
         let binary_array_type = reader.parse()?;
         let rank: i32 = reader.parse()?;
-        let lengths = reader.parse_sized(rank as usize)?;
+        let lengths: Vec<i32> = reader.parse_sized(rank as usize)?;
         let lower_bounds = match binary_array_type {
             BinaryArrayType::SingleOffset
             | BinaryArrayType::JaggedOffset
@@ -262,53 +379,65 @@ impl<R: Read> ParseFrom<R> for BinaryArray {
             _ => None,
         };
         let binary_type = reader.parse()?;
-        let mut additional_info = vec![];
-
-        for _ in 0..rank {
-            if let Some(info) = reader.parse_typed(binary_type)? {
-                additional_info.push(info)
-            }
+        let additional_info: Option<AdditionalInfo> = reader.parse_typed(binary_type)?;
+        let mut length: usize = 1;
+
+        for dimension in &lengths {
+            length = usize::try_from(*dimension)
+                .ok()
+                .and_then(|dimension| length.checked_mul(dimension))
+                .ok_or(ParseError::InvalidCount(*dimension))?;
         }
 
-        let members = read_references(reader, &additional_info)?;
+        Ok((
+            Self {
+                object_id,
+                binary_array_type,
+                rank,
+                lengths,
+                lower_bounds,
+                binary_type,
+                additional_info,
+                members: vec![],
+            },
+            length,
+        ))
+    }
+}
 
-        Ok(Self {
-            object_id,
-            binary_array_type,
-            rank,
-            lengths,
-            lower_bounds,
-            binary_type,
-            additional_info,
-            members,
-        })
+impl<R: Read> ParseFromContext<R> for BinaryArray {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
+        let (mut array, length) = Self::parse_header(reader)?;
+
+        array.members = read_elements(reader, length, array.additional_info.as_ref(), context)?;
+        Ok(array)
     }
 }
 
 impl<W: Write> UnparseTo<W> for BinaryArray {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::BinaryArray)?;
-        writer.unparse(self.object_id)?;
-        writer.unparse(self.binary_array_type)?;
-        writer.unparse(self.rank)?;
-        writer.unparse(self.lengths)?;
-        writer.unparse(self.lower_bounds)?;
-        writer.unparse(self.binary_type)?;
-        writer.unparse(self.additional_info)?;
-        writer.unparse(self.members)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::BinaryArray)?;
+        writer.unparse(&self.object_id)?;
+        writer.unparse(&self.binary_array_type)?;
+        writer.unparse(&self.rank)?;
+        writer.unparse(&self.lengths)?;
+        writer.unparse(&self.lower_bounds)?;
+        writer.unparse(&self.binary_type)?;
+        writer.unparse(&self.additional_info)?;
+        writer.unparse(&self.members)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ArraySingleString {
+pub struct ArraySingleString {
     pub array_info: ArrayInfo,
-    pub members: Vec<String>,
+    pub members: Vec<Record>,
 }
 
-impl<R: Read> ParseFrom<R> for ArraySingleString {
-    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+impl<R: Read> ParseFromContext<R> for ArraySingleString {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
         let array_info: ArrayInfo = reader.parse()?;
-        let members = reader.parse_sized(array_info.length as usize)?;
+        let members = read_elements(reader, array_info.length as usize, None, context)?;
 
         Ok(Self {
             array_info,
@@ -318,15 +447,15 @@ impl<R: Read> ParseFrom<R> for ArraySingleString {
 }
 
 impl<W: Write> UnparseTo<W> for ArraySingleString {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::ArraySingleString)?;
-        writer.unparse(self.array_info)?;
-        writer.unparse(self.members)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::ArraySingleString)?;
+        writer.unparse(&self.array_info)?;
+        writer.unparse(&self.members)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct BinaryMethodCall {
+pub struct BinaryMethodCall {
     pub message_flags: MessageFlags,
     pub method_name: StringValueWithCode,
     pub type_name: StringValueWithCode,
@@ -361,18 +490,18 @@ impl<R: Read> ParseFrom<R> for BinaryMethodCall {
 }
 
 impl<W: Write> UnparseTo<W> for BinaryMethodCall {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::MethodCall)?;
-        writer.unparse(self.message_flags)?;
-        writer.unparse(self.method_name)?;
-        writer.unparse(self.type_name)?;
-        writer.unparse(self.call_context)?;
-        writer.unparse(self.args)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::MethodCall)?;
+        writer.unparse(&self.message_flags)?;
+        writer.unparse(&self.method_name)?;
+        writer.unparse(&self.type_name)?;
+        writer.unparse(&self.call_context)?;
+        writer.unparse(&self.args)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct BinaryMethodReturn {
+pub struct BinaryMethodReturn {
     pub message_flags: MessageFlags,
     pub return_value: Option<ValueWithCode>,
     pub call_context: Option<StringValueWithCode>,
@@ -408,96 +537,93 @@ impl<R: Read> ParseFrom<R> for BinaryMethodReturn {
 }
 
 impl<W: Write> UnparseTo<W> for BinaryMethodReturn {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::MethodReturn)?;
-        writer.unparse(self.message_flags)?;
-        writer.unparse(self.return_value)?;
-        writer.unparse(self.call_context)?;
-        writer.unparse(self.args)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::MethodReturn)?;
+        writer.unparse(&self.message_flags)?;
+        writer.unparse(&self.return_value)?;
+        writer.unparse(&self.call_context)?;
+        writer.unparse(&self.args)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ClassWithMembers {
+pub struct ClassWithMembers {
     pub class_info: ClassInfo,
     pub library_id: i32,
-    pub data: Vec<Vec<u8>>,
+    pub member_references: Vec<Record>,
 }
 
-impl<R: Read> ParseFromSized<R> for ClassWithMembers {
-    fn parse_from_sized(reader: &mut R, size: usize) -> Result<Self, ParseError> {
+impl<R: Read> ParseFromContext<R> for ClassWithMembers {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
         let class_info: ClassInfo = reader.parse()?;
         let library_id = reader.parse()?;
-        let mut data = vec![];
+        let member_type_info = MemberTypeInfo::untyped(class_info.member_names.len());
 
-        for _ in 0..class_info.member_count {
-            let mut member_data = vec![0; size];
-            reader.read_exact(member_data.as_mut_slice())?;
-            data.push(member_data);
-        }
+        context
+            .member_type_infos
+            .insert(class_info.object_id, member_type_info.clone());
+
+        let member_references = read_references(reader, &member_type_info, context)?;
 
         Ok(Self {
             class_info,
             library_id,
-            data,
+            member_references,
         })
     }
 }
 
 impl<W: Write> UnparseTo<W> for ClassWithMembers {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::ClassWithMembers)?;
-        writer.unparse(self.class_info)?;
-        writer.unparse(self.library_id)?;
-        writer.unparse(self.data)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::ClassWithMembers)?;
+        writer.unparse(&self.class_info)?;
+        writer.unparse(&self.library_id)?;
+        writer.unparse(&self.member_references)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct SystemClassWithMembers {
+pub struct SystemClassWithMembers {
     pub class_info: ClassInfo,
-    pub data: Vec<Vec<u8>>,
+    pub member_references: Vec<Record>,
 }
 
-impl<R: Read> ParseFromSized<R> for SystemClassWithMembers {
-    fn parse_from_sized(reader: &mut R, size: usize) -> Result<Self, ParseError> {
+impl<R: Read> ParseFromContext<R> for SystemClassWithMembers {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
         let class_info: ClassInfo = reader.parse()?;
-        let mut data = vec![];
+        let member_type_info = MemberTypeInfo::untyped(class_info.member_names.len());
 
-        for _ in 0..class_info.member_count {
-            let mut member_data = vec![0; size];
-            reader.read_exact(member_data.as_mut_slice())?;
-            data.push(member_data);
-        }
+        context
+            .member_type_infos
+            .insert(class_info.object_id, member_type_info.clone());
+
+        let member_references = read_references(reader, &member_type_info, context)?;
 
-        Ok(Self { class_info, data })
+        Ok(Self {
+            class_info,
+            member_references,
+        })
     }
 }
 
 impl<W: Write> UnparseTo<W> for SystemClassWithMembers {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::SystemClassWithMembers)?;
-        writer.unparse(self.class_info)?;
-        writer.unparse(self.data)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::SystemClassWithMembers)?;
+        writer.unparse(&self.class_info)?;
+        writer.unparse(&self.member_references)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct ArraySingleObject {
+pub struct ArraySingleObject {
     pub array_info: ArrayInfo,
-    pub members: Vec<Vec<u8>>,
+    pub members: Vec<Record>,
 }
 
-impl<R: Read> ParseFromSized<R> for ArraySingleObject {
-    fn parse_from_sized(reader: &mut R, size: usize) -> Result<Self, ParseError> {
+impl<R: Read> ParseFromContext<R> for ArraySingleObject {
+    fn parse_from_context(reader: &mut R, context: &mut ParseContext) -> Result<Self, ParseError> {
         let array_info: ArrayInfo = reader.parse()?;
-        let mut members = vec![];
-
-        for _ in 0..array_info.length {
-            let mut member_data = vec![0; size];
-            reader.read_exact(member_data.as_mut_slice())?;
-            members.push(member_data);
-        }
+        let members = read_elements(reader, array_info.length as usize, None, context)?;
 
         Ok(Self {
             array_info,
@@ -507,14 +633,15 @@ impl<R: Read> ParseFromSized<R> for ArraySingleObject {
 }
 
 impl<W: Write> UnparseTo<W> for ArraySingleObject {
-    fn unparse_to(self, writer: &mut W) -> Result<(), io::Error> {
-        writer.unparse(RecordType::ArraySingleObject)?;
-        writer.unparse(self.array_info)?;
-        writer.unparse(self.members)
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&RecordType::ArraySingleObject)?;
+        writer.unparse(&self.array_info)?;
+        writer.unparse(&self.members)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[allow(dead_code)]
 pub(crate) struct MethodCallArray {
     pub input_arguments: Option<Vec<()>>,
     pub generic_type_arguments: Option<Vec<()>>,
@@ -524,6 +651,7 @@ pub(crate) struct MethodCallArray {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[allow(dead_code)]
 pub(crate) struct MethodReturnCallArray {
     pub return_value: Option<()>,
     pub output_arguments: Option<Vec<()>>,
@@ -0,0 +1,480 @@
+use crate::{
+    array::PrimitiveArray,
+    enums::Primitive,
+    stream::{Class, ClassArray, Field, Stream},
+};
+use indexmap::IndexMap;
+use serde::ser::{self, Impossible, Serialize};
+use std::{
+    fmt::Display,
+    io::{self, Write},
+};
+use thiserror::Error;
+
+pub(crate) const DICTIONARY_NAME: &str = "System.Collections.Generic.Dictionary`2";
+const KEY_VALUE_PAIR_NAME: &str = "System.Collections.Generic.KeyValuePair`2";
+const GENERIC_COMPARER_NAME: &str = "System.Collections.Generic.GenericEqualityComparer`1";
+const OBJECT_COMPARER_NAME: &str = "System.Collections.Generic.ObjectEqualityComparer`1";
+const MSCORLIB: &str =
+    "mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089";
+pub(crate) const ENUM_VALUE_NAME: &str = "value__";
+
+#[derive(Error, Debug)]
+pub enum SerializeError {
+    #[error("failed to write buffer")]
+    IoError(#[from] io::Error),
+    #[error("{0}")]
+    Custom(String),
+    #[error("cannot serialize {0} as nrbf")]
+    UnsupportedType(&'static str),
+    #[error("the root value must serialize to a class")]
+    RootNotClass,
+}
+
+impl ser::Error for SerializeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// Decides the .NET type and assembly names of serialized structs and enums.
+///
+/// A serde name that is already assembly qualified, such as
+/// `#[serde(rename = "Game.Player, Game, Version=1.0.0.0")]`, is used as is. Other names are
+/// prefixed with `namespace` and placed in `library_name`, or treated as system classes when
+/// no library is set.
+#[derive(Debug, Clone, Default)]
+pub struct Naming {
+    pub namespace: Option<String>,
+    pub library_name: Option<String>,
+}
+
+impl Naming {
+    fn class(&self, name: &str) -> Class {
+        let (name, library_name) = match name.split_once(", ") {
+            Some((name, library_name)) => (name.to_string(), Some(library_name.to_string())),
+            None => (
+                match &self.namespace {
+                    Some(namespace) if !name.contains('.') => format!("{namespace}.{name}"),
+                    _ => name.to_string(),
+                },
+                self.library_name.clone(),
+            ),
+        };
+
+        Class {
+            library_name,
+            name,
+            fields: IndexMap::new(),
+        }
+    }
+}
+
+pub fn to_stream<T: Serialize + ?Sized>(
+    value: &T,
+    naming: &Naming,
+) -> Result<Stream, SerializeError> {
+    match value.serialize(FieldSerializer { naming })? {
+        Field::Class(root) => Ok(Stream { root }),
+        _ => Err(SerializeError::RootNotClass),
+    }
+}
+
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(
+    writer: &mut W,
+    value: &T,
+) -> Result<(), SerializeError> {
+    to_writer_with(writer, value, &Naming::default())
+}
+
+pub fn to_writer_with<W: Write, T: Serialize + ?Sized>(
+    writer: &mut W,
+    value: &T,
+    naming: &Naming,
+) -> Result<(), SerializeError> {
+    Ok(to_stream(value, naming)?.encode(writer)?)
+}
+
+struct FieldSerializer<'a> {
+    naming: &'a Naming,
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+    type Ok = Field;
+    type Error = SerializeError;
+
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = Impossible<Field, SerializeError>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = Impossible<Field, SerializeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::Boolean(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::SByte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::Int16(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::Int32(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::Int64(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::Byte(v)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::UInt16(v)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::UInt32(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::UInt64(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::Single(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::Char(v)))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Field, SerializeError> {
+        Ok(Field::Primitive(Primitive::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Field, SerializeError> {
+        Ok(Field::PrimitiveArray(PrimitiveArray::Byte(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Field, SerializeError> {
+        Ok(Field::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Field, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Field, SerializeError> {
+        Ok(Field::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Field, SerializeError> {
+        Ok(Field::Null)
+    }
+
+    // .NET enums are classes with a single `value__` member holding the underlying value.
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Field, SerializeError> {
+        let mut class = self.naming.class(name);
+
+        class.fields.insert(
+            ENUM_VALUE_NAME.to_string(),
+            Field::Primitive(Primitive::Int32(variant_index as i32)),
+        );
+
+        Ok(Field::Class(class))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Field, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Field, SerializeError> {
+        Err(SerializeError::UnsupportedType("newtype variant"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer<'a>, SerializeError> {
+        Ok(SeqSerializer {
+            naming: self.naming,
+            elements: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>, SerializeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, SerializeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerializeError> {
+        Err(SerializeError::UnsupportedType("tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>, SerializeError> {
+        Ok(MapSerializer {
+            naming: self.naming,
+            pairs: vec![],
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer<'a>, SerializeError> {
+        Ok(StructSerializer {
+            naming: self.naming,
+            class: self.naming.class(name),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerializeError> {
+        Err(SerializeError::UnsupportedType("struct variant"))
+    }
+}
+
+struct SeqSerializer<'a> {
+    naming: &'a Naming,
+    elements: Vec<Field>,
+}
+
+impl<'a> ser::SerializeSeq for SeqSerializer<'a> {
+    type Ok = Field;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.elements.push(value.serialize(FieldSerializer {
+            naming: self.naming,
+        })?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Field, SerializeError> {
+        // Serde doesn't say what an empty sequence would have held, so it becomes an object
+        // array.
+        Ok(Field::from_elements(self.elements, None))
+    }
+}
+
+impl<'a> ser::SerializeTuple for SeqSerializer<'a> {
+    type Ok = Field;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Field, SerializeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for SeqSerializer<'a> {
+    type Ok = Field;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Field, SerializeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+// Maps are written the way .NET Framework writes a `Dictionary<TKey, TValue>`: a system class
+// holding the key type's default comparer and a `KeyValuePair<TKey, TValue>[]`. The type
+// arguments are the type all keys, or all values, share, and `System.Object` otherwise.
+struct MapSerializer<'a> {
+    naming: &'a Naming,
+    pairs: Vec<(Field, Field)>,
+    key: Option<Field>,
+}
+
+impl<'a> ser::SerializeMap for MapSerializer<'a> {
+    type Ok = Field;
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerializeError> {
+        self.key = Some(key.serialize(FieldSerializer {
+            naming: self.naming,
+        })?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        let key = self.key.take().unwrap_or(Field::Null);
+        let value = value.serialize(FieldSerializer {
+            naming: self.naming,
+        })?;
+
+        self.pairs.push((key, value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Field, SerializeError> {
+        let length = self.pairs.len() as i32;
+        let (key_type, key_equatable) = type_argument(self.pairs.iter().map(|(key, _)| key));
+        let (value_type, _) = type_argument(self.pairs.iter().map(|(_, value)| value));
+        let arguments = format!("[[{key_type}],[{value_type}]]");
+        let comparer_name = if key_equatable {
+            GENERIC_COMPARER_NAME
+        } else {
+            OBJECT_COMPARER_NAME
+        };
+        let mut fields = IndexMap::new();
+
+        fields.insert(
+            "Version".to_string(),
+            Field::Primitive(Primitive::Int32(length)),
+        );
+        fields.insert(
+            "Comparer".to_string(),
+            Field::Class(Class::new(None, format!("{comparer_name}[[{key_type}]]"))),
+        );
+        // An empty dictionary has no buckets, and then no pairs either.
+        fields.insert(
+            "HashSize".to_string(),
+            Field::Primitive(Primitive::Int32(length)),
+        );
+
+        if !self.pairs.is_empty() {
+            let pair_name = format!("{KEY_VALUE_PAIR_NAME}{arguments}");
+            let pairs = self
+                .pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    let mut pair = Class::new(None, pair_name.clone());
+
+                    pair.fields.insert("key".to_string(), key);
+                    pair.fields.insert("value".to_string(), value);
+                    Field::Class(pair)
+                })
+                .collect();
+
+            fields.insert(
+                "KeyValuePairs".to_string(),
+                Field::ClassArray(ClassArray {
+                    library_name: None,
+                    name: pair_name,
+                    elements: pairs,
+                }),
+            );
+        }
+
+        Ok(Field::Class(Class {
+            library_name: None,
+            name: format!("{DICTIONARY_NAME}{arguments}"),
+            fields,
+        }))
+    }
+}
+
+// The assembly qualified name of the type the values share, and whether it is a primitive or
+// string, which `GenericEqualityComparer<T>` needs.
+fn type_argument<'f>(values: impl Iterator<Item = &'f Field>) -> (String, bool) {
+    let mut shared = None;
+
+    for value in values {
+        let name = match value {
+            Field::Primitive(Primitive::Null) | Field::Null => continue,
+            Field::Primitive(primitive) => {
+                (format!("System.{:?}", primitive.get_type()), None, true)
+            }
+            Field::Class(class) => (class.name.clone(), class.library_name.clone(), false),
+            _ => return (format!("System.Object, {MSCORLIB}"), false),
+        };
+
+        match &shared {
+            None => shared = Some(name),
+            Some(shared) if *shared == name => {}
+            Some(_) => return (format!("System.Object, {MSCORLIB}"), false),
+        }
+    }
+
+    match shared {
+        Some((name, library_name, equatable)) => (
+            format!("{name}, {}", library_name.as_deref().unwrap_or(MSCORLIB)),
+            equatable,
+        ),
+        None => (format!("System.Object, {MSCORLIB}"), false),
+    }
+}
+
+struct StructSerializer<'a> {
+    naming: &'a Naming,
+    class: Class,
+}
+
+impl<'a> ser::SerializeStruct for StructSerializer<'a> {
+    type Ok = Field;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.class.fields.insert(
+            key.to_string(),
+            value.serialize(FieldSerializer {
+                naming: self.naming,
+            })?,
+        );
+        Ok(())
+    }
+
+    fn end(self) -> Result<Field, SerializeError> {
+        Ok(Field::Class(self.class))
+    }
+}
@@ -1,15 +1,26 @@
 use crate::{
-    common::{ArrayInfo, ClassInfo, ClassTypeInfo, MemberTypeInfo},
-    enums::{AdditionalInfo, BinaryType, Primitive, PrimitiveType, Record},
-    parse::{Parse, ParseError},
-    records::{ArraySinglePrimitive, BinaryLibrary, ClassWithMembersAndTypes, SerializationHeader},
-    unparse::Unparse,
+    array::PrimitiveArray,
+    borrowed::BorrowedStream,
+    common::{ClassInfo, ClassTypeInfo, MemberTypeInfo},
+    enums::{AdditionalInfo, BinaryArrayType, BinaryType, Primitive, PrimitiveType, Record},
+    parse::ParseError,
+    reader::RecordReader,
+    records::{BinaryLibrary, RecordType, SerializationHeader},
+    unparse::{Unparse, UnparseTo},
 };
-use chrono::{NaiveDateTime, NaiveTime};
 use indexmap::IndexMap;
 use std::{
-    collections::BTreeMap,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{self, Debug},
     io::{self, Read, Write},
+    mem::{self, size_of},
+    rc::Rc,
+    sync::Arc,
+};
+#[cfg(feature = "async")]
+use {
+    crate::reader::AsyncRecordReader,
+    tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt},
 };
 
 #[derive(Debug)]
@@ -18,205 +29,355 @@ pub struct Stream {
 }
 
 impl Stream {
+    /// The id that [`Field::Reference`] uses for the root class.
+    pub const ROOT_ID: i32 = 1;
+
     pub fn decode<R: Read>(reader: &mut R) -> Result<Self, ParseError> {
-        let records: Vec<Record> = reader.parse()?;
-
-        let mut objects = BTreeMap::new();
-        let mut libraries = BTreeMap::new();
-        let mut root_id = None;
-        let mut root = None;
-
-        for record in records {
-            match record {
-                Record::SerializationHeader(header) => root_id = Some(header.root_id),
-                Record::ClassWithId(class) => {
-                    objects.insert(
-                        class.object_id,
-                        Record::ClassWithId(class),
-                    );
-                }
-                Record::BinaryLibrary(library) => {
-                    libraries.insert(library.library_id, library.library_name);
-                }
-                Record::MessageEnd => (),
-                Record::ClassWithMembersAndTypes(class) => {
-                    if class.class_info.object_id == root_id.unwrap() {
-                        root = Some(class.clone());
-                    }
+        Self::decode_with(reader, DecodeOptions::default())
+    }
 
-                    objects.insert(
-                        class.class_info.object_id,
-                        Record::ClassWithMembersAndTypes(class),
-                    );
-                }
-                Record::ArraySinglePrimitive(array) => {
-                    objects.insert(
-                        array.array_info.object_id,
-                        Record::ArraySinglePrimitive(array),
-                    );
-                }
-                Record::BinaryArray(array) => {
-                    println!("{:?}", array);
-                    objects.insert(array.object_id, Record::BinaryArray(array));
-                }
-                other => todo!("{:?}", other),
-            }
+    pub fn decode_with<R: Read>(
+        reader: &mut R,
+        options: DecodeOptions,
+    ) -> Result<Self, ParseError> {
+        let mut decoder = StreamDecoder {
+            keep_references: options.keep_references,
+            ..Default::default()
+        };
+
+        for record in RecordReader::new(reader) {
+            decoder.push(record?)?;
         }
 
-        Ok(Self {
-            root: StreamDecoderState { objects, libraries }.decode_class(&root.unwrap()),
-        })
+        decoder.finish()
     }
 
-    pub fn encode<W: Write>(self, writer: &mut W) -> Result<(), io::Error> {
-        let mut records = vec![];
+    /// Decodes without copying strings or byte arrays out of `bytes`.
+    pub fn decode_borrowed(bytes: &[u8]) -> Result<BorrowedStream<'_>, ParseError> {
+        BorrowedStream::decode(bytes)
+    }
 
-        records.push(Record::SerializationHeader(
-            SerializationHeader {
-                root_id: 1,
-                header_id: -1,
-                major_version: 1,
-                minor_version: 0,
-            },
-        ));
+    #[cfg(feature = "async")]
+    pub async fn decode_async<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Self, ParseError> {
+        let mut reader = AsyncRecordReader::new(reader);
+        let mut decoder = StreamDecoder::default();
+
+        while let Some(record) = reader.next().await {
+            decoder.push(record?)?;
+        }
+
+        decoder.finish()
+    }
 
-        let mut state = StreamEncoderState::new();
-        let mut new_records = state.encode_class(self.root);
+    pub fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
+        self.encode_with(writer, EncodeOptions::default())
+    }
+
+    pub fn encode_with<W: Write>(
+        &self,
+        writer: &mut W,
+        options: EncodeOptions,
+    ) -> Result<(), io::Error> {
+        StreamEncoder::new(writer, options).encode_root(&self.root)?;
+        writer.unparse(&Record::MessageEnd)
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn encode_async<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), io::Error> {
+        self.encode_with_async(writer, EncodeOptions::default())
+            .await
+    }
 
-        for (library_name, library_id) in state.libraries {
-            records.push(Record::BinaryLibrary(BinaryLibrary {
-                library_id,
-                library_name,
-            }));
+    /// Encodes with the blocking encoder into a buffer, which is written to `writer` whenever it
+    /// holds more than a few kilobytes, so only about one record is kept in memory at a time.
+    #[cfg(feature = "async")]
+    pub async fn encode_with_async<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        options: EncodeOptions,
+    ) -> Result<(), io::Error> {
+        const FLUSH_SIZE: usize = 8 * 1024;
+
+        let mut buffer = vec![];
+        let mut encoder = StreamEncoder::new(&mut buffer, options);
+
+        encoder.start(&self.root)?;
+
+        while encoder.encode_pending()? {
+            if encoder.writer.len() >= FLUSH_SIZE {
+                writer.write_all(encoder.writer).await?;
+                encoder.writer.clear();
+            }
         }
 
-        records.append(&mut new_records);
-        records.push(Record::MessageEnd);
-        writer.unparse(records)
+        encoder.finish()?;
+        encoder.writer.unparse(&Record::MessageEnd)?;
+
+        writer.write_all(encoder.writer).await?;
+        writer.flush().await
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// Keep each object that is referenced from several places, or from inside itself, as one
+    /// [`Field::Shared`] and [`Field::Reference`]s to it. Otherwise such objects are copied into
+    /// every place that refers to them, and references that would form a cycle are left `Null`.
+    pub keep_references: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LibraryLayout {
+    /// All `BinaryLibrary` records are written in one block after the header.
+    #[default]
+    Grouped,
+    /// Each `BinaryLibrary` record is written immediately before the first class that uses it,
+    /// matching the layout produced by BinaryFormatter.
+    Interleaved,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    pub library_layout: LibraryLayout,
+    /// Write classes as `ClassWithMembers`/`SystemClassWithMembers` records, without member
+    /// type information. Primitive members are then written as `MemberPrimitiveTyped` records.
+    pub omit_member_types: bool,
+    /// Write each distinct string once and refer back to it with `MemberReference` records.
+    pub intern_strings: bool,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            library_layout: LibraryLayout::default(),
+            omit_member_types: false,
+            intern_strings: true,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Class {
-    pub library_name: String,
+    /// The assembly the class belongs to, or `None` for system classes from mscorlib.
+    pub library_name: Option<String>,
     pub name: String,
     pub fields: IndexMap<String, Field>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum PrimitiveArray {
-    Boolean(Vec<bool>),
-    Byte(Vec<u8>),
-    Char(Vec<char>),
-    Decimal(Vec<String>),
-    Double(Vec<f64>),
-    Int16(Vec<i16>),
-    Int32(Vec<i32>),
-    Int64(Vec<i64>),
-    SByte(Vec<i8>),
-    Single(Vec<f32>),
-    TimeSpan(Vec<NaiveTime>),
-    DateTime(Vec<NaiveDateTime>),
-    UInt16(Vec<u16>),
-    UInt32(Vec<u32>),
-    UInt64(Vec<u64>),
-    Null,
-    String(Vec<String>),
+impl Class {
+    pub fn new(library_name: Option<String>, name: impl Into<String>) -> Self {
+        Self {
+            library_name,
+            name: name.into(),
+            fields: IndexMap::new(),
+        }
+    }
 }
 
-macro_rules! into_field {
-    ($primitive_type:ident, $array:expr) => {
-        PrimitiveArray::$primitive_type(
-            $array
-                .iter()
-                .map(|primitive| {
-                    if let Primitive::$primitive_type(value) = primitive {
-                        value.clone()
-                    } else {
-                        unreachable!()
-                    }
-                })
-                .collect(),
-        )
-    };
+/// An array whose elements all have one class type, like `Game.Item[]`, rather than `object[]`.
+/// Elements can also be null, or instances of subclasses.
+#[derive(Debug, Clone)]
+pub struct ClassArray {
+    /// The assembly the element class belongs to, or `None` for system classes from mscorlib.
+    pub library_name: Option<String>,
+    /// The element class, like `Game.Item`.
+    pub name: String,
+    pub elements: Vec<Field>,
 }
 
-macro_rules! from_field {
-    ($primitive_type:ident, $value:expr) => {
-        $value
-            .iter()
-            .map(|value| Primitive::$primitive_type(value.clone()))
-            .collect()
+/// An element type that can be written into a [`StreamedArray`].
+pub trait ArrayElement: Copy {
+    const PRIMITIVE_TYPE: PrimitiveType;
+
+    fn extend_le_bytes(self, bytes: &mut Vec<u8>);
+}
+
+macro_rules! array_element {
+    ($primitive_type:ident, $type:ty) => {
+        impl ArrayElement for $type {
+            const PRIMITIVE_TYPE: PrimitiveType = PrimitiveType::$primitive_type;
+
+            fn extend_le_bytes(self, bytes: &mut Vec<u8>) {
+                bytes.extend_from_slice(&self.to_le_bytes());
+            }
+        }
     };
 }
 
-impl PrimitiveArray {
-    fn get_type(&self) -> PrimitiveType {
-        match self {
-            Self::Boolean(_) => PrimitiveType::Boolean,
-            Self::Byte(_) => PrimitiveType::Byte,
-            Self::Char(_) => PrimitiveType::Char,
-            Self::Decimal(_) => PrimitiveType::Decimal,
-            Self::Double(_) => PrimitiveType::Double,
-            Self::Int16(_) => PrimitiveType::Int16,
-            Self::Int32(_) => PrimitiveType::Int32,
-            Self::Int64(_) => PrimitiveType::Int64,
-            Self::SByte(_) => PrimitiveType::SByte,
-            Self::Single(_) => PrimitiveType::Single,
-            Self::TimeSpan(_) => PrimitiveType::TimeSpan,
-            Self::DateTime(_) => PrimitiveType::DateTime,
-            Self::UInt16(_) => PrimitiveType::UInt16,
-            Self::UInt32(_) => PrimitiveType::UInt32,
-            Self::UInt64(_) => PrimitiveType::UInt64,
-            Self::Null => PrimitiveType::Null,
-            Self::String(_) => PrimitiveType::String,
-        }
-    }
-
-    fn into_field(array: Vec<Primitive>, primitive_type: PrimitiveType) -> Self {
-        match primitive_type {
-            PrimitiveType::Boolean => into_field!(Boolean, array),
-            PrimitiveType::Byte => into_field!(Byte, array),
-            PrimitiveType::Char => into_field!(Char, array),
-            PrimitiveType::Decimal => into_field!(Decimal, array),
-            PrimitiveType::Double => into_field!(Double, array),
-            PrimitiveType::Int16 => into_field!(Int16, array),
-            PrimitiveType::Int32 => into_field!(Int32, array),
-            PrimitiveType::Int64 => into_field!(Int64, array),
-            PrimitiveType::SByte => into_field!(SByte, array),
-            PrimitiveType::Single => into_field!(Single, array),
-            PrimitiveType::TimeSpan => into_field!(TimeSpan, array),
-            PrimitiveType::DateTime => into_field!(DateTime, array),
-            PrimitiveType::UInt16 => into_field!(UInt16, array),
-            PrimitiveType::UInt32 => into_field!(UInt32, array),
-            PrimitiveType::UInt64 => into_field!(UInt64, array),
-            PrimitiveType::Null => Self::Null,
-            PrimitiveType::String => into_field!(String, array),
+array_element!(Byte, u8);
+array_element!(Double, f64);
+array_element!(Int16, i16);
+array_element!(Int32, i32);
+array_element!(Int64, i64);
+array_element!(SByte, i8);
+array_element!(Single, f32);
+array_element!(UInt16, u16);
+array_element!(UInt32, u32);
+array_element!(UInt64, u64);
+
+impl ArrayElement for bool {
+    const PRIMITIVE_TYPE: PrimitiveType = PrimitiveType::Boolean;
+
+    fn extend_le_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.push(self as u8);
+    }
+}
+
+// Hands each chunk of little-endian element bytes to the sink and returns the element count.
+type ArraySource = dyn Fn(&mut dyn FnMut(&[u8]) -> Result<(), io::Error>) -> Result<usize, io::Error>
+    + Send
+    + Sync;
+
+/// A primitive array whose elements are produced while the stream is being encoded, so that
+/// arrays too large to hold in memory can be written straight to the output.
+///
+/// The source is called again every time the array is written, and must produce exactly
+/// `length` elements.
+#[derive(Clone)]
+pub struct StreamedArray {
+    primitive_type: PrimitiveType,
+    length: usize,
+    source: Arc<ArraySource>,
+}
+
+impl StreamedArray {
+    const CHUNK_LENGTH: usize = 4096;
+
+    pub fn from_iter<T, I, F>(length: usize, elements: F) -> Self
+    where
+        T: ArrayElement,
+        I: IntoIterator<Item = T>,
+        F: Fn() -> I + Send + Sync + 'static,
+    {
+        Self {
+            primitive_type: T::PRIMITIVE_TYPE,
+            length,
+            source: Arc::new(move |sink| {
+                let mut bytes = Vec::with_capacity(Self::CHUNK_LENGTH * size_of::<T>());
+                let mut count = 0;
+
+                for element in elements() {
+                    element.extend_le_bytes(&mut bytes);
+                    count += 1;
+
+                    if count % Self::CHUNK_LENGTH == 0 {
+                        sink(&bytes)?;
+                        bytes.clear();
+                    }
+                }
+
+                sink(&bytes)?;
+                Ok(count)
+            }),
         }
     }
+
+    /// Produces each element by calling `element` with its index.
+    pub fn from_fn<T, F>(length: usize, element: F) -> Self
+    where
+        T: ArrayElement,
+        F: Fn(usize) -> T + Send + Sync + 'static,
+    {
+        let element = Arc::new(element);
+
+        Self::from_iter(length, move || {
+            let element = element.clone();
+            (0..length).map(move |index| element(index))
+        })
+    }
+
+    pub fn get_type(&self) -> PrimitiveType {
+        self.primitive_type
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Runs the source and collects every element it produces.
+    pub fn to_primitive_array(&self) -> PrimitiveArray {
+        let mut bytes = vec![];
+
+        (self.source)(&mut |chunk| {
+            bytes.extend_from_slice(chunk);
+            Ok(())
+        })
+        .expect("collecting into a buffer never fails");
+
+        PrimitiveArray::from_le_bytes(self.primitive_type, &bytes)
+    }
+
+    /// Collects the first `count` elements, or all of them if there are fewer. The source is
+    /// stopped once it has produced enough, so this is cheap for the start of a large array.
+    pub fn prefix(&self, count: usize) -> PrimitiveArray {
+        // `element_size` is known for every `ArrayElement` type.
+        let element_size = PrimitiveArray::element_size(self.primitive_type).unwrap();
+        let size = count.saturating_mul(element_size);
+        let mut bytes = vec![];
+
+        // Failing the sink is how the source is told to stop, so its result is ignored.
+        let _ = (self.source)(&mut |chunk| {
+            let wanted = chunk.len().min(size - bytes.len());
+            bytes.extend_from_slice(&chunk[..wanted]);
+
+            if bytes.len() < size {
+                Ok(())
+            } else {
+                Err(io::ErrorKind::Interrupted.into())
+            }
+        });
+
+        PrimitiveArray::from_le_bytes(self.primitive_type, &bytes)
+    }
 }
 
-impl From<PrimitiveArray> for Vec<Primitive> {
-    fn from(value: PrimitiveArray) -> Self {
-        match value {
-            PrimitiveArray::Boolean(value) => from_field!(Boolean, value),
-            PrimitiveArray::Byte(value) => from_field!(Byte, value),
-            PrimitiveArray::Char(value) => from_field!(Char, value),
-            PrimitiveArray::Decimal(value) => from_field!(Decimal, value),
-            PrimitiveArray::Double(value) => from_field!(Double, value),
-            PrimitiveArray::Int16(value) => from_field!(Int16, value),
-            PrimitiveArray::Int32(value) => from_field!(Int32, value),
-            PrimitiveArray::Int64(value) => from_field!(Int64, value),
-            PrimitiveArray::SByte(value) => from_field!(SByte, value),
-            PrimitiveArray::Single(value) => from_field!(Single, value),
-            PrimitiveArray::TimeSpan(value) => from_field!(TimeSpan, value),
-            PrimitiveArray::DateTime(value) => from_field!(DateTime, value),
-            PrimitiveArray::UInt16(value) => from_field!(UInt16, value),
-            PrimitiveArray::UInt32(value) => from_field!(UInt32, value),
-            PrimitiveArray::UInt64(value) => from_field!(UInt64, value),
-            PrimitiveArray::Null => vec![],
-            PrimitiveArray::String(value) => from_field!(String, value),
+impl Debug for StreamedArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamedArray")
+            .field("primitive_type", &self.primitive_type)
+            .field("length", &self.length)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: crate::io::Write> UnparseTo<W> for StreamedArray {
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        // `element_size` is known for every `ArrayElement` type.
+        let element_size = PrimitiveArray::element_size(self.primitive_type).unwrap();
+        let byte_length = self.length * element_size;
+        let mut written = 0;
+
+        let count = (self.source)(&mut |chunk| {
+            written += chunk.len();
+
+            if written > byte_length {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("array source produced more than {} elements", self.length),
+                ));
+            }
+
+            writer.write_all(chunk)
+        })?;
+
+        if count != self.length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "array source produced {count} elements, expected {}",
+                    self.length
+                ),
+            ));
         }
+
+        Ok(())
     }
 }
 
@@ -225,169 +386,1038 @@ pub enum Field {
     Primitive(Primitive),
     PrimitiveArray(PrimitiveArray),
     Class(Class),
+    ObjectArray(Vec<Field>),
+    ClassArray(ClassArray),
+    StreamedArray(StreamedArray),
+    /// An object that [`Field::Reference`]s elsewhere in the stream point to, with an id that
+    /// is unique within the stream. Only classes, arrays and strings can be shared.
+    Shared(i32, Box<Field>),
+    /// The [`Field::Shared`] object with this id, or the root for [`Stream::ROOT_ID`].
+    Reference(i32),
+    Null,
+}
+
+impl Field {
+    // Sequences become the most specific array .NET would use for them: a primitive array when
+    // every element is the same primitive type, a string array for strings and nulls, and an
+    // object array otherwise. An empty sequence has no elements to go by, so it uses
+    // `element_type`, the primitive type of the Rust elements when that is known.
+    pub(crate) fn from_elements(elements: Vec<Field>, element_type: Option<PrimitiveType>) -> Self {
+        let mut primitive_type = None;
+
+        for element in &elements {
+            let element_type = match element {
+                Field::Primitive(Primitive::Null) | Field::Null => PrimitiveType::Null,
+                Field::Primitive(primitive) => primitive.get_type(),
+                _ => return Field::ObjectArray(elements),
+            };
+
+            primitive_type = match (primitive_type, element_type) {
+                (None, element_type) => Some(element_type),
+                (Some(PrimitiveType::Null), PrimitiveType::String)
+                | (Some(PrimitiveType::String), PrimitiveType::Null) => Some(PrimitiveType::String),
+                (Some(primitive_type), element_type) if primitive_type == element_type => {
+                    Some(primitive_type)
+                }
+                _ => return Field::ObjectArray(elements),
+            };
+        }
+
+        match primitive_type.or(element_type) {
+            Some(PrimitiveType::Null) | None => Field::ObjectArray(elements),
+            Some(primitive_type) => Field::PrimitiveArray(PrimitiveArray::into_field(
+                elements
+                    .into_iter()
+                    .map(|element| match element {
+                        Field::Primitive(primitive) => primitive,
+                        _ => Primitive::Null,
+                    })
+                    .collect(),
+                primitive_type,
+            )),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct ClassMetadataKey<'a> {
+    library_id: Option<i32>,
+    name: &'a str,
+    member_names: Vec<&'a str>,
+    member_type_info: MemberTypeInfo,
 }
 
-struct StreamEncoderState {
-    libraries: BTreeMap<String, i32>,
+enum PendingRecord<'a> {
+    Class(&'a Class),
+    PrimitiveArray(&'a PrimitiveArray),
+    StreamedArray(&'a StreamedArray),
+    ObjectArray(&'a [Field]),
+    ClassArray(&'a ClassArray),
+}
+
+// A member whose ids have been handed out but which has not been written yet.
+enum Member<'a> {
+    Primitive(&'a Primitive),
+    String(i32, &'a str),
+    Reference(i32),
+    Null,
+}
+
+// Writes records straight from the object model as it is walked, borrowing every value.
+struct StreamEncoder<'a, 'w, W: Write> {
+    writer: &'w mut W,
+    options: EncodeOptions,
+    libraries: IndexMap<&'a str, i32>,
+    metadata: HashMap<ClassMetadataKey<'a>, i32>,
+    strings: HashMap<&'a str, i32>,
+    // Object ids for the ids of shared objects, and which of those have been written.
+    shared: HashMap<i32, i32>,
+    defined: HashSet<i32>,
+    pending: VecDeque<(i32, PendingRecord<'a>)>,
     counter: i32,
 }
 
-impl StreamEncoderState {
-    fn new() -> Self {
+impl<'a, 'w, W: Write> StreamEncoder<'a, 'w, W> {
+    fn new(writer: &'w mut W, options: EncodeOptions) -> Self {
         Self {
+            writer,
+            options,
+            libraries: IndexMap::new(),
+            metadata: HashMap::new(),
+            strings: HashMap::new(),
+            shared: HashMap::new(),
+            defined: HashSet::new(),
+            pending: VecDeque::new(),
             counter: 1,
-            libraries: BTreeMap::new(),
         }
     }
 
-    fn encode_class(&mut self, class: Class) -> Vec<Record> {
-        let mut records = vec![];
-        let mut member_names = vec![];
-        let mut member_types = vec![];
-        let mut additional_info = vec![];
-        let mut member_references = vec![];
-
-        let object_id = self.counter;
+    fn next_id(&mut self) -> i32 {
+        let id = self.counter;
         self.counter += 1;
+        id
+    }
 
-        self.libraries
-            .insert(class.library_name.clone(), self.counter);
-        self.counter += 1;
+    fn library_id(&mut self, library_name: &'a str) -> Result<i32, io::Error> {
+        if let Some(library_id) = self.libraries.get(library_name) {
+            return Ok(*library_id);
+        }
+
+        let library_id = self.next_id();
+
+        if self.options.library_layout == LibraryLayout::Interleaved {
+            self.write_library(library_id, library_name)?;
+        }
+
+        self.libraries.insert(library_name, library_id);
+        Ok(library_id)
+    }
+
+    fn write_library(&mut self, library_id: i32, library_name: &str) -> Result<(), io::Error> {
+        self.writer.unparse(&RecordType::BinaryLibrary)?;
+        self.writer.unparse(&library_id)?;
+        self.writer.unparse(library_name)
+    }
+
+    // Grouped libraries have to be written before any object, so they are found and numbered
+    // up front.
+    fn collect_libraries(&mut self, class: &'a Class) -> Result<(), io::Error> {
+        if let Some(library_name) = &class.library_name {
+            self.library_id(library_name)?;
+        }
+
+        let mut fields: VecDeque<&Field> = class.fields.values().collect();
+
+        while let Some(field) = fields.pop_front() {
+            match field {
+                Field::Class(class) => {
+                    if let Some(library_name) = &class.library_name {
+                        self.library_id(library_name)?;
+                    }
 
-        for (field_name, field_value) in class.fields {
-            member_names.push(field_name);
-            match field_value {
-                Field::Primitive(value) => {
-                    member_types.push(BinaryType::Primitive_);
-                    additional_info.push(AdditionalInfo::Primitive(
-                        value.get_type(),
-                    ));
-                    member_references.push(Record::MemberPrimitiveUnTyped(value));
+                    fields.extend(class.fields.values());
                 }
-                Field::PrimitiveArray(value) => {
-                    let primitive_type = value.get_type();
-                    let array: Vec<Primitive> = value.into();
-
-                    member_types.push(BinaryType::PrimitiveArray);
-                    additional_info.push(AdditionalInfo::PrimitiveArray(
-                        primitive_type,
-                    ));
-                    member_references.push(Record::MemberReference { id: self.counter });
-                    records.push(Record::ArraySinglePrimitive(
-                        ArraySinglePrimitive {
-                            array_info: ArrayInfo {
-                                object_id: self.counter,
-                                length: array.len() as i32,
-                            },
-                            primitive_type,
-                            members: array,
-                        },
-                    ));
-                    self.counter += 1;
+                Field::ObjectArray(elements) => fields.extend(elements),
+                Field::ClassArray(array) => {
+                    if let Some(library_name) = &array.library_name {
+                        self.library_id(library_name)?;
+                    }
+
+                    fields.extend(&array.elements);
                 }
-                Field::Class(value) => {
-                    records.append(&mut self.encode_class(value.clone()));
-                    member_types.push(BinaryType::Class);
-                    additional_info.push(AdditionalInfo::Class(ClassTypeInfo {
-                        type_name: value.name,
-                        library_id: self.libraries[&value.library_name],
-                    }));
-                    member_references.push(Record::MemberReference { id: self.counter });
-                    self.counter += 1;
+                Field::Shared(_, value) => fields.push_back(value),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_root(&mut self, root: &'a Class) -> Result<(), io::Error> {
+        self.start(root)?;
+        while self.encode_pending()? {}
+        self.finish()
+    }
+
+    // Writes the header and grouped libraries, and queues the root.
+    fn start(&mut self, root: &'a Class) -> Result<(), io::Error> {
+        let root_id = self.next_id();
+
+        self.shared.insert(Stream::ROOT_ID, root_id);
+        self.defined.insert(Stream::ROOT_ID);
+
+        if self.options.library_layout == LibraryLayout::Grouped {
+            self.collect_libraries(root)?;
+        }
+
+        self.writer.unparse(&SerializationHeader {
+            root_id,
+            header_id: -1,
+            major_version: 1,
+            minor_version: 0,
+        })?;
+
+        for (library_name, library_id) in self.libraries.clone() {
+            self.write_library(library_id, library_name)?;
+        }
+
+        self.pending
+            .push_back((root_id, PendingRecord::Class(root)));
+        Ok(())
+    }
+
+    // Writes the next queued object, returning `false` once there are none left. Referenced
+    // objects are written after their parent in the order they were first referenced, the same
+    // breadth-first order BinaryFormatter uses.
+    fn encode_pending(&mut self) -> Result<bool, io::Error> {
+        let Some((object_id, object)) = self.pending.pop_front() else {
+            return Ok(false);
+        };
+
+        match object {
+            PendingRecord::Class(class) => self.encode_class(object_id, class)?,
+            PendingRecord::PrimitiveArray(array) => {
+                self.encode_primitive_array(object_id, array)?
+            }
+            PendingRecord::StreamedArray(array) => self.encode_streamed_array(object_id, array)?,
+            PendingRecord::ObjectArray(array) => self.encode_object_array(object_id, array)?,
+            PendingRecord::ClassArray(array) => self.encode_class_array(object_id, array)?,
+        }
+
+        Ok(true)
+    }
+
+    fn finish(&self) -> Result<(), io::Error> {
+        match self.shared.keys().find(|id| !self.defined.contains(id)) {
+            Some(id) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("reference to object {id}, which is not in the stream"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn reference(&mut self, object: PendingRecord<'a>) -> Member<'a> {
+        let id = self.next_id();
+        self.pending.push_back((id, object));
+        Member::Reference(id)
+    }
+
+    fn shared_id(&mut self, id: i32) -> i32 {
+        if let Some(object_id) = self.shared.get(&id) {
+            return *object_id;
+        }
+
+        let object_id = self.next_id();
+        self.shared.insert(id, object_id);
+        object_id
+    }
+
+    fn shared(&mut self, id: i32, value: &'a Field) -> Result<Member<'a>, io::Error> {
+        if !self.defined.insert(id) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("object {id} is shared more than once"),
+            ));
+        }
+
+        let object_id = self.shared_id(id);
+
+        if let Field::Primitive(Primitive::String(value)) = value {
+            return Ok(Member::String(object_id, value));
+        }
+
+        let object = self.pending_record(value)?;
+        self.pending.push_back((object_id, object));
+        Ok(Member::Reference(object_id))
+    }
+
+    fn string(&mut self, value: &'a str) -> Member<'a> {
+        if let Some(id) = self.strings.get(value) {
+            return Member::Reference(*id);
+        }
+
+        let object_id = self.next_id();
+
+        if self.options.intern_strings {
+            self.strings.insert(value, object_id);
+        }
+
+        Member::String(object_id, value)
+    }
+
+    // Ids are handed out before anything is written, so that they follow the member order
+    // rather than the order records end up in.
+    fn member(&mut self, value: &'a Field) -> Result<Member<'a>, io::Error> {
+        Ok(match value {
+            Field::Primitive(Primitive::String(value)) => self.string(value),
+            Field::Primitive(Primitive::Null) | Field::Null => Member::Null,
+            Field::Primitive(value) => Member::Primitive(value),
+            Field::Shared(id, value) => self.shared(*id, value)?,
+            Field::Reference(id) => Member::Reference(self.shared_id(*id)),
+            value => {
+                let object = self.pending_record(value)?;
+                self.reference(object)
+            }
+        })
+    }
+
+    fn pending_record(&mut self, value: &'a Field) -> Result<PendingRecord<'a>, io::Error> {
+        Ok(match value {
+            Field::PrimitiveArray(value) => PendingRecord::PrimitiveArray(value),
+            Field::Class(value) => PendingRecord::Class(value),
+            Field::StreamedArray(value) => PendingRecord::StreamedArray(value),
+            Field::ObjectArray(value) => PendingRecord::ObjectArray(value),
+            Field::ClassArray(value) => PendingRecord::ClassArray(value),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "only classes, arrays and strings can be shared",
+                ))
+            }
+        })
+    }
+
+    // Class members name their library, so an interleaved library is written here, before the
+    // record whose member types refer to it.
+    fn member_type(
+        &mut self,
+        value: &'a Field,
+    ) -> Result<(BinaryType, Option<AdditionalInfo>), io::Error> {
+        Ok(match value {
+            Field::Primitive(Primitive::String(_)) => (BinaryType::String, None),
+            Field::Primitive(Primitive::Null) | Field::Null => (BinaryType::Object, None),
+            Field::Primitive(value) => (
+                BinaryType::Primitive_,
+                Some(AdditionalInfo::Primitive(value.get_type())),
+            ),
+            Field::PrimitiveArray(PrimitiveArray::String(_)) => (BinaryType::StringArray, None),
+            Field::PrimitiveArray(value) => (
+                BinaryType::PrimitiveArray,
+                Some(AdditionalInfo::PrimitiveArray(value.get_type())),
+            ),
+            Field::StreamedArray(value) => (
+                BinaryType::PrimitiveArray,
+                Some(AdditionalInfo::PrimitiveArray(value.get_type())),
+            ),
+            Field::Class(value) => self.class_type(&value.name, value.library_name.as_deref())?,
+            Field::ObjectArray(_) => (BinaryType::ObjectArray, None),
+            // Arrays of a class are typed as a class with `[]` after its name.
+            Field::ClassArray(value) => {
+                self.class_type(&format!("{}[]", value.name), value.library_name.as_deref())?
+            }
+            Field::Shared(_, value) => self.member_type(value)?,
+            // The object may not have been reached yet, so its type is not known.
+            Field::Reference(_) => (BinaryType::Object, None),
+        })
+    }
+
+    fn class_type(
+        &mut self,
+        name: &str,
+        library_name: Option<&'a str>,
+    ) -> Result<(BinaryType, Option<AdditionalInfo>), io::Error> {
+        Ok(match library_name {
+            Some(library_name) => (
+                BinaryType::Class,
+                Some(AdditionalInfo::Class(ClassTypeInfo {
+                    type_name: name.to_owned(),
+                    library_id: self.library_id(library_name)?,
+                })),
+            ),
+            None => (
+                BinaryType::SystemClass,
+                Some(AdditionalInfo::SystemClass(name.to_owned())),
+            ),
+        })
+    }
+
+    fn write_member(&mut self, member: Member, typed: bool) -> Result<(), io::Error> {
+        match member {
+            Member::Primitive(value) if typed => self.writer.unparse(value),
+            Member::Primitive(value) => {
+                self.writer.unparse(&RecordType::MemberTypedPrimitive)?;
+                self.writer.unparse(&value.get_type())?;
+                self.writer.unparse(value)
+            }
+            Member::String(object_id, value) => {
+                self.writer.unparse(&RecordType::BinaryObjectString)?;
+                self.writer.unparse(&object_id)?;
+                self.writer.unparse(value)
+            }
+            Member::Reference(id) => self.writer.unparse(&Record::MemberReference { id }),
+            Member::Null => self.writer.unparse(&Record::ObjectNull),
+        }
+    }
+
+    fn encode_class(&mut self, object_id: i32, class: &'a Class) -> Result<(), io::Error> {
+        let library_id = match &class.library_name {
+            Some(library_name) => Some(self.library_id(library_name)?),
+            None => None,
+        };
+        let typed = !self.options.omit_member_types;
+        let mut members = Vec::with_capacity(class.fields.len());
+        let mut member_types = Vec::with_capacity(class.fields.len());
+        let mut additional_info = vec![];
+
+        for value in class.fields.values() {
+            members.push(self.member(value)?);
+
+            // Untyped records leave the member types out, and with them the libraries of class
+            // members, which are then written before the class that uses them instead.
+            if typed {
+                let (member_type, info) = self.member_type(value)?;
+                member_types.push(member_type);
+                additional_info.extend(info);
+            }
+        }
+
+        let key = ClassMetadataKey {
+            library_id,
+            name: &class.name,
+            member_names: class.fields.keys().map(String::as_str).collect(),
+            member_type_info: MemberTypeInfo {
+                member_types,
+                additional_info,
+            },
+        };
+
+        // Later instances with the same type and member layout only point back at the first
+        // record's metadata instead of repeating it.
+        if let Some(metadata_id) = self.metadata.get(&key) {
+            self.writer.unparse(&RecordType::ClassWithId)?;
+            self.writer.unparse(&object_id)?;
+            self.writer.unparse(metadata_id)?;
+        } else {
+            self.writer.unparse(&match (library_id, typed) {
+                (Some(_), true) => RecordType::ClassWithMembersAndTypes,
+                (None, true) => RecordType::SystemClassWithMembersAndTypes,
+                (Some(_), false) => RecordType::ClassWithMembers,
+                (None, false) => RecordType::SystemClassWithMembers,
+            })?;
+            self.writer.unparse(&object_id)?;
+            self.writer.unparse(key.name)?;
+            self.writer.unparse(&(key.member_names.len() as i32))?;
+            self.writer.unparse(&key.member_names)?;
+
+            if typed {
+                self.writer.unparse(&key.member_type_info)?;
+            }
+
+            self.writer.unparse(&library_id)?;
+            self.metadata.insert(key, object_id);
+        }
+
+        for member in members {
+            self.write_member(member, typed)?;
+        }
+
+        Ok(())
+    }
+
+    fn encode_primitive_array(
+        &mut self,
+        object_id: i32,
+        array: &'a PrimitiveArray,
+    ) -> Result<(), io::Error> {
+        if let PrimitiveArray::String(values) = array {
+            let members: Vec<Option<Member>> = values
+                .iter()
+                .map(|value| value.as_deref().map(|value| self.string(value)))
+                .collect();
+
+            self.writer.unparse(&RecordType::ArraySingleString)?;
+            self.writer.unparse(&object_id)?;
+            self.writer.unparse(&(values.len() as i32))?;
+
+            return self.write_elements(members);
+        }
+
+        self.writer.unparse(&RecordType::ArraySinglePrimitive)?;
+        self.writer.unparse(&object_id)?;
+        self.writer.unparse(&(array.len() as i32))?;
+        self.writer.unparse(&array.get_type())?;
+        self.writer.unparse(array)
+    }
+
+    fn encode_streamed_array(
+        &mut self,
+        object_id: i32,
+        array: &'a StreamedArray,
+    ) -> Result<(), io::Error> {
+        self.writer.unparse(&RecordType::ArraySinglePrimitive)?;
+        self.writer.unparse(&object_id)?;
+        self.writer.unparse(&(array.len() as i32))?;
+        self.writer.unparse(&array.get_type())?;
+        self.writer.unparse(array)
+    }
+
+    fn encode_object_array(&mut self, object_id: i32, array: &'a [Field]) -> Result<(), io::Error> {
+        let members = array
+            .iter()
+            .map(|element| match element {
+                Field::Null | Field::Primitive(Primitive::Null) => Ok(None),
+                element => self.member(element).map(Some),
+            })
+            .collect::<Result<Vec<_>, io::Error>>()?;
+
+        self.writer.unparse(&RecordType::ArraySingleObject)?;
+        self.writer.unparse(&object_id)?;
+        self.writer.unparse(&(array.len() as i32))?;
+
+        self.write_elements(members)
+    }
+
+    fn encode_class_array(
+        &mut self,
+        object_id: i32,
+        array: &'a ClassArray,
+    ) -> Result<(), io::Error> {
+        let (binary_type, additional_info) =
+            self.class_type(&array.name, array.library_name.as_deref())?;
+        let members = array
+            .elements
+            .iter()
+            .map(|element| match element {
+                Field::Null | Field::Primitive(Primitive::Null) => Ok(None),
+                element => self.member(element).map(Some),
+            })
+            .collect::<Result<Vec<_>, io::Error>>()?;
+
+        self.writer.unparse(&RecordType::BinaryArray)?;
+        self.writer.unparse(&object_id)?;
+        self.writer.unparse(&BinaryArrayType::Single)?;
+        self.writer.unparse(&1i32)?;
+        self.writer.unparse(&(array.elements.len() as i32))?;
+        self.writer.unparse(&binary_type)?;
+        self.writer.unparse(&additional_info)?;
+
+        self.write_elements(members)
+    }
+
+    // Runs of nulls are written as a single null record.
+    fn write_elements(&mut self, members: Vec<Option<Member>>) -> Result<(), io::Error> {
+        let mut null_count = 0;
+
+        for member in members {
+            match member {
+                None => null_count += 1,
+                Some(member) => {
+                    self.write_nulls(null_count)?;
+                    null_count = 0;
+                    self.write_member(member, false)?;
                 }
             }
         }
 
-        records.insert(
-            0,
-            Record::ClassWithMembersAndTypes(ClassWithMembersAndTypes {
-                class_info: ClassInfo {
-                    object_id,
-                    name: class.name,
-                    member_count: member_names.len() as i32,
-                    member_names,
-                },
-                member_type_info: MemberTypeInfo {
-                    member_types,
-                    additional_info,
-                },
-                library_id: self.libraries[&class.library_name],
-                member_references,
+        self.write_nulls(null_count)
+    }
+
+    fn write_nulls(&mut self, null_count: i32) -> Result<(), io::Error> {
+        match null_count {
+            0 => Ok(()),
+            1 => self.writer.unparse(&Record::ObjectNull),
+            2..=255 => self.writer.unparse(&Record::ObjectNullMultiple256 {
+                null_count: null_count as u8,
             }),
-        );
-        self.counter += 1;
+            _ => self
+                .writer
+                .unparse(&Record::ObjectNullMultiple { null_count }),
+        }
+    }
+}
+
+pub(crate) struct ClassMetadata {
+    pub library_name: Option<String>,
+    pub name: String,
+    pub member_names: Vec<String>,
+}
+
+#[derive(Clone)]
+struct PendingObject {
+    field: Field,
+    // Positions in the class's fields or the array's elements that still hold a placeholder
+    // for another object, with that object's id.
+    references: Vec<(usize, i32)>,
+    string_array: bool,
+}
+
+impl PendingObject {
+    fn new(field: Field) -> Self {
+        Self {
+            field,
+            references: vec![],
+            string_array: false,
+        }
+    }
 
-        records
+    fn into_field(self) -> Field {
+        match self.field {
+            Field::ObjectArray(elements) if self.string_array => {
+                Field::PrimitiveArray(PrimitiveArray::String(
+                    elements
+                        .into_iter()
+                        .map(|element| match element {
+                            Field::Primitive(Primitive::String(value)) => Some(value),
+                            _ => None,
+                        })
+                        .collect(),
+                ))
+            }
+            field => field,
+        }
     }
 }
 
-struct StreamDecoderState {
-    objects: BTreeMap<i32, Record>,
-    libraries: BTreeMap<i32, String>,
+// Builds the object model as records arrive. Each record is converted as soon as it is read
+// and then dropped; references between objects are only filled in once the whole stream is
+// known, moving each object into the last place that refers to it.
+#[derive(Default)]
+pub(crate) struct StreamDecoder {
+    root_id: Option<i32>,
+    libraries: HashMap<i32, String>,
+    metadata: HashMap<i32, Rc<ClassMetadata>>,
+    objects: HashMap<i32, PendingObject>,
+    reference_counts: HashMap<i32, usize>,
+    resolving: HashSet<i32>,
+    keep_references: bool,
+    // The ids given to shared objects, by object id.
+    labels: HashMap<i32, i32>,
+    shared_count: i32,
 }
 
-impl StreamDecoderState {
-    fn decode_class(&self, class: &ClassWithMembersAndTypes) -> Class {
-        let field_count = class.class_info.member_count as usize;
-        let mut field_names = vec![];
-        let mut field_types = vec![];
-        let mut field_values: Vec<Field> = vec![];
+impl StreamDecoder {
+    pub(crate) fn push(&mut self, record: Record) -> Result<(), ParseError> {
+        match record {
+            Record::SerializationHeader(header) => self.root_id = Some(header.root_id),
+            Record::BinaryLibrary(library) => self.define_library(library),
+            Record::MessageEnd => (),
+            other => {
+                self.push_object(other)?;
+            }
+        }
 
-        let mut ai = 0usize;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Stream, ParseError> {
+        let root_id = self.root_id()?;
+        let root = if self.keep_references {
+            self.resolve_shared(root_id)?
+        } else {
+            self.resolve(root_id, false)?
+        };
 
-        for i in 0..field_count {
-            let field_name = &class.class_info.member_names[i];
-            let field_type = class.member_type_info.member_types[i];
+        match root {
+            Field::Class(root) => Ok(Stream { root }),
+            Field::Shared(_, root) => match *root {
+                Field::Class(root) => Ok(Stream { root }),
+                _ => Err(ParseError::MissingObject(root_id)),
+            },
+            _ => Err(ParseError::MissingObject(root_id)),
+        }
+    }
 
-            match field_type {
-                BinaryType::Primitive_ => {
-                    if let Record::MemberPrimitiveUnTyped(primitive) = &class.member_references[ai]
+    // Records the libraries and class layouts a record defines without decoding any objects, so
+    // that objects can later be decoded on their own.
+    pub(crate) fn define(&mut self, record: &Record) -> Result<(), ParseError> {
+        let (class_info, library_id) = match record {
+            Record::SerializationHeader(header) => {
+                self.root_id = Some(header.root_id);
+                return Ok(());
+            }
+            Record::BinaryLibrary(library) => {
+                self.define_library(library.clone());
+                return Ok(());
+            }
+            Record::ClassWithMembersAndTypes(class) => (&class.class_info, Some(class.library_id)),
+            Record::SystemClassWithMembersAndTypes(class) => (&class.class_info, None),
+            Record::ClassWithMembers(class) => (&class.class_info, Some(class.library_id)),
+            Record::SystemClassWithMembers(class) => (&class.class_info, None),
+            _ => return Ok(()),
+        };
+
+        self.define_class(class_info.clone(), library_id)?;
+        Ok(())
+    }
+
+    // A decoder with the same definitions but no objects.
+    pub(crate) fn definitions(&self) -> Self {
+        Self {
+            root_id: self.root_id,
+            libraries: self.libraries.clone(),
+            metadata: self.metadata.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn root_id(&self) -> Result<i32, ParseError> {
+        self.root_id.ok_or(ParseError::MissingHeader)
+    }
+
+    pub(crate) fn metadata(&self, metadata_id: i32) -> Result<Rc<ClassMetadata>, ParseError> {
+        self.metadata
+            .get(&metadata_id)
+            .cloned()
+            .ok_or(ParseError::MissingMetadata(metadata_id))
+    }
+
+    fn push_object(&mut self, record: Record) -> Result<i32, ParseError> {
+        let (object_id, metadata, members) = match record {
+            Record::ClassWithMembersAndTypes(class) => (
+                class.class_info.object_id,
+                self.define_class(class.class_info, Some(class.library_id))?,
+                class.member_references,
+            ),
+            Record::SystemClassWithMembersAndTypes(class) => (
+                class.class_info.object_id,
+                self.define_class(class.class_info, None)?,
+                class.member_references,
+            ),
+            Record::ClassWithMembers(class) => (
+                class.class_info.object_id,
+                self.define_class(class.class_info, Some(class.library_id))?,
+                class.member_references,
+            ),
+            Record::SystemClassWithMembers(class) => (
+                class.class_info.object_id,
+                self.define_class(class.class_info, None)?,
+                class.member_references,
+            ),
+            Record::ClassWithId(class) => (
+                class.object_id,
+                self.metadata(class.metadata_id)?,
+                class.member_references,
+            ),
+            Record::BinaryObjectString(string) => {
+                self.objects.insert(
+                    string.object_id,
+                    PendingObject::new(Field::Primitive(Primitive::String(string.value))),
+                );
+                return Ok(string.object_id);
+            }
+            Record::ArraySinglePrimitive(array) => {
+                self.objects.insert(
+                    array.array_info.object_id,
+                    PendingObject::new(Field::PrimitiveArray(array.members)),
+                );
+                return Ok(array.array_info.object_id);
+            }
+            Record::ArraySingleString(array) => {
+                let object = self.decode_array(array.members, true)?;
+                self.objects.insert(array.array_info.object_id, object);
+                return Ok(array.array_info.object_id);
+            }
+            Record::ArraySingleObject(array) => {
+                let object = self.decode_array(array.members, false)?;
+                self.objects.insert(array.array_info.object_id, object);
+                return Ok(array.array_info.object_id);
+            }
+            Record::BinaryArray(array) => {
+                // Multi-dimensional arrays are flattened in row-major order.
+                let object = match array.additional_info {
+                    // Only single-dimensional class arrays keep their element type.
+                    Some(AdditionalInfo::Class(_) | AdditionalInfo::SystemClass(_))
+                        if array.binary_array_type == BinaryArrayType::Single =>
                     {
-                        field_values.push(Field::Primitive(primitive.clone()));
-                    }
-                    ai += 1;
-                }
-                BinaryType::PrimitiveArray => {
-                    if let Record::MemberReference { id } = &class.member_references[ai] {
-                        if let Record::ArraySinglePrimitive(array) = &self.objects[id] {
-                            field_values.push(Field::PrimitiveArray(
-                                PrimitiveArray::into_field(
-                                    array.members.clone(),
-                                    array.primitive_type,
+                        let (name, library_name) = match array.additional_info {
+                            Some(AdditionalInfo::Class(info)) => (
+                                info.type_name,
+                                Some(
+                                    self.libraries
+                                        .get(&info.library_id)
+                                        .ok_or(ParseError::MissingLibrary(info.library_id))?
+                                        .clone(),
                                 ),
-                            ))
+                            ),
+                            Some(AdditionalInfo::SystemClass(name)) => (name, None),
+                            _ => unreachable!(),
+                        };
+                        let mut object = self.decode_array(array.members, false)?;
+
+                        if let Field::ObjectArray(elements) = object.field {
+                            object.field = Field::ClassArray(ClassArray {
+                                library_name,
+                                name,
+                                elements,
+                            });
                         }
-                    };
-                    ai += 1;
+
+                        object
+                    }
+                    Some(AdditionalInfo::Primitive(primitive_type)) => {
+                        PendingObject::new(Field::PrimitiveArray(PrimitiveArray::into_field(
+                            array
+                                .members
+                                .into_iter()
+                                .map(|member| match member {
+                                    Record::MemberPrimitiveUnTyped(value) => value,
+                                    _ => Primitive::Null,
+                                })
+                                .collect(),
+                            primitive_type,
+                        )))
+                    }
+                    _ => {
+                        self.decode_array(array.members, array.binary_type == BinaryType::String)?
+                    }
+                };
+
+                self.objects.insert(array.object_id, object);
+                return Ok(array.object_id);
+            }
+            other => return Err(ParseError::UnexpectedRecord(other.record_type())),
+        };
+        let object = self.decode_class(&metadata, members)?;
+
+        self.objects.insert(object_id, object);
+        Ok(object_id)
+    }
+
+    fn define_library(&mut self, library: BinaryLibrary) {
+        self.libraries
+            .insert(library.library_id, library.library_name);
+    }
+
+    pub(crate) fn define_class(
+        &mut self,
+        class_info: ClassInfo,
+        library_id: Option<i32>,
+    ) -> Result<Rc<ClassMetadata>, ParseError> {
+        let library_name = match library_id {
+            Some(library_id) => Some(
+                self.libraries
+                    .get(&library_id)
+                    .ok_or(ParseError::MissingLibrary(library_id))?
+                    .clone(),
+            ),
+            None => None,
+        };
+        // Members are placed in the class by name, so each name can be used only once.
+        let mut names = HashSet::new();
+
+        if let Some(name) = class_info
+            .member_names
+            .iter()
+            .find(|name| !names.insert(name.as_str()))
+        {
+            return Err(ParseError::DuplicateMember(name.clone()));
+        }
+
+        let metadata = Rc::new(ClassMetadata {
+            library_name,
+            name: class_info.name,
+            member_names: class_info.member_names,
+        });
+
+        self.metadata.insert(class_info.object_id, metadata.clone());
+        Ok(metadata)
+    }
+
+    fn decode_class(
+        &mut self,
+        metadata: &ClassMetadata,
+        members: Vec<Record>,
+    ) -> Result<PendingObject, ParseError> {
+        let mut fields = IndexMap::with_capacity(members.len());
+        let mut references = vec![];
+
+        let mut names = metadata.member_names.iter();
+
+        for member in members {
+            if let Record::BinaryLibrary(library) = member {
+                self.define_library(library);
+                continue;
+            }
+
+            let Some(name) = names.next() else {
+                break;
+            };
+            let field = self.decode_member(member, fields.len(), &mut references)?;
+
+            fields.insert(name.clone(), field);
+        }
+
+        Ok(PendingObject {
+            field: Field::Class(Class {
+                library_name: metadata.library_name.clone(),
+                name: metadata.name.clone(),
+                fields,
+            }),
+            references,
+            string_array: false,
+        })
+    }
+
+    fn decode_array(
+        &mut self,
+        members: Vec<Record>,
+        string_array: bool,
+    ) -> Result<PendingObject, ParseError> {
+        let mut elements = vec![];
+        let mut references = vec![];
+
+        for member in members {
+            match member {
+                Record::ObjectNullMultiple256 { null_count } => {
+                    elements.extend((0..null_count).map(|_| Field::Null))
                 }
-                BinaryType::Class => {
-                    if let Record::MemberReference { id } = &class.member_references[ai] {
-                        if let Record::ClassWithMembersAndTypes(class) = &self.objects[id] {
-                            field_values.push(Field::Class(self.decode_class(class)))
-                        }
-                    };
-                    ai += 1;
+                Record::ObjectNullMultiple { null_count } => {
+                    elements.extend((0..null_count).map(|_| Field::Null))
+                }
+                Record::BinaryLibrary(library) => self.define_library(library),
+                other => {
+                    let element = self.decode_member(other, elements.len(), &mut references)?;
+                    elements.push(element);
+                }
+            }
+        }
+
+        Ok(PendingObject {
+            field: Field::ObjectArray(elements),
+            references,
+            string_array,
+        })
+    }
+
+    // Objects written inline are stored like any other object and referred to by id.
+    fn decode_member(
+        &mut self,
+        member: Record,
+        index: usize,
+        references: &mut Vec<(usize, i32)>,
+    ) -> Result<Field, ParseError> {
+        let id = match member {
+            Record::MemberPrimitiveUnTyped(value) | Record::MemberTypedPrimitive { value } => {
+                return Ok(Field::Primitive(value))
+            }
+            Record::ObjectNull => return Ok(Field::Null),
+            Record::MemberReference { id } => id,
+            other => self.push_object(other)?,
+        };
+
+        *self.reference_counts.entry(id).or_default() += 1;
+        references.push((index, id));
+        Ok(Field::Null)
+    }
+
+    // Every object but the root is placed wherever it is referenced. An object referenced from
+    // several places is copied into all but the last of them, which takes the original. Only
+    // the original's references lower the counts of the objects they lead to; a copy's children
+    // are copied without counting, since the original resolves the same references later.
+    pub(crate) fn resolve(&mut self, id: i32, copy: bool) -> Result<Field, ParseError> {
+        // The tree model cannot represent cycles, so a reference back to an object that is
+        // still being resolved is left empty.
+        if !self.resolving.insert(id) {
+            return Ok(Field::Null);
+        }
+
+        let remaining = self.reference_counts.get(&id).copied().unwrap_or(0);
+        let object = if copy {
+            self.objects.get(&id).cloned()
+        } else if remaining > 1 {
+            self.reference_counts.insert(id, remaining - 1);
+            self.objects.get(&id).cloned()
+        } else {
+            self.objects.remove(&id)
+        };
+        let copy = copy || remaining > 1;
+        let mut object = object.ok_or(ParseError::MissingObject(id))?;
+
+        for (index, target) in mem::take(&mut object.references) {
+            let value = self.resolve(target, copy)?;
+
+            match &mut object.field {
+                Field::Class(class) => class.fields[index] = value,
+                Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }) => {
+                    elements[index] = value
                 }
-                other => todo!("{:?}", other),
+                _ => unreachable!(),
             }
+        }
+
+        self.resolving.remove(&id);
+
+        Ok(object.into_field())
+    }
+
+    // Like `resolve`, but each object is placed only once. Objects with more than one reference
+    // are given an id as they are reached, and objects found again while they are still being
+    // resolved are given one then; every other place they appear in becomes a reference.
+    fn resolve_shared(&mut self, id: i32) -> Result<Field, ParseError> {
+        if let Some(label) = self.labels.get(&id) {
+            return Ok(Field::Reference(*label));
+        }
+
+        if self.resolving.contains(&id) {
+            return Ok(Field::Reference(self.label(id)));
+        }
 
-            field_names.push(field_name);
-            field_types.push(field_type);
+        let object = self.objects.get(&id).ok_or(ParseError::MissingObject(id))?;
+
+        // Strings are values in the object model, so they are simply copied.
+        if let Field::Primitive(_) = object.field {
+            return Ok(object.field.clone());
         }
 
-        let mut fields = IndexMap::new();
+        let mut object = self.objects.remove(&id).unwrap();
 
-        for i in 0..field_count {
-            fields.insert(
-                field_names[i].clone(),
-                field_values[i].clone(),
-            );
+        if self.reference_counts.get(&id).copied().unwrap_or(0) > 1 {
+            self.label(id);
         }
 
-        Class {
-            library_name: self.libraries.get(&class.library_id).unwrap().clone(),
-            name: class.class_info.name.clone(),
-            fields,
+        self.resolving.insert(id);
+
+        for (index, target) in mem::take(&mut object.references) {
+            let value = self.resolve_shared(target)?;
+
+            match &mut object.field {
+                Field::Class(class) => class.fields[index] = value,
+                Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }) => {
+                    elements[index] = value
+                }
+                _ => unreachable!(),
+            }
         }
+
+        self.resolving.remove(&id);
+
+        Ok(match self.labels.get(&id) {
+            Some(label) => Field::Shared(*label, Box::new(object.into_field())),
+            None => object.into_field(),
+        })
+    }
+
+    fn label(&mut self, id: i32) -> i32 {
+        // The root keeps its fixed id, which is never handed out to another object.
+        let label = if self.root_id == Some(id) {
+            Stream::ROOT_ID
+        } else {
+            self.shared_count += 1;
+            Stream::ROOT_ID + self.shared_count
+        };
+
+        self.labels.insert(id, label);
+        label
     }
 }
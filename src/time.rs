@@ -0,0 +1,187 @@
+use crate::{
+    io::{self, Read, Write},
+    parse::{Parse, ParseError, ParseFrom},
+    unparse::{Unparse, UnparseTo},
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use core::fmt;
+use thiserror::Error;
+
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+/// A .NET `DateTime`: 100 ns ticks since 0001-01-01T00:00:00, and what those ticks are relative
+/// to.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct DateTime {
+    pub ticks: i64,
+    pub kind: DateTimeKind,
+}
+
+/// The error for a `chrono` time or duration that doesn't fit in a [`DateTime`] or [`TimeSpan`].
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+#[error("time out of range")]
+pub struct OutOfRangeError;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum DateTimeKind {
+    #[default]
+    Unspecified,
+    Utc,
+    Local,
+    /// A local time in the hour that repeats when daylight saving time ends, on the side of it
+    /// after the clocks went back.
+    LocalAmbiguousDst,
+}
+
+impl DateTime {
+    // The kind is kept in the top two bits.
+    const TICKS_MASK: u64 = 0x3FFF_FFFF_FFFF_FFFF;
+    /// The most ticks that the 62 bits left for them can hold.
+    pub(crate) const MAX_TICKS: i64 = Self::TICKS_MASK as i64;
+
+    pub fn new(ticks: i64, kind: DateTimeKind) -> Self {
+        Self { ticks, kind }
+    }
+
+    /// The time as a `NaiveDateTime`, or `None` if the ticks are out of range.
+    pub fn to_naive(&self) -> Option<NaiveDateTime> {
+        epoch().checked_add_signed(TimeSpan::new(self.ticks).to_duration())
+    }
+}
+
+impl TryFrom<NaiveDateTime> for DateTime {
+    type Error = OutOfRangeError;
+
+    /// Converts to a `DateTime` of unspecified kind, dropping precision finer than a tick. Times
+    /// before 0001-01-01, or past the 62 bits of ticks a stream holds (in the year 14615), are
+    /// out of range.
+    fn try_from(value: NaiveDateTime) -> Result<Self, Self::Error> {
+        let ticks = TimeSpan::try_from(value - epoch())?.ticks;
+
+        if !(0..=Self::MAX_TICKS).contains(&ticks) {
+            return Err(OutOfRangeError);
+        }
+
+        Ok(Self::new(ticks, DateTimeKind::Unspecified))
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_naive() {
+            Some(value) => write!(f, "{}", value.format("%Y-%m-%dT%H:%M:%S%.f"))?,
+            None => write!(f, "{} ticks", self.ticks)?,
+        }
+
+        match self.kind {
+            DateTimeKind::Utc => f.write_str("Z"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<R: Read> ParseFrom<R> for DateTime {
+    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+        let value: u64 = reader.parse()?;
+        let kind = match value >> 62 {
+            0 => DateTimeKind::Unspecified,
+            1 => DateTimeKind::Utc,
+            2 => DateTimeKind::Local,
+            _ => DateTimeKind::LocalAmbiguousDst,
+        };
+
+        Ok(Self::new((value & Self::TICKS_MASK) as i64, kind))
+    }
+}
+
+impl<W: Write> UnparseTo<W> for DateTime {
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&((self.ticks as u64 & Self::TICKS_MASK) | (self.kind as u64) << 62))
+    }
+}
+
+/// A .NET `TimeSpan`: a signed count of 100 ns ticks.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+pub struct TimeSpan {
+    pub ticks: i64,
+}
+
+impl TimeSpan {
+    pub fn new(ticks: i64) -> Self {
+        Self { ticks }
+    }
+
+    pub fn to_duration(&self) -> Duration {
+        Duration::seconds(self.ticks / TICKS_PER_SECOND)
+            + Duration::nanoseconds(self.ticks % TICKS_PER_SECOND * 100)
+    }
+}
+
+impl TryFrom<Duration> for TimeSpan {
+    type Error = OutOfRangeError;
+
+    /// Drops precision finer than a tick. Durations of more than about 29,000 years are out of
+    /// range.
+    fn try_from(value: Duration) -> Result<Self, Self::Error> {
+        let seconds = value.num_seconds();
+        let nanoseconds = (value - Duration::seconds(seconds))
+            .num_nanoseconds()
+            .unwrap_or(0);
+
+        seconds
+            .checked_mul(TICKS_PER_SECOND)
+            .and_then(|ticks| ticks.checked_add(nanoseconds / 100))
+            .map(Self::new)
+            .ok_or(OutOfRangeError)
+    }
+}
+
+impl fmt::Display for TimeSpan {
+    /// Formats like .NET's `TimeSpan.ToString()`: `[-][d.]hh:mm:ss[.fffffff]`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ticks = self.ticks.unsigned_abs();
+        let seconds = ticks / TICKS_PER_SECOND as u64;
+        let fraction = ticks % TICKS_PER_SECOND as u64;
+        let days = seconds / 86_400;
+
+        if self.ticks < 0 {
+            f.write_str("-")?;
+        }
+
+        if days > 0 {
+            write!(f, "{days}.")?;
+        }
+
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            seconds / 3_600 % 24,
+            seconds / 60 % 60,
+            seconds % 60
+        )?;
+
+        if fraction > 0 {
+            write!(f, ".{fraction:07}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> ParseFrom<R> for TimeSpan {
+    fn parse_from(reader: &mut R) -> Result<Self, ParseError> {
+        Ok(Self::new(reader.parse()?))
+    }
+}
+
+impl<W: Write> UnparseTo<W> for TimeSpan {
+    fn unparse_to(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.unparse(&self.ticks)
+    }
+}
+
+fn epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(1, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap()
+}
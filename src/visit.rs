@@ -0,0 +1,314 @@
+use crate::{
+    enums::Primitive,
+    path,
+    stream::{Class, ClassArray, Field, Stream},
+};
+use std::collections::HashSet;
+
+/// Where a value is in the object graph, given to every [`Visitor`] and [`VisitorMut`] callback.
+#[derive(Debug, Clone, Copy)]
+pub struct VisitContext<'a> {
+    /// The path of the value in the form [`Stream::get`] takes, like `World.Entities[42]`. Empty
+    /// for the root.
+    pub path: &'a str,
+    /// How many classes and arrays the value is inside of. The root is at depth 0.
+    pub depth: usize,
+    /// The object id when the value is a [`Field::Shared`] object, or [`Stream::ROOT_ID`] for the
+    /// root.
+    pub id: Option<i32>,
+}
+
+/// Callbacks for walking a stream with [`Stream::walk`]. Every callback does nothing by default.
+///
+/// Values are visited depth-first in stream order. A class member is given to
+/// [`field`](Visitor::field) and an array element to [`element`](Visitor::element) before the
+/// value itself is walked.
+#[allow(unused_variables)]
+pub trait Visitor {
+    /// Called before a class's members are walked. Returning `false` skips them.
+    fn enter_class(&mut self, class: &Class, context: &VisitContext) -> bool {
+        true
+    }
+
+    /// Called after a class's members are walked, or skipped.
+    fn exit_class(&mut self, class: &Class, context: &VisitContext) {}
+
+    fn field(&mut self, name: &str, value: &Field, context: &VisitContext) {}
+
+    /// Called before an array's elements are walked. Returning `false` skips them. Streamed
+    /// arrays are read into a [`Field::PrimitiveArray`] first.
+    fn enter_array(&mut self, array: &Field, context: &VisitContext) -> bool {
+        true
+    }
+
+    /// Called after an array's elements are walked, or skipped.
+    fn exit_array(&mut self, array: &Field, context: &VisitContext) {}
+
+    fn element(&mut self, index: usize, value: &Field, context: &VisitContext) {}
+
+    fn primitive(&mut self, value: &Primitive, context: &VisitContext) {}
+
+    /// Called for [`Field::Reference`], and for a [`Field::Shared`] object with an id that has
+    /// already been walked.
+    fn reference(&mut self, id: i32, context: &VisitContext) {}
+
+    /// Called for [`Field::Null`] and [`Primitive::Null`].
+    fn null(&mut self, context: &VisitContext) {}
+}
+
+/// Callbacks for walking and changing a stream with [`Stream::walk_mut`]. Every callback does
+/// nothing by default.
+///
+/// Values are walked after [`field`](VisitorMut::field) and [`element`](VisitorMut::element)
+/// return, so a value replaced there is walked in its new form. Elements of primitive arrays are
+/// given as a [`Field::Primitive`] and converted back to the array's element type the way
+/// [`Stream::set`] converts values, and are left unchanged if they can't be.
+#[allow(unused_variables)]
+pub trait VisitorMut {
+    /// Called before a class's members are walked. Returning `false` skips them.
+    fn enter_class(&mut self, class: &mut Class, context: &VisitContext) -> bool {
+        true
+    }
+
+    /// Called after a class's members are walked, or skipped.
+    fn exit_class(&mut self, class: &mut Class, context: &VisitContext) {}
+
+    fn field(&mut self, name: &str, value: &mut Field, context: &VisitContext) {}
+
+    /// Called before an array's elements are walked. Returning `false` skips them. Streamed
+    /// arrays are replaced with a [`Field::PrimitiveArray`] first.
+    fn enter_array(&mut self, array: &mut Field, context: &VisitContext) -> bool {
+        true
+    }
+
+    /// Called after an array's elements are walked, or skipped.
+    fn exit_array(&mut self, array: &mut Field, context: &VisitContext) {}
+
+    fn element(&mut self, index: usize, value: &mut Field, context: &VisitContext) {}
+
+    fn primitive(&mut self, value: &mut Primitive, context: &VisitContext) {}
+
+    /// Called for [`Field::Reference`], and for a [`Field::Shared`] object with an id that has
+    /// already been walked.
+    fn reference(&mut self, id: i32, context: &VisitContext) {}
+
+    /// Called for [`Field::Null`] and [`Primitive::Null`].
+    fn null(&mut self, context: &VisitContext) {}
+}
+
+impl Stream {
+    /// Walks every value reachable from the root, starting with the root class.
+    ///
+    /// References are reported rather than followed, so cyclic graphs are walked once. Objects
+    /// that appear more than once are only shared when decoded with
+    /// [`DecodeOptions::keep_references`](crate::DecodeOptions); otherwise each copy is walked.
+    pub fn walk(&self, visitor: &mut impl Visitor) {
+        let mut walker = Walker::new(visitor);
+
+        walker.class(&self.root, &VisitContext::ROOT);
+    }
+
+    /// Walks every value reachable from the root like [`walk`](Stream::walk), letting the
+    /// visitor change them.
+    pub fn walk_mut(&mut self, visitor: &mut impl VisitorMut) {
+        let mut walker = Walker::new(visitor);
+
+        walker.class_mut(&mut self.root, &VisitContext::ROOT);
+    }
+}
+
+struct Walker<'v, V: ?Sized> {
+    visitor: &'v mut V,
+    walked: HashSet<i32>,
+}
+
+impl<'v, V: ?Sized> Walker<'v, V> {
+    fn new(visitor: &'v mut V) -> Self {
+        Self {
+            visitor,
+            walked: HashSet::from([Stream::ROOT_ID]),
+        }
+    }
+}
+
+impl<V: Visitor + ?Sized> Walker<'_, V> {
+    fn field(&mut self, field: &Field, context: &VisitContext) {
+        match field {
+            Field::Shared(id, value) => {
+                if self.walked.insert(*id) {
+                    self.field(value, &context.with_id(*id));
+                } else {
+                    self.visitor.reference(*id, context);
+                }
+            }
+            Field::Class(class) => self.class(class, context),
+            Field::ObjectArray(elements) | Field::ClassArray(ClassArray { elements, .. }) => {
+                if self.visitor.enter_array(field, context) {
+                    for (index, value) in elements.iter().enumerate() {
+                        let path = element_path(context.path, index);
+                        let context = context.child(&path);
+
+                        self.visitor.element(index, value, &context);
+                        self.field(value, &context);
+                    }
+                }
+
+                self.visitor.exit_array(field, context);
+            }
+            Field::PrimitiveArray(array) => {
+                if self.visitor.enter_array(field, context) {
+                    for index in 0..array.len() {
+                        let path = element_path(context.path, index);
+                        let context = context.child(&path);
+                        let value = Field::Primitive(array.get(index).unwrap_or(Primitive::Null));
+
+                        self.visitor.element(index, &value, &context);
+                        self.field(&value, &context);
+                    }
+                }
+
+                self.visitor.exit_array(field, context);
+            }
+            Field::StreamedArray(array) => {
+                self.field(&Field::PrimitiveArray(array.to_primitive_array()), context)
+            }
+            Field::Primitive(Primitive::Null) | Field::Null => self.visitor.null(context),
+            Field::Primitive(value) => self.visitor.primitive(value, context),
+            Field::Reference(id) => self.visitor.reference(*id, context),
+        }
+    }
+
+    fn class(&mut self, class: &Class, context: &VisitContext) {
+        if self.visitor.enter_class(class, context) {
+            for (name, value) in &class.fields {
+                let path = member_path(context.path, name);
+                let context = context.child(&path);
+
+                self.visitor.field(name, value, &context);
+                self.field(value, &context);
+            }
+        }
+
+        self.visitor.exit_class(class, context);
+    }
+}
+
+impl<V: VisitorMut + ?Sized> Walker<'_, V> {
+    fn field_mut(&mut self, field: &mut Field, context: &VisitContext) {
+        if let Field::StreamedArray(array) = field {
+            *field = Field::PrimitiveArray(array.to_primitive_array());
+        }
+
+        match field {
+            Field::Shared(id, value) => {
+                if self.walked.insert(*id) {
+                    self.field_mut(value, &context.with_id(*id));
+                } else {
+                    self.visitor.reference(*id, context);
+                }
+            }
+            Field::Class(class) => self.class_mut(class, context),
+            Field::ObjectArray(_) | Field::ClassArray(_) | Field::PrimitiveArray(_) => {
+                if self.visitor.enter_array(field, context) {
+                    match field {
+                        Field::ObjectArray(elements)
+                        | Field::ClassArray(ClassArray { elements, .. }) => {
+                            for (index, value) in elements.iter_mut().enumerate() {
+                                let path = element_path(context.path, index);
+                                let context = context.child(&path);
+
+                                self.visitor.element(index, value, &context);
+                                self.field_mut(value, &context);
+                            }
+                        }
+                        Field::PrimitiveArray(array) => {
+                            let primitive_type = array.get_type();
+
+                            for index in 0..array.len() {
+                                let path = element_path(context.path, index);
+                                let context = context.child(&path);
+                                let mut value =
+                                    Field::Primitive(array.get(index).unwrap_or(Primitive::Null));
+
+                                self.visitor.element(index, &mut value, &context);
+                                self.field_mut(&mut value, &context);
+
+                                let value = match value {
+                                    Field::Primitive(Primitive::Null) | Field::Null => {
+                                        Primitive::Null
+                                    }
+                                    Field::Primitive(value) => {
+                                        match path::cast(value, primitive_type) {
+                                            Some(value) => value,
+                                            None => continue,
+                                        }
+                                    }
+                                    _ => continue,
+                                };
+
+                                array.replace(index, value);
+                            }
+                        }
+                        // `enter_array` replaced the array with something that isn't one.
+                        _ => {}
+                    }
+                }
+
+                self.visitor.exit_array(field, context);
+            }
+            Field::StreamedArray(_) => unreachable!("streamed arrays are read above"),
+            Field::Primitive(Primitive::Null) | Field::Null => self.visitor.null(context),
+            Field::Primitive(value) => self.visitor.primitive(value, context),
+            Field::Reference(id) => self.visitor.reference(*id, context),
+        }
+    }
+
+    fn class_mut(&mut self, class: &mut Class, context: &VisitContext) {
+        if self.visitor.enter_class(class, context) {
+            for (name, value) in &mut class.fields {
+                let path = member_path(context.path, name);
+                let context = context.child(&path);
+
+                self.visitor.field(name, value, &context);
+                self.field_mut(value, &context);
+            }
+        }
+
+        self.visitor.exit_class(class, context);
+    }
+}
+
+impl VisitContext<'_> {
+    const ROOT: VisitContext<'static> = VisitContext {
+        path: "",
+        depth: 0,
+        id: Some(Stream::ROOT_ID),
+    };
+
+    fn child<'a>(&self, path: &'a str) -> VisitContext<'a> {
+        VisitContext {
+            path,
+            depth: self.depth + 1,
+            id: None,
+        }
+    }
+
+    fn with_id(&self, id: i32) -> Self {
+        Self {
+            id: Some(id),
+            ..*self
+        }
+    }
+}
+
+fn member_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{parent}.{name}")
+    }
+}
+
+fn element_path(parent: &str, index: usize) -> String {
+    format!("{parent}[{index}]")
+}
@@ -0,0 +1,169 @@
+#[cfg(feature = "std")]
+use crate::stream::StreamedArray;
+use crate::{
+    enums::{Primitive, Record},
+    io::{self, Write},
+    records::{
+        ArraySingleObject, ArraySinglePrimitive, ArraySingleString, BinaryArray, ClassWithId,
+        ClassWithMembers, ClassWithMembersAndTypes, RecordType, SerializationHeader,
+        SystemClassWithMembers, SystemClassWithMembersAndTypes,
+    },
+    unparse::Unparse,
+};
+
+/// Writes a stream one record at a time, in exactly the order the records are given.
+///
+/// Records that hold member values write them straight after their header, so a class can be
+/// given with an empty `member_references` and its values written afterwards with the
+/// `write_member_*` methods.
+pub struct RecordWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> Result<(), io::Error> {
+        self.writer.unparse(record)
+    }
+
+    pub fn write_header(&mut self, root_id: i32) -> Result<(), io::Error> {
+        self.writer.unparse(&SerializationHeader {
+            root_id,
+            header_id: -1,
+            major_version: 1,
+            minor_version: 0,
+        })
+    }
+
+    pub fn write_library(&mut self, library_id: i32, library_name: &str) -> Result<(), io::Error> {
+        self.writer.unparse(&RecordType::BinaryLibrary)?;
+        self.writer.unparse(&library_id)?;
+        self.writer.unparse(library_name)
+    }
+
+    pub fn write_class_with_id(&mut self, class: &ClassWithId) -> Result<(), io::Error> {
+        self.writer.unparse(class)
+    }
+
+    pub fn write_class_with_members(&mut self, class: &ClassWithMembers) -> Result<(), io::Error> {
+        self.writer.unparse(class)
+    }
+
+    pub fn write_system_class_with_members(
+        &mut self,
+        class: &SystemClassWithMembers,
+    ) -> Result<(), io::Error> {
+        self.writer.unparse(class)
+    }
+
+    pub fn write_class_with_members_and_types(
+        &mut self,
+        class: &ClassWithMembersAndTypes,
+    ) -> Result<(), io::Error> {
+        self.writer.unparse(class)
+    }
+
+    pub fn write_system_class_with_members_and_types(
+        &mut self,
+        class: &SystemClassWithMembersAndTypes,
+    ) -> Result<(), io::Error> {
+        self.writer.unparse(class)
+    }
+
+    pub fn write_object_string(&mut self, object_id: i32, value: &str) -> Result<(), io::Error> {
+        self.writer.unparse(&RecordType::BinaryObjectString)?;
+        self.writer.unparse(&object_id)?;
+        self.writer.unparse(value)
+    }
+
+    pub fn write_binary_array(&mut self, array: &BinaryArray) -> Result<(), io::Error> {
+        self.writer.unparse(array)
+    }
+
+    pub fn write_array_single_primitive(
+        &mut self,
+        array: &ArraySinglePrimitive,
+    ) -> Result<(), io::Error> {
+        self.writer.unparse(array)
+    }
+
+    /// Writes an `ArraySinglePrimitive` record whose elements are produced by `array` as they
+    /// are written.
+    #[cfg(feature = "std")]
+    pub fn write_streamed_array(
+        &mut self,
+        object_id: i32,
+        array: &StreamedArray,
+    ) -> Result<(), io::Error> {
+        self.writer.unparse(&RecordType::ArraySinglePrimitive)?;
+        self.writer.unparse(&object_id)?;
+        self.writer.unparse(&(array.len() as i32))?;
+        self.writer.unparse(&array.get_type())?;
+        self.writer.unparse(array)
+    }
+
+    pub fn write_array_single_string(
+        &mut self,
+        array: &ArraySingleString,
+    ) -> Result<(), io::Error> {
+        self.writer.unparse(array)
+    }
+
+    pub fn write_array_single_object(
+        &mut self,
+        array: &ArraySingleObject,
+    ) -> Result<(), io::Error> {
+        self.writer.unparse(array)
+    }
+
+    /// Writes a primitive member value without a type code, as used by members whose type is
+    /// given in the class' member type information.
+    pub fn write_member_primitive(&mut self, value: &Primitive) -> Result<(), io::Error> {
+        self.writer.unparse(value)
+    }
+
+    pub fn write_member_typed_primitive(&mut self, value: &Primitive) -> Result<(), io::Error> {
+        self.writer.unparse(&RecordType::MemberTypedPrimitive)?;
+        self.writer.unparse(&value.get_type())?;
+        self.writer.unparse(value)
+    }
+
+    pub fn write_member_reference(&mut self, id: i32) -> Result<(), io::Error> {
+        self.writer.unparse(&Record::MemberReference { id })
+    }
+
+    pub fn write_object_null(&mut self) -> Result<(), io::Error> {
+        self.writer.unparse(&Record::ObjectNull)
+    }
+
+    /// Writes an `ObjectNullMultiple256` record when the count fits in a byte, and an
+    /// `ObjectNullMultiple` record otherwise. A negative count is an `InvalidData` error.
+    pub fn write_object_null_multiple(&mut self, null_count: i32) -> Result<(), io::Error> {
+        if null_count < 0 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        match u8::try_from(null_count) {
+            Ok(null_count) => self
+                .writer
+                .unparse(&Record::ObjectNullMultiple256 { null_count }),
+            Err(_) => self
+                .writer
+                .unparse(&Record::ObjectNullMultiple { null_count }),
+        }
+    }
+
+    /// Writes the closing `MessageEnd` record and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        self.writer.unparse(&Record::MessageEnd)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
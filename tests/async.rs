@@ -0,0 +1,120 @@
+#![cfg(feature = "async")]
+
+use ms_nrbf::{
+    AsyncRecordReader, Class, EncodeOptions, Field, LibraryLayout, Primitive, PrimitiveArray,
+    RecordReader, Stream,
+};
+use tokio::io::{duplex, AsyncWriteExt};
+
+// Small enough that every record larger than a few bytes is split across reads and writes.
+const PIPE_SIZE: usize = 64;
+
+fn stream() -> Stream {
+    let mut root = Class::new(Some("Game, Version=1.0.0.0".to_owned()), "Game.Save");
+    let items = (0..50)
+        .map(|index| {
+            let mut item = Class::new(Some("Game, Version=1.0.0.0".to_owned()), "Game.Item");
+            item.fields.insert(
+                "Name".to_owned(),
+                Field::Primitive(Primitive::String(format!("item {index}"))),
+            );
+            item.fields.insert(
+                "Count".to_owned(),
+                Field::Primitive(Primitive::Int32(index)),
+            );
+            Field::Class(item)
+        })
+        .collect();
+
+    root.fields.insert(
+        "Player".to_owned(),
+        Field::Primitive(Primitive::String("Ann".to_owned())),
+    );
+    root.fields.insert(
+        "Map".to_owned(),
+        Field::PrimitiveArray(PrimitiveArray::Byte((0..=255).cycle().take(4096).collect())),
+    );
+    root.fields
+        .insert("Items".to_owned(), Field::ObjectArray(items));
+
+    Stream { root }
+}
+
+fn encode(stream: &Stream, options: EncodeOptions) -> Vec<u8> {
+    let mut bytes = vec![];
+    stream.encode_with(&mut bytes, options).unwrap();
+    bytes
+}
+
+#[tokio::test]
+async fn streams_round_trip_through_a_small_pipe() {
+    let options = EncodeOptions {
+        library_layout: LibraryLayout::Interleaved,
+        ..EncodeOptions::default()
+    };
+    let stream = stream();
+    let (mut writer, mut reader) = duplex(PIPE_SIZE);
+
+    let (written, decoded) = tokio::join!(
+        stream.encode_with_async(&mut writer, options.clone()),
+        Stream::decode_async(&mut reader)
+    );
+
+    written.unwrap();
+    assert_eq!(
+        encode(&decoded.unwrap(), options.clone()),
+        encode(&stream, options)
+    );
+}
+
+#[tokio::test]
+async fn async_encoding_matches_blocking_encoding() {
+    let stream = stream();
+    let (mut writer, mut reader) = duplex(PIPE_SIZE);
+    let mut bytes = vec![];
+
+    let (written, read) = tokio::join!(
+        async {
+            stream.encode_async(&mut writer).await?;
+            writer.shutdown().await
+        },
+        tokio::io::AsyncReadExt::read_to_end(&mut reader, &mut bytes)
+    );
+
+    written.unwrap();
+    read.unwrap();
+    assert_eq!(bytes, encode(&stream, EncodeOptions::default()));
+}
+
+#[tokio::test]
+async fn async_records_match_blocking_records() {
+    let bytes = encode(&stream(), EncodeOptions::default());
+    let (mut writer, reader) = duplex(PIPE_SIZE);
+    let mut records = AsyncRecordReader::new(reader);
+
+    let (written, read) = tokio::join!(writer.write_all(&bytes), async {
+        let mut read = vec![];
+
+        while let Some(record) = records.next().await {
+            read.push(record.unwrap());
+        }
+
+        read
+    });
+
+    written.unwrap();
+    assert_eq!(
+        read,
+        RecordReader::new(bytes.as_slice())
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn truncated_streams_fail() {
+    let bytes = encode(&stream(), EncodeOptions::default());
+    let mut truncated = &bytes[..bytes.len() / 2];
+
+    assert!(Stream::decode_async(&mut truncated).await.is_err());
+}
@@ -0,0 +1,152 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{encode, object_array_stream};
+use ms_nrbf::{
+    records::Record, BorrowedField, Class, ClassArray, Field, ParseError, Primitive,
+    PrimitiveArray, Stream,
+};
+
+fn save() -> Stream {
+    let mut player = Class::new(Some("Game".to_owned()), "Game.Player");
+    player.fields.insert(
+        "Name".to_owned(),
+        Field::Primitive(Primitive::String("Ann".to_owned())),
+    );
+    player
+        .fields
+        .insert("Level".to_owned(), Field::Primitive(Primitive::Int32(7)));
+
+    let mut root = Class::new(Some("Game".to_owned()), "Game.Save");
+    root.fields
+        .insert("Player".to_owned(), Field::Class(player.clone()));
+    root.fields.insert(
+        "Party".to_owned(),
+        Field::ObjectArray(vec![Field::Class(player.clone()), Field::Null, Field::Null]),
+    );
+    root.fields.insert(
+        "Heroes".to_owned(),
+        Field::ClassArray(ClassArray {
+            library_name: Some("Game".to_owned()),
+            name: "Game.Player".to_owned(),
+            elements: vec![Field::Class(player), Field::Null],
+        }),
+    );
+
+    let mut version = Class::new(None, "System.Version");
+    version
+        .fields
+        .insert("_Major".to_owned(), Field::Primitive(Primitive::Int32(4)));
+    root.fields.insert(
+        "Versions".to_owned(),
+        Field::ClassArray(ClassArray {
+            library_name: None,
+            name: "System.Version".to_owned(),
+            elements: vec![Field::Class(version)],
+        }),
+    );
+    root.fields.insert(
+        "Map".to_owned(),
+        Field::PrimitiveArray(PrimitiveArray::Byte(vec![1, 2, 3])),
+    );
+    root.fields.insert(
+        "Tags".to_owned(),
+        Field::PrimitiveArray(PrimitiveArray::String(vec![Some("a".to_owned()), None])),
+    );
+
+    Stream { root }
+}
+
+#[test]
+fn borrowed_streams_match_owned_streams() {
+    let bytes = encode(&save());
+    let borrowed = Stream::decode_borrowed(&bytes).unwrap().into_owned();
+    let owned = Stream::decode(&mut bytes.as_slice()).unwrap();
+
+    assert_eq!(format!("{borrowed:?}"), format!("{owned:?}"));
+    assert_eq!(encode(&borrowed), encode(&owned));
+}
+
+#[test]
+fn class_arrays_keep_their_element_class() {
+    let bytes = encode(&save());
+    let stream = Stream::decode_borrowed(&bytes).unwrap();
+
+    let BorrowedField::ClassArray(heroes) = &stream.root.fields["Heroes"] else {
+        panic!("the heroes are a class array");
+    };
+    let BorrowedField::ClassArray(versions) = &stream.root.fields["Versions"] else {
+        panic!("the versions are a class array");
+    };
+
+    assert_eq!(heroes.library_name, Some("Game"));
+    assert_eq!(heroes.name, "Game.Player");
+    assert!(matches!(
+        heroes.elements.as_slice(),
+        [BorrowedField::Class(_), BorrowedField::Null]
+    ));
+    assert_eq!(versions.library_name, None);
+    assert_eq!(versions.name, "System.Version");
+}
+
+#[test]
+fn null_runs_must_fit_in_their_array() {
+    let overlong = object_array_stream(2, vec![Record::ObjectNullMultiple256 { null_count: 3 }]);
+    let negative = object_array_stream(2, vec![Record::ObjectNullMultiple { null_count: -1 }]);
+
+    assert!(matches!(
+        Stream::decode_borrowed(&overlong),
+        Err(ParseError::TooManyElements(2))
+    ));
+    assert!(matches!(
+        Stream::decode_borrowed(&negative),
+        Err(ParseError::InvalidCount(-1))
+    ));
+}
+
+#[test]
+fn strings_and_bytes_borrow_from_the_input() {
+    let bytes = encode(&save());
+    let stream = Stream::decode_borrowed(&bytes).unwrap();
+    let input = bytes.as_ptr_range();
+
+    let BorrowedField::Class(player) = &stream.root.fields["Player"] else {
+        panic!("the player is a class");
+    };
+    let BorrowedField::String(name) = player.fields["Name"] else {
+        panic!("the name is a string");
+    };
+    let BorrowedField::Bytes(map) = stream.root.fields["Map"] else {
+        panic!("the map is a byte array");
+    };
+
+    assert_eq!(name, "Ann");
+    assert!(input.contains(&name.as_ptr()));
+    assert_eq!(map, [1, 2, 3]);
+    assert!(input.contains(&map.as_ptr()));
+}
+
+#[test]
+fn references_back_to_an_object_being_resolved_are_null() {
+    let mut node = Class::new(Some("Game".to_owned()), "Game.Node");
+    node.fields.insert("Next".to_owned(), Field::Reference(2));
+
+    let mut root = Class::new(Some("Game".to_owned()), "Game.List");
+    root.fields.insert(
+        "Head".to_owned(),
+        Field::Shared(2, Box::new(Field::Class(node))),
+    );
+    root.fields
+        .insert("Self".to_owned(), Field::Reference(Stream::ROOT_ID));
+
+    let bytes = encode(&Stream { root });
+    let stream = Stream::decode_borrowed(&bytes).unwrap();
+
+    let BorrowedField::Class(head) = &stream.root.fields["Head"] else {
+        panic!("the head is a class");
+    };
+
+    assert!(matches!(head.fields["Next"], BorrowedField::Null));
+    assert!(matches!(stream.root.fields["Self"], BorrowedField::Null));
+}
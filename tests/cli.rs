@@ -0,0 +1,209 @@
+// Runs the `nrbf` binary, which is only built with the `cli` feature.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{class, encode};
+use ms_nrbf::{DecodeOptions, Field, Primitive, PrimitiveArray, Stream};
+use serde_json::Value;
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+// `Held` is referenced again by `Again`, so the dump has both an `$id` and a `$ref`.
+fn save() -> Vec<u8> {
+    let item = class(
+        "Game.Item",
+        vec![("Count", Field::Primitive(Primitive::Int32(3)))],
+    );
+    let root = class(
+        "Game.Save",
+        vec![
+            (
+                "Name",
+                Field::Primitive(Primitive::String("Ann".to_owned())),
+            ),
+            ("Held", Field::Shared(2, Box::new(Field::Class(item)))),
+            ("Again", Field::Reference(2)),
+            (
+                "Scores",
+                Field::PrimitiveArray(PrimitiveArray::Int32(vec![1, 2, 3])),
+            ),
+        ],
+    );
+
+    encode(&Stream { root })
+}
+
+/// Runs `nrbf` with the given arguments, writing `stdin` to it.
+fn nrbf(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_nrbf"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// A path in the temporary directory that no other test, or other run of the tests, uses.
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("nrbf-{}-{name}", std::process::id()))
+}
+
+fn json(output: &Output) -> Value {
+    assert!(output.status.success(), "{output:?}");
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn dump_prints_the_object_graph_with_its_references() {
+    let bytes = save();
+    let options = DecodeOptions {
+        keep_references: true,
+    };
+    let expected = Stream::decode_with(&mut bytes.as_slice(), options)
+        .unwrap()
+        .to_json();
+    let dumped = json(&nrbf(&["dump", "-"], &bytes));
+
+    assert_eq!(dumped, expected);
+    assert_eq!(dumped["Held"]["$id"], 2);
+    assert_eq!(dumped["Again"]["$ref"], 2);
+}
+
+#[test]
+fn dump_prints_records_in_stream_order() {
+    let output = nrbf(&["dump", "--records", "--compact", "-"], &save());
+    let records = json(&output);
+    let types: Vec<&str> = records
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|record| record["record"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(
+        output.stdout.iter().filter(|&&byte| byte == b'\n').count(),
+        1
+    );
+    assert_eq!(
+        types,
+        [
+            "SerializationHeader",
+            "BinaryLibrary",
+            "ClassWithMembersAndTypes",
+            "ClassWithMembersAndTypes",
+            "ArraySinglePrimitive",
+            "MessageEnd",
+        ]
+    );
+}
+
+#[test]
+fn dump_reports_streams_it_cannot_read() {
+    let bytes = save();
+    let output = nrbf(&["dump", "-"], &bytes[..bytes.len() / 2]);
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("nrbf: "));
+}
+
+#[test]
+fn build_encodes_a_dump_back_into_the_same_bytes() {
+    let bytes = save();
+    let stream = temp_path("round-trip.bin");
+    let dump = temp_path("round-trip.json");
+    let built = temp_path("round-trip-built.bin");
+
+    fs::write(&stream, &bytes).unwrap();
+    let output = nrbf(&["dump", stream.to_str().unwrap()], &[]);
+    assert!(output.status.success(), "{output:?}");
+    fs::write(&dump, &output.stdout).unwrap();
+
+    let output = nrbf(
+        &[
+            "build",
+            dump.to_str().unwrap(),
+            "-o",
+            built.to_str().unwrap(),
+        ],
+        &[],
+    );
+    let rebuilt = fs::read(&built);
+
+    for path in [&stream, &dump, &built] {
+        let _ = fs::remove_file(path);
+    }
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(rebuilt.unwrap(), bytes);
+}
+
+#[test]
+fn build_writes_nothing_for_json_it_cannot_encode() {
+    let built = temp_path("invalid-built.bin");
+    // A reference to an object that the dump never defines.
+    let output = nrbf(
+        &["build", "-", "-o", built.to_str().unwrap()],
+        br#"{"$type": "Game.Save", "$library": "Game", "Held": {"$ref": 9}}"#,
+    );
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("nrbf: "));
+    assert!(!built.exists());
+}
+
+fn inspect(args: &[&str], stdin: &[u8]) -> String {
+    let output = nrbf(&[&["inspect"], args, &["-"]].concat(), stdin);
+
+    assert!(output.status.success(), "{output:?}");
+    // Colour is only used on a terminal, so the piped output is plain text.
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn inspect_prints_the_tree_and_statistics() {
+    let bytes = save();
+    let printed = inspect(&[], &bytes);
+
+    for line in [
+        "root: Game.Save #1",
+        "  Name: String #3 = \"Ann\"",
+        "  Held: Game.Item #4",
+        "    Count: 3",
+        "  Again: Game.Item #4 (shown above)",
+        "  Scores: Int32[] #5 (3 items) = [1, 2, 3]",
+        "           2  ClassWithMembersAndTypes",
+        "  Game",
+    ] {
+        assert!(
+            printed.lines().any(|printed| printed == line),
+            "{line:?} in {printed}"
+        );
+    }
+
+    assert!(printed.contains(&format!("Bytes by type ({} in total)", bytes.len())));
+    assert!(printed.contains("Int32[] #5 (3 items)  root.Scores"));
+}
+
+#[test]
+fn inspect_cuts_the_tree_short() {
+    let bytes = save();
+    let shallow = inspect(&["--depth", "1"], &bytes);
+    let few = inspect(&["--items", "2"], &bytes);
+    let stats = inspect(&["--stats"], &bytes);
+
+    assert!(shallow.contains("  Held: Game.Item #4 …\n"));
+    assert!(!shallow.contains("Count"));
+    assert!(few.contains("= [1, 2, …]\n"));
+    assert!(stats.starts_with("Records\n"));
+    assert!(!stats.contains("root:"));
+}
@@ -0,0 +1,82 @@
+//! Fixtures shared by the integration tests.
+// Each test file uses only some of these.
+#![allow(dead_code)]
+
+use ms_nrbf::{
+    records::{
+        ArrayInfo, ArraySingleObject, BinaryType, ClassInfo, MemberTypeInfo, Record,
+        SerializationHeader, SystemClassWithMembersAndTypes,
+    },
+    Class, Field, RecordWriter, Stream,
+};
+
+/// A class in the `Game` library with the given members, in order.
+pub fn class(name: &str, fields: Vec<(&str, Field)>) -> Class {
+    class_in("Game", name, fields)
+}
+
+pub fn class_in(library_name: &str, name: &str, fields: Vec<(&str, Field)>) -> Class {
+    let mut class = Class::new(Some(library_name.to_owned()), name);
+
+    for (name, value) in fields {
+        class.fields.insert(name.to_owned(), value);
+    }
+
+    class
+}
+
+pub fn encode(stream: &Stream) -> Vec<u8> {
+    let mut bytes = vec![];
+    stream.encode(&mut bytes).unwrap();
+    bytes
+}
+
+pub fn header() -> Record {
+    Record::SerializationHeader(SerializationHeader {
+        root_id: 1,
+        header_id: -1,
+        major_version: 1,
+        minor_version: 0,
+    })
+}
+
+pub fn write_records(records: &[Record]) -> Vec<u8> {
+    let mut writer = RecordWriter::new(vec![]);
+
+    for record in records {
+        writer.write_record(record).unwrap();
+    }
+
+    writer.into_inner()
+}
+
+/// A stream whose root holds, as `Items`, an object array of `length` with the given elements.
+pub fn object_array_stream(length: i32, members: Vec<Record>) -> Vec<u8> {
+    let root = SystemClassWithMembersAndTypes {
+        class_info: ClassInfo {
+            object_id: 1,
+            name: "Inventory".to_owned(),
+            member_count: 1,
+            member_names: vec!["Items".to_owned()],
+        },
+        member_type_info: MemberTypeInfo {
+            member_types: vec![BinaryType::ObjectArray],
+            additional_info: vec![],
+        },
+        member_references: vec![Record::MemberReference { id: 2 }],
+    };
+    let items = ArraySingleObject {
+        array_info: ArrayInfo {
+            object_id: 2,
+            length,
+        },
+        members,
+    };
+
+    write_records(&[
+        header(),
+        Record::SystemClassWithMembersAndTypes(root),
+        Record::ArraySingleObject(items),
+        Record::MessageEnd,
+    ])
+}
@@ -0,0 +1,314 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{class, encode, header, object_array_stream, write_records};
+use ms_nrbf::{
+    records::{
+        AdditionalInfo, ArrayInfo, ArraySingleObject, BinaryLibrary, BinaryObjectString,
+        BinaryType, ClassInfo, ClassWithMembers, ClassWithMembersAndTypes, MemberTypeInfo,
+        PrimitiveType, Record, SystemClassWithMembersAndTypes,
+    },
+    DecodeOptions, Field, LazyStream, ParseError, Primitive, RecordReader, Stream,
+};
+use std::io::Cursor;
+
+// `Left` is referenced twice and `Right` once, and both refer to `Bottom`.
+fn diamond() -> Vec<u8> {
+    let bottom = class(
+        "Game.Bottom",
+        vec![("Value", Field::Primitive(Primitive::Int32(4)))],
+    );
+    let left = class(
+        "Game.Left",
+        vec![("Bottom", Field::Shared(3, Box::new(Field::Class(bottom))))],
+    );
+    let right = class("Game.Right", vec![("Bottom", Field::Reference(3))]);
+    let root = class(
+        "Game.Top",
+        vec![
+            ("Left", Field::Shared(2, Box::new(Field::Class(left)))),
+            ("LeftAgain", Field::Reference(2)),
+            ("Right", Field::Class(right)),
+        ],
+    );
+
+    encode(&Stream { root })
+}
+
+fn bottom_value(parent: &Field) -> &Field {
+    let Field::Class(parent) = parent else {
+        panic!("expected a class, found {parent:?}");
+    };
+    let Field::Class(bottom) = &parent.fields["Bottom"] else {
+        panic!(
+            "expected the bottom class, found {:?}",
+            parent.fields["Bottom"]
+        );
+    };
+
+    &bottom.fields["Value"]
+}
+
+#[test]
+fn shared_objects_are_copied_into_every_place_that_refers_to_them() {
+    let stream = Stream::decode(&mut diamond().as_slice()).unwrap();
+
+    for member in ["Left", "LeftAgain", "Right"] {
+        assert!(matches!(
+            bottom_value(&stream.root.fields[member]),
+            Field::Primitive(Primitive::Int32(4))
+        ));
+    }
+}
+
+#[test]
+fn shared_objects_are_kept_once_with_keep_references() {
+    let options = DecodeOptions {
+        keep_references: true,
+    };
+    let stream = Stream::decode_with(&mut diamond().as_slice(), options).unwrap();
+
+    assert!(matches!(stream.root.fields["Left"], Field::Shared(_, _)));
+    assert!(matches!(
+        stream.root.fields["LeftAgain"],
+        Field::Reference(_)
+    ));
+}
+
+#[test]
+fn references_back_to_an_object_being_resolved_are_null() {
+    let root = class(
+        "Game.Loop",
+        vec![("Self", Field::Reference(Stream::ROOT_ID))],
+    );
+    let stream = Stream::decode(&mut encode(&Stream { root }).as_slice()).unwrap();
+
+    assert!(matches!(stream.root.fields["Self"], Field::Null));
+}
+
+// A class member and an object array element that are classes written inline, each with its
+// library written just before it, as BinaryFormatter does.
+fn inline_library_records() -> Vec<Record> {
+    let item = ClassWithMembersAndTypes {
+        class_info: ClassInfo {
+            object_id: 4,
+            name: "Game.Item".to_owned(),
+            member_count: 1,
+            member_names: vec!["Count".to_owned()],
+        },
+        member_type_info: MemberTypeInfo {
+            member_types: vec![BinaryType::Primitive_],
+            additional_info: vec![AdditionalInfo::Primitive(PrimitiveType::Int32)],
+        },
+        library_id: 2,
+        member_references: vec![Record::MemberPrimitiveUnTyped(Primitive::Int32(5))],
+    };
+    let items = ArraySingleObject {
+        array_info: ArrayInfo {
+            object_id: 3,
+            length: 2,
+        },
+        members: vec![
+            Record::BinaryLibrary(BinaryLibrary {
+                library_id: 2,
+                library_name: "Game".to_owned(),
+            }),
+            Record::ClassWithMembersAndTypes(item),
+            Record::ObjectNull,
+        ],
+    };
+    let held = ClassWithMembersAndTypes {
+        class_info: ClassInfo {
+            object_id: 6,
+            name: "Tools.Hammer".to_owned(),
+            member_count: 0,
+            member_names: vec![],
+        },
+        member_type_info: MemberTypeInfo {
+            member_types: vec![],
+            additional_info: vec![],
+        },
+        library_id: 5,
+        member_references: vec![],
+    };
+    let root = SystemClassWithMembersAndTypes {
+        class_info: ClassInfo {
+            object_id: 1,
+            name: "Inventory".to_owned(),
+            member_count: 2,
+            member_names: vec!["Items".to_owned(), "Held".to_owned()],
+        },
+        member_type_info: MemberTypeInfo {
+            member_types: vec![BinaryType::ObjectArray, BinaryType::Object],
+            additional_info: vec![],
+        },
+        member_references: vec![
+            Record::MemberReference { id: 3 },
+            Record::BinaryLibrary(BinaryLibrary {
+                library_id: 5,
+                library_name: "Tools".to_owned(),
+            }),
+            Record::ClassWithMembersAndTypes(held),
+        ],
+    };
+
+    vec![
+        header(),
+        Record::SystemClassWithMembersAndTypes(root),
+        Record::ArraySingleObject(items),
+        Record::MessageEnd,
+    ]
+}
+
+#[test]
+fn libraries_can_be_written_among_members_and_elements() {
+    let records = inline_library_records();
+    let bytes = write_records(&records);
+    let read: Vec<Record> = RecordReader::new(bytes.as_slice())
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(read, records);
+
+    for stream in [
+        Stream::decode(&mut bytes.as_slice()).unwrap(),
+        Stream::decode_borrowed(&bytes).unwrap().into_owned(),
+    ] {
+        let Field::ObjectArray(items) = &stream.root.fields["Items"] else {
+            panic!(
+                "expected an object array, found {:?}",
+                stream.root.fields["Items"]
+            );
+        };
+        let [Field::Class(item), Field::Null] = items.as_slice() else {
+            panic!("expected an item and a null, found {items:?}");
+        };
+
+        assert_eq!(item.library_name.as_deref(), Some("Game"));
+        assert!(matches!(
+            item.fields["Count"],
+            Field::Primitive(Primitive::Int32(5))
+        ));
+        assert!(matches!(
+            &stream.root.fields["Held"],
+            Field::Class(held) if held.library_name.as_deref() == Some("Tools")
+        ));
+    }
+}
+
+#[test]
+fn negative_member_counts_are_rejected() {
+    let class_info = ClassInfo {
+        object_id: 1,
+        name: "Game.Broken".to_owned(),
+        member_count: -1,
+        member_names: vec![],
+    };
+    let classes = [
+        Record::ClassWithMembers(ClassWithMembers {
+            class_info: class_info.clone(),
+            library_id: 2,
+            member_references: vec![],
+        }),
+        Record::SystemClassWithMembersAndTypes(SystemClassWithMembersAndTypes {
+            class_info,
+            member_type_info: MemberTypeInfo {
+                member_types: vec![],
+                additional_info: vec![],
+            },
+            member_references: vec![],
+        }),
+    ];
+
+    for class in classes {
+        let bytes = write_records(&[header(), class, Record::MessageEnd]);
+        let records = RecordReader::new(bytes.as_slice()).collect::<Result<Vec<_>, _>>();
+
+        assert!(matches!(records, Err(ParseError::InvalidCount(-1))));
+        assert!(matches!(
+            Stream::decode(&mut bytes.as_slice()),
+            Err(ParseError::InvalidCount(-1))
+        ));
+        assert!(matches!(
+            Stream::decode_borrowed(&bytes),
+            Err(ParseError::InvalidCount(-1))
+        ));
+        assert!(matches!(
+            LazyStream::open(Cursor::new(&bytes)),
+            Err(ParseError::InvalidCount(-1))
+        ));
+    }
+}
+
+#[test]
+fn repeated_member_names_are_rejected() {
+    let root = SystemClassWithMembersAndTypes {
+        class_info: ClassInfo {
+            object_id: 1,
+            name: "Game.Pair".to_owned(),
+            member_count: 2,
+            member_names: vec!["x".to_owned(), "x".to_owned()],
+        },
+        member_type_info: MemberTypeInfo {
+            member_types: vec![BinaryType::Primitive_, BinaryType::Object],
+            additional_info: vec![AdditionalInfo::Primitive(PrimitiveType::Int32)],
+        },
+        member_references: vec![
+            Record::MemberPrimitiveUnTyped(Primitive::Int32(1)),
+            Record::MemberReference { id: 2 },
+        ],
+    };
+    let bytes = write_records(&[
+        header(),
+        Record::SystemClassWithMembersAndTypes(root),
+        Record::BinaryObjectString(BinaryObjectString {
+            object_id: 2,
+            value: "a".to_owned(),
+        }),
+        Record::MessageEnd,
+    ]);
+    let duplicate =
+        |error: ParseError| matches!(error, ParseError::DuplicateMember(name) if name == "x");
+
+    for keep_references in [false, true] {
+        let options = DecodeOptions { keep_references };
+
+        assert!(duplicate(
+            Stream::decode_with(&mut bytes.as_slice(), options).unwrap_err()
+        ));
+    }
+
+    assert!(duplicate(Stream::decode_borrowed(&bytes).unwrap_err()));
+}
+
+#[test]
+fn null_runs_must_fit_in_their_array() {
+    let fitting = object_array_stream(
+        3,
+        vec![
+            Record::ObjectNullMultiple256 { null_count: 2 },
+            Record::ObjectNull,
+        ],
+    );
+    let overlong = object_array_stream(
+        2,
+        vec![Record::ObjectNullMultiple {
+            null_count: 1 << 30,
+        }],
+    );
+    let negative = object_array_stream(2, vec![Record::ObjectNullMultiple { null_count: -1 }]);
+
+    assert!(matches!(
+        &Stream::decode(&mut fitting.as_slice()).unwrap().root.fields["Items"],
+        Field::ObjectArray(items) if items.len() == 3
+    ));
+    assert!(matches!(
+        Stream::decode(&mut overlong.as_slice()),
+        Err(ParseError::TooManyElements(2))
+    ));
+    assert!(matches!(
+        Stream::decode(&mut negative.as_slice()),
+        Err(ParseError::InvalidCount(-1))
+    ));
+}
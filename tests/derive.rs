@@ -0,0 +1,141 @@
+#![cfg(feature = "derive")]
+
+use ms_nrbf::{
+    Class, ConvertError, Field, NrbfClass, NrbfField, Primitive, PrimitiveArray, Stream,
+};
+
+#[derive(NrbfClass, Debug, PartialEq)]
+#[nrbf(type = "Game.Point", assembly = "Game, Version=1.0.0.0")]
+struct Point {
+    #[nrbf(rename = "<X>k__BackingField")]
+    x: i32,
+    y: i32,
+    label: Option<String>,
+}
+
+#[derive(NrbfClass, Debug, PartialEq)]
+#[nrbf(type = "Game.Path", assembly = "Game, Version=1.0.0.0")]
+struct Path {
+    points: Vec<Point>,
+}
+
+#[derive(NrbfClass, Debug, PartialEq)]
+#[nrbf(type = "Game.Pair", assembly = "Game, Version=1.0.0.0")]
+struct Pair<T> {
+    first: T,
+    second: T,
+}
+
+fn point(x: i32) -> Point {
+    Point {
+        x,
+        y: -x,
+        label: Some(format!("p{x}")),
+    }
+}
+
+#[test]
+fn derived_classes_round_trip() {
+    let path = Path {
+        points: vec![point(1), point(2)],
+    };
+    let mut bytes = vec![];
+    Stream {
+        root: path.into_class(),
+    }
+    .encode(&mut bytes)
+    .unwrap();
+
+    let root = Stream::decode(&mut bytes.as_slice()).unwrap().root;
+
+    assert_eq!(
+        Path::from_class(root).unwrap(),
+        Path {
+            points: vec![point(1), point(2)],
+        }
+    );
+}
+
+#[test]
+fn renamed_members_use_the_dotnet_name() {
+    let class = point(1).into_class();
+
+    assert_eq!(class.library_name.as_deref(), Some("Game, Version=1.0.0.0"));
+    assert_eq!(
+        class.fields.keys().collect::<Vec<_>>(),
+        ["<X>k__BackingField", "y", "label"]
+    );
+}
+
+#[test]
+fn classes_of_another_type_are_rejected() {
+    let mut class = point(1).into_class();
+    class.name = "Game.Vector".to_owned();
+
+    assert!(matches!(
+        Point::from_class(class),
+        Err(ConvertError::TypeMismatch {
+            expected: "Game.Point",
+            found,
+        }) if found == "Game.Vector"
+    ));
+}
+
+#[test]
+fn shared_objects_are_converted() {
+    let mut class = Class::new(Some("Game, Version=1.0.0.0".to_owned()), "Game.Point");
+    class
+        .fields
+        .insert("<X>k__BackingField".to_owned(), 3.into_field());
+    class.fields.insert("y".to_owned(), (-3).into_field());
+    class.fields.insert(
+        "label".to_owned(),
+        Field::Shared(
+            4,
+            Box::new(Field::Primitive(Primitive::String("p3".to_owned()))),
+        ),
+    );
+
+    let shared = Field::Shared(2, Box::new(Field::Class(class)));
+
+    assert_eq!(Point::from_field(shared).unwrap(), point(3));
+}
+
+#[test]
+fn empty_vecs_keep_their_primitive_array_type() {
+    let primitive_array = |field| match field {
+        Field::PrimitiveArray(array) => array,
+        other => panic!("expected a primitive array, found {other:?}"),
+    };
+
+    assert_eq!(
+        primitive_array(Vec::<i32>::new().into_field()),
+        PrimitiveArray::Int32(vec![])
+    );
+    assert_eq!(
+        primitive_array(Vec::<Option<String>>::new().into_field()),
+        PrimitiveArray::String(vec![])
+    );
+    assert!(matches!(
+        Vec::<Option<i32>>::new().into_field(),
+        Field::ObjectArray(elements) if elements.is_empty()
+    ));
+}
+
+#[test]
+fn generic_classes_convert_their_members() {
+    let pair = Pair {
+        first: point(1),
+        second: point(2),
+    };
+    let class = pair.into_class();
+
+    assert!(matches!(class.fields["first"], Field::Class(_)));
+    assert_eq!(
+        Pair::<Point>::from_class(class).unwrap(),
+        Pair {
+            first: point(1),
+            second: point(2),
+        }
+    );
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::class;
+use ms_nrbf::{Change, Field, Primitive, PrimitiveArray, Stream};
+
+fn int(value: i32) -> Field {
+    Field::Primitive(Primitive::Int32(value))
+}
+
+fn save(health: i32, scores: Vec<i16>, fields: Vec<(&str, Field)>) -> Stream {
+    let mut player = class(
+        "Game.Player",
+        vec![
+            ("Health", int(health)),
+            (
+                "Scores",
+                Field::PrimitiveArray(PrimitiveArray::Int16(scores)),
+            ),
+            ("Speed", Field::Primitive(Primitive::Double(f64::NAN))),
+        ],
+    );
+
+    for (name, value) in fields {
+        player.fields.insert(name.to_owned(), value);
+    }
+
+    Stream {
+        root: class("Game.Save", vec![("Player", Field::Class(player))]),
+    }
+}
+
+// Each change as its kind and path.
+fn summary(changes: &[Change]) -> Vec<(&'static str, &str)> {
+    changes
+        .iter()
+        .map(|change| {
+            let kind = match change {
+                Change::Added { .. } => "added",
+                Change::Removed { .. } => "removed",
+                Change::Changed { .. } => "changed",
+            };
+
+            (kind, change.path())
+        })
+        .collect()
+}
+
+#[test]
+fn equal_streams_have_no_changes() {
+    let stream = save(10, vec![1, 2], vec![]);
+
+    assert!(stream.diff(&save(10, vec![1, 2], vec![])).is_empty());
+}
+
+#[test]
+fn changes_are_found_member_by_member() {
+    let old = save(10, vec![1, 2, 3], vec![("Guild", int(1))]);
+    let new = save(7, vec![1, 5], vec![("Title", int(2))]);
+    let changes = old.diff(&new);
+
+    assert_eq!(
+        summary(&changes),
+        [
+            ("changed", "root.Player.Health"),
+            ("changed", "root.Player.Scores[1]"),
+            ("removed", "root.Player.Scores[2]"),
+            ("removed", "root.Player.Guild"),
+            ("added", "root.Player.Title"),
+        ]
+    );
+    assert!(matches!(
+        &changes[0],
+        Change::Changed {
+            old: Field::Primitive(Primitive::Int32(10)),
+            new: Field::Primitive(Primitive::Int32(7)),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn classes_of_different_types_are_changed_whole() {
+    let item = |name| Field::Class(class(name, vec![("Count", int(1))]));
+    let old = save(10, vec![], vec![("Held", item("Game.Sword"))]);
+    let new = save(10, vec![], vec![("Held", item("Game.Shield"))]);
+
+    assert_eq!(summary(&old.diff(&new)), [("changed", "root.Player.Held")]);
+}
+
+#[test]
+fn shared_objects_are_compared_where_they_appear() {
+    let item = || Field::Class(class("Game.Item", vec![("Count", int(1))]));
+    let old = save(10, vec![], vec![("Held", item())]);
+    let new = save(
+        10,
+        vec![],
+        vec![("Held", Field::Shared(2, Box::new(item())))],
+    );
+
+    assert!(old.diff(&new).is_empty());
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::class;
+use ms_nrbf::{DotOptions, Field, Primitive, Stream, StreamedArray};
+
+// The player holds item 2 and refers to it again, refers to itself, and has an object array.
+fn player() -> Stream {
+    let item = class(
+        "Game.Item",
+        vec![(
+            "Name",
+            Field::Primitive(Primitive::String("Sword \"of\" doom".to_owned())),
+        )],
+    );
+
+    Stream {
+        root: class(
+            "Game.Player",
+            vec![
+                ("Level", Field::Primitive(Primitive::Int32(3))),
+                ("Held", Field::Shared(2, Box::new(Field::Class(item)))),
+                ("Again", Field::Reference(2)),
+                ("Me", Field::Reference(1)),
+                (
+                    "Items",
+                    Field::ObjectArray(vec![
+                        Field::Null,
+                        Field::Primitive(Primitive::Int32(1)),
+                        Field::Reference(2),
+                    ]),
+                ),
+            ],
+        ),
+    }
+}
+
+#[test]
+fn objects_are_nodes_and_members_that_hold_them_are_edges() {
+    let options = DotOptions {
+        max_array_items: Some(2),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        player().to_dot_with(options),
+        r#"digraph nrbf {
+    node [shape=box, fontname="monospace"];
+    n1 [label="Game.Player #1\nLevel = 3\l"];
+    n1 -> n2 [label="Held"];
+    n1 -> n2 [label="Again", style=dashed];
+    n1 -> n1 [label="Me", style=dashed, color=red, constraint=false];
+    n1 -> o1 [label="Items"];
+    n2 [label="Game.Item #2\nName = \"Sword \\\"of\\\" doom\"\l"];
+    o1 [label="Object[3]\n[0] = null\l[1] = 1\l… 1 more\l"];
+}
+"#
+    );
+}
+
+#[test]
+fn objects_past_the_maximum_depth_are_named_in_their_parent() {
+    let options = DotOptions {
+        max_depth: Some(0),
+        ..Default::default()
+    };
+    let dot = player().to_dot_with(options);
+
+    assert!(dot.contains(r#"Held → Game.Item …\lItems → Object[] …\l"#));
+    assert!(dot.contains(r##"n2 [label="#2", style=dashed];"##));
+    assert!(!dot.contains("n1 -> o1"));
+}
+
+#[test]
+fn streamed_arrays_are_only_read_as_far_as_they_are_shown() {
+    let values = StreamedArray::from_fn(1_000_000, |index: usize| {
+        assert!(index < 10_000, "the whole array was read");
+        index as i32
+    });
+    let stream = Stream {
+        root: class("Game.Map", vec![("Heights", Field::StreamedArray(values))]),
+    };
+    let options = DotOptions {
+        max_array_items: Some(3),
+        ..Default::default()
+    };
+
+    assert!(stream
+        .to_dot_with(options)
+        .contains(r"Heights = Int32[1000000] {0, 1, 2, … 999997 more}\l"));
+}
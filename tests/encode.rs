@@ -0,0 +1,265 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{class_in, encode};
+use ms_nrbf::{
+    records::{Record, RecordType},
+    Class, EncodeOptions, Field, LibraryLayout, Primitive, PrimitiveArray, RecordReader,
+    RecordWriter, Stream, StreamedArray,
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+fn record_types(stream: &Stream, options: EncodeOptions) -> Vec<RecordType> {
+    let mut bytes = vec![];
+    stream.encode_with(&mut bytes, options).unwrap();
+
+    RecordReader::new(bytes.as_slice())
+        .map(|record| record.unwrap().record_type())
+        .collect()
+}
+
+// The record types of the root class's members, which are read as part of the class record.
+fn root_member_types(stream: &Stream, options: EncodeOptions) -> Vec<RecordType> {
+    let mut bytes = vec![];
+    stream.encode_with(&mut bytes, options).unwrap();
+
+    let root = RecordReader::new(bytes.as_slice())
+        .map(Result::unwrap)
+        .find(|record| record.object_id() == Some(Stream::ROOT_ID))
+        .unwrap();
+
+    root.members().iter().map(Record::record_type).collect()
+}
+
+#[test]
+fn interleaved_libraries_precede_the_class_in_an_object_array() {
+    let item = class_in(
+        "B",
+        "Item",
+        vec![("Count", Field::Primitive(Primitive::Int32(1)))],
+    );
+    let stream = Stream {
+        root: class_in(
+            "A",
+            "Root",
+            vec![("Items", Field::ObjectArray(vec![Field::Class(item)]))],
+        ),
+    };
+    let options = EncodeOptions {
+        library_layout: LibraryLayout::Interleaved,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        record_types(&stream, options),
+        [
+            RecordType::SerializedStreamHeader,
+            RecordType::BinaryLibrary,
+            RecordType::ClassWithMembersAndTypes,
+            RecordType::ArraySingleObject,
+            RecordType::BinaryLibrary,
+            RecordType::ClassWithMembersAndTypes,
+            RecordType::MessageEnd,
+        ]
+    );
+}
+
+#[test]
+fn interleaved_libraries_of_typed_members_precede_their_parent() {
+    let item = class_in(
+        "B",
+        "Item",
+        vec![("Count", Field::Primitive(Primitive::Int32(1)))],
+    );
+    let stream = Stream {
+        root: class_in("A", "Root", vec![("Item", Field::Class(item))]),
+    };
+    let options = EncodeOptions {
+        library_layout: LibraryLayout::Interleaved,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        record_types(&stream, options),
+        [
+            RecordType::SerializedStreamHeader,
+            RecordType::BinaryLibrary,
+            RecordType::BinaryLibrary,
+            RecordType::ClassWithMembersAndTypes,
+            RecordType::ClassWithMembersAndTypes,
+            RecordType::MessageEnd,
+        ]
+    );
+}
+
+#[test]
+fn streamed_array_prefix_stops_the_source_early() {
+    let produced = Arc::new(AtomicUsize::new(0));
+    let counter = produced.clone();
+    let array = StreamedArray::from_fn(1_000_000, move |index| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        index as i32
+    });
+
+    assert_eq!(array.prefix(3), PrimitiveArray::Int32(vec![0, 1, 2]));
+    assert!(produced.load(Ordering::Relaxed) < 10_000);
+    assert_eq!(
+        StreamedArray::from_fn(2, |index| index as u8).prefix(5),
+        PrimitiveArray::Byte(vec![0, 1])
+    );
+}
+
+#[test]
+fn repeated_classes_reuse_their_metadata() {
+    let item = |count| {
+        Field::Class(class_in(
+            "A",
+            "Item",
+            vec![("Count", Field::Primitive(Primitive::Int32(count)))],
+        ))
+    };
+    let stream = Stream {
+        root: class_in(
+            "A",
+            "Root",
+            vec![("Items", Field::ObjectArray(vec![item(1), item(2)]))],
+        ),
+    };
+
+    assert_eq!(
+        record_types(&stream, EncodeOptions::default()),
+        [
+            RecordType::SerializedStreamHeader,
+            RecordType::BinaryLibrary,
+            RecordType::ClassWithMembersAndTypes,
+            RecordType::ArraySingleObject,
+            RecordType::ClassWithMembersAndTypes,
+            RecordType::ClassWithId,
+            RecordType::MessageEnd,
+        ]
+    );
+}
+
+#[test]
+fn system_classes_have_no_library() {
+    let mut root = Class::new(None, "System.Version");
+    root.fields
+        .insert("_Major".to_owned(), Field::Primitive(Primitive::Int32(4)));
+
+    assert_eq!(
+        record_types(&Stream { root }, EncodeOptions::default()),
+        [
+            RecordType::SerializedStreamHeader,
+            RecordType::SystemClassWithMembersAndTypes,
+            RecordType::MessageEnd,
+        ]
+    );
+}
+
+#[test]
+fn omitting_member_types_writes_compact_records() {
+    let stream = Stream {
+        root: class_in(
+            "A",
+            "Root",
+            vec![("Count", Field::Primitive(Primitive::Int32(1)))],
+        ),
+    };
+    let options = EncodeOptions {
+        omit_member_types: true,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        record_types(&stream, options.clone()),
+        [
+            RecordType::SerializedStreamHeader,
+            RecordType::BinaryLibrary,
+            RecordType::ClassWithMembers,
+            RecordType::MessageEnd,
+        ]
+    );
+    assert_eq!(
+        root_member_types(&stream, options),
+        [RecordType::MemberTypedPrimitive]
+    );
+}
+
+#[test]
+fn repeated_strings_are_interned_unless_disabled() {
+    let name = || Field::Primitive(Primitive::String("Ann".to_owned()));
+    let stream = Stream {
+        root: class_in("A", "Root", vec![("First", name()), ("Second", name())]),
+    };
+    let interned = root_member_types(&stream, EncodeOptions::default());
+    let repeated = root_member_types(
+        &stream,
+        EncodeOptions {
+            intern_strings: false,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        interned,
+        [RecordType::BinaryObjectString, RecordType::MemberReference]
+    );
+    assert_eq!(
+        repeated,
+        [
+            RecordType::BinaryObjectString,
+            RecordType::BinaryObjectString
+        ]
+    );
+}
+
+#[test]
+fn records_written_back_give_the_same_bytes() {
+    let stream = Stream {
+        root: class_in(
+            "A",
+            "Root",
+            vec![
+                (
+                    "Name",
+                    Field::Primitive(Primitive::String("Ann".to_owned())),
+                ),
+                (
+                    "Scores",
+                    Field::PrimitiveArray(PrimitiveArray::Int16(vec![1, 2])),
+                ),
+                ("Nothing", Field::Null),
+            ],
+        ),
+    };
+    let mut bytes = vec![];
+    stream.encode(&mut bytes).unwrap();
+
+    let mut writer = RecordWriter::new(vec![]);
+
+    for record in RecordReader::new(bytes.as_slice()) {
+        writer.write_record(&record.unwrap()).unwrap();
+    }
+
+    assert_eq!(writer.into_inner(), bytes);
+}
+
+#[test]
+fn streamed_arrays_are_written_like_primitive_arrays() {
+    let encode_values = |array| {
+        encode(&Stream {
+            root: class_in("A", "Root", vec![("Values", array)]),
+        })
+    };
+    let streamed = StreamedArray::from_fn(10_000, |index| index as i64 * 3);
+    let collected = PrimitiveArray::Int64((0..10_000).map(|index| index * 3).collect());
+
+    assert_eq!(
+        encode_values(Field::StreamedArray(streamed)),
+        encode_values(Field::PrimitiveArray(collected))
+    );
+}
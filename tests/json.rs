@@ -0,0 +1,145 @@
+#![cfg(feature = "json")]
+
+mod common;
+
+use common::{class, encode};
+use ms_nrbf::{
+    ClassArray, DateTime, DateTimeKind, DecodeOptions, Field, Primitive, PrimitiveArray, Stream,
+    TimeSpan,
+};
+use serde_json::json;
+
+fn player() -> Stream {
+    let item = class(
+        "Game.Item",
+        vec![("Count", Field::Primitive(Primitive::UInt16(2)))],
+    );
+
+    Stream {
+        root: class(
+            "Game.Player",
+            vec![
+                (
+                    "Name",
+                    Field::Primitive(Primitive::String("Ann".to_owned())),
+                ),
+                ("Level", Field::Primitive(Primitive::Int32(3))),
+                ("Speed", Field::Primitive(Primitive::Single(0.1))),
+                ("Rank", Field::Primitive(Primitive::Char('A'))),
+                (
+                    "Gold",
+                    Field::Primitive(Primitive::Decimal("1.50".to_owned())),
+                ),
+                (
+                    "Played",
+                    Field::Primitive(Primitive::TimeSpan(TimeSpan::new(600_000_000))),
+                ),
+                (
+                    "Created",
+                    Field::Primitive(Primitive::DateTime(DateTime::new(
+                        638_000_000_000_000_000,
+                        DateTimeKind::Utc,
+                    ))),
+                ),
+                ("Health", Field::Primitive(Primitive::Double(f64::NAN))),
+                (
+                    "Scores",
+                    Field::PrimitiveArray(PrimitiveArray::Int16(vec![1, 2, 3])),
+                ),
+                (
+                    "Items",
+                    Field::ClassArray(ClassArray {
+                        library_name: Some("Game".to_owned()),
+                        name: "Game.Item".to_owned(),
+                        elements: vec![Field::Class(item), Field::Null],
+                    }),
+                ),
+                ("$Secret", Field::Null),
+            ],
+        ),
+    }
+}
+
+#[test]
+fn streams_are_written_as_tagged_json() {
+    assert_eq!(
+        player().to_json(),
+        json!({
+            "$type": "Game.Player",
+            "$library": "Game",
+            "Name": "Ann",
+            "Level": 3,
+            "Speed": { "$Single": 0.1 },
+            "Rank": { "$Char": "A" },
+            "Gold": { "$Decimal": "1.50" },
+            "Played": { "$TimeSpan": 600_000_000 },
+            "Created": { "$DateTime": { "ticks": 638_000_000_000_000_000_i64, "kind": "Utc" } },
+            "Health": { "$Double": "NaN" },
+            "Scores": { "$array": "Int16", "$items": [1, 2, 3] },
+            "Items": {
+                "$array": "Game.Item",
+                "$library": "Game",
+                "$items": [{ "$type": "Game.Item", "$library": "Game", "Count": { "$UInt16": 2 } }, null],
+            },
+            "$$Secret": null,
+        })
+    );
+}
+
+#[test]
+fn json_round_trips_to_the_same_bytes() {
+    let stream = player();
+    let json = stream.to_json();
+
+    assert_eq!(encode(&Stream::from_json(&json).unwrap()), encode(&stream));
+}
+
+#[test]
+fn shared_objects_round_trip_through_ids_and_references() {
+    let item = class(
+        "Game.Item",
+        vec![("Count", Field::Primitive(Primitive::Int32(1)))],
+    );
+    let stream = Stream {
+        root: class(
+            "Game.Player",
+            vec![
+                ("Held", Field::Shared(2, Box::new(Field::Class(item)))),
+                ("Favourite", Field::Reference(2)),
+            ],
+        ),
+    };
+    let options = DecodeOptions {
+        keep_references: true,
+    };
+    let decoded = Stream::decode_with(&mut encode(&stream).as_slice(), options).unwrap();
+    let json = decoded.to_json();
+
+    assert_eq!(json["Held"]["$id"], json["Favourite"]["$ref"]);
+    assert_eq!(encode(&Stream::from_json(&json).unwrap()), encode(&decoded));
+}
+
+#[test]
+fn errors_name_the_member_they_were_found_at() {
+    let error = Stream::from_json(&json!({
+        "$type": "Game.Player",
+        "Scores": { "$array": "Int16", "$items": [1, "two"] },
+    }))
+    .unwrap_err();
+
+    assert_eq!(error.path, "root.Scores[1]");
+    assert!(Stream::from_json(&json!([1, 2])).is_err());
+}
+
+#[test]
+fn date_time_ticks_must_fit_in_a_stream() {
+    for ticks in [-1, i64::MAX] {
+        let error = Stream::from_json(&json!({
+            "$type": "Game.Player",
+            "Created": { "$DateTime": { "ticks": ticks, "kind": "Utc" } },
+        }))
+        .unwrap_err();
+
+        assert_eq!(error.path, "root.Created");
+    }
+}
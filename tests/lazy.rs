@@ -0,0 +1,242 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{class, header, object_array_stream, write_records};
+use ms_nrbf::{
+    records::{
+        AdditionalInfo, ArrayInfo, ArraySingleObject, ArraySinglePrimitive, BinaryArray,
+        BinaryArrayType, BinaryType, ClassInfo, MemberTypeInfo, PrimitiveType, Record,
+        SystemClassWithMembersAndTypes,
+    },
+    Field, LazyField, LazyObject, LazyStream, ParseError, Primitive, PrimitiveArray, Stream,
+};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+fn world() -> Cursor<Vec<u8>> {
+    let player = class(
+        "Game.Player",
+        vec![
+            (
+                "Name",
+                Field::Primitive(Primitive::String("Ann".to_owned())),
+            ),
+            ("Level", Field::Primitive(Primitive::Int32(3))),
+        ],
+    );
+    let root = class(
+        "Game.World",
+        vec![
+            ("Seed", Field::Primitive(Primitive::Int64(42))),
+            ("Player", Field::Class(player)),
+            (
+                "Heights",
+                Field::PrimitiveArray(PrimitiveArray::Single(vec![0.5; 10_000])),
+            ),
+        ],
+    );
+    let mut bytes = vec![];
+    Stream { root }.encode(&mut bytes).unwrap();
+
+    Cursor::new(bytes)
+}
+
+fn object_id(field: &LazyField) -> i32 {
+    match field {
+        LazyField::Object(id) => *id,
+        other => panic!("expected an object, found {other:?}"),
+    }
+}
+
+#[test]
+fn the_root_leaves_its_objects_unread() {
+    let mut stream = LazyStream::open(world()).unwrap();
+    let root = stream.root().unwrap();
+
+    assert_eq!(root.name, "Game.World");
+    assert!(matches!(
+        root.fields["Seed"],
+        LazyField::Primitive(Primitive::Int64(42))
+    ));
+    assert!(matches!(root.fields["Player"], LazyField::Object(_)));
+    assert!(matches!(root.fields["Heights"], LazyField::Object(_)));
+}
+
+#[test]
+fn objects_are_read_by_id() {
+    let mut stream = LazyStream::open(world()).unwrap();
+    let root = stream.root().unwrap();
+
+    let LazyObject::Class(player) = stream.get(object_id(&root.fields["Player"])).unwrap() else {
+        panic!("expected the player class");
+    };
+    assert_eq!(player.name, "Game.Player");
+    assert!(matches!(
+        player.fields["Level"],
+        LazyField::Primitive(Primitive::Int32(3))
+    ));
+    assert!(matches!(
+        &player.fields["Name"],
+        LazyField::Primitive(Primitive::String(name)) if name == "Ann"
+    ));
+
+    let heights = object_id(&root.fields["Heights"]);
+    let LazyObject::PrimitiveArray(PrimitiveArray::Single(values)) = stream.get(heights).unwrap()
+    else {
+        panic!("expected the array of heights");
+    };
+    assert_eq!(values.len(), 10_000);
+
+    let range = stream.range(heights).unwrap();
+    assert!(range.end - range.start >= 40_000);
+}
+
+#[test]
+fn loading_an_object_reads_everything_it_refers_to() {
+    let mut stream = LazyStream::open(world()).unwrap();
+    let player = object_id(&stream.root().unwrap().fields["Player"]);
+
+    let Field::Class(player) = stream.load(player).unwrap() else {
+        panic!("expected the player class");
+    };
+    assert!(matches!(
+        &player.fields["Name"],
+        Field::Primitive(Primitive::String(name)) if name == "Ann"
+    ));
+}
+
+#[test]
+fn unknown_ids_are_missing_objects() {
+    let mut stream = LazyStream::open(world()).unwrap();
+
+    assert!(stream.range(1000).is_none());
+    assert!(matches!(
+        stream.get(1000),
+        Err(ParseError::MissingObject(1000))
+    ));
+}
+
+// Counts the bytes read, but not the ones seeked over.
+struct Counting {
+    inner: Cursor<Vec<u8>>,
+    read: usize,
+}
+
+impl Read for Counting {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read += read;
+        Ok(read)
+    }
+}
+
+impl Seek for Counting {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+// A root whose `Grid` is a 100 by 100 `BinaryArray` written inline, and whose `Rows` hold an
+// `ArraySinglePrimitive` written inline among the elements.
+fn inline_arrays() -> Vec<u8> {
+    let grid = BinaryArray {
+        object_id: 2,
+        binary_array_type: BinaryArrayType::Rectangular,
+        rank: 2,
+        lengths: vec![100, 100],
+        lower_bounds: None,
+        binary_type: BinaryType::Primitive_,
+        additional_info: Some(AdditionalInfo::Primitive(PrimitiveType::Int32)),
+        members: (0..10_000)
+            .map(|value| Record::MemberPrimitiveUnTyped(Primitive::Int32(value)))
+            .collect(),
+    };
+    let row = ArraySinglePrimitive {
+        array_info: ArrayInfo {
+            object_id: 4,
+            length: 10_000,
+        },
+        members: PrimitiveArray::Double(vec![0.5; 10_000]),
+    };
+    let rows = ArraySingleObject {
+        array_info: ArrayInfo {
+            object_id: 3,
+            length: 2,
+        },
+        members: vec![Record::ArraySinglePrimitive(row), Record::ObjectNull],
+    };
+    let root = SystemClassWithMembersAndTypes {
+        class_info: ClassInfo {
+            object_id: 1,
+            name: "Terrain".to_owned(),
+            member_count: 2,
+            member_names: vec!["Grid".to_owned(), "Rows".to_owned()],
+        },
+        member_type_info: MemberTypeInfo {
+            member_types: vec![BinaryType::Object, BinaryType::Object],
+            additional_info: vec![],
+        },
+        member_references: vec![Record::BinaryArray(grid), Record::ArraySingleObject(rows)],
+    };
+
+    write_records(&[
+        header(),
+        Record::SystemClassWithMembersAndTypes(root),
+        Record::MessageEnd,
+    ])
+}
+
+#[test]
+fn arrays_inside_other_records_are_skipped_until_needed() {
+    let bytes = inline_arrays();
+    let length = bytes.len();
+    let scanned = LazyStream::open(Counting {
+        inner: Cursor::new(bytes),
+        read: 0,
+    })
+    .unwrap()
+    .into_inner();
+
+    assert!(scanned.read < length / 10);
+
+    let mut stream = LazyStream::open(Cursor::new(inline_arrays())).unwrap();
+
+    let LazyObject::ObjectArray(grid) = stream.get(2).unwrap() else {
+        panic!("expected the grid");
+    };
+    assert_eq!(grid.len(), 10_000);
+    assert!(matches!(
+        grid[9_999],
+        LazyField::Primitive(Primitive::Int32(9_999))
+    ));
+
+    let LazyObject::PrimitiveArray(PrimitiveArray::Double(row)) = stream.get(4).unwrap() else {
+        panic!("expected the row");
+    };
+    assert_eq!(row.len(), 10_000);
+}
+
+#[test]
+fn null_runs_must_fit_in_their_array() {
+    let bytes = object_array_stream(
+        2,
+        vec![Record::ObjectNullMultiple {
+            null_count: 1 << 30,
+        }],
+    );
+
+    assert!(matches!(
+        LazyStream::open(Cursor::new(bytes)),
+        Err(ParseError::TooManyElements(2))
+    ));
+}
+
+#[test]
+fn streams_without_a_header_have_no_root() {
+    let bytes = write_records(&[Record::MessageEnd]);
+
+    assert!(matches!(
+        LazyStream::open(Cursor::new(bytes)),
+        Err(ParseError::MissingHeader)
+    ));
+}
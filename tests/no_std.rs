@@ -0,0 +1,124 @@
+// Built with `--no-default-features`, where the crate is `no_std` and records are read and
+// written through its own `io` traits.
+#![cfg(not(feature = "std"))]
+
+use ms_nrbf::{
+    io::{self, ErrorKind, Read, Write},
+    records::{BinaryLibrary, Record},
+    ParseError, Primitive, RecordReader, RecordWriter,
+};
+
+// Hands out one byte per call, like a device that is read a register at a time.
+struct ByteByByte<'a> {
+    bytes: &'a [u8],
+}
+
+impl Read for ByteByByte<'_> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), io::Error> {
+        for byte in buf {
+            let (first, rest) = self
+                .bytes
+                .split_first()
+                .ok_or(io::Error::from(ErrorKind::UnexpectedEof))?;
+
+            *byte = *first;
+            self.bytes = rest;
+        }
+
+        Ok(())
+    }
+}
+
+// A sink with room for a fixed number of bytes.
+struct Fixed<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Write for Fixed<N> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), io::Error> {
+        let end = self.len + buf.len();
+
+        if end > N {
+            return Err(ErrorKind::WriteZero.into());
+        }
+
+        self.bytes[self.len..end].copy_from_slice(buf);
+        self.len = end;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+fn records() -> Vec<u8> {
+    let mut writer = RecordWriter::new(Vec::new());
+
+    writer.write_header(1).unwrap();
+    writer.write_library(2, "Game").unwrap();
+    writer
+        .write_member_typed_primitive(&Primitive::Int32(7))
+        .unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn records_are_read_through_the_crate_reader_trait() {
+    let bytes = records();
+    let read: Vec<Record> = RecordReader::new(ByteByByte { bytes: &bytes })
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(
+        read[1],
+        Record::BinaryLibrary(BinaryLibrary {
+            library_id: 2,
+            library_name: "Game".into(),
+        })
+    );
+    assert_eq!(
+        read[2],
+        Record::MemberTypedPrimitive {
+            value: Primitive::Int32(7)
+        }
+    );
+    assert_eq!(read.last(), Some(&Record::MessageEnd));
+}
+
+#[test]
+fn records_are_written_through_the_crate_writer_trait() {
+    let bytes = records();
+    let mut writer = RecordWriter::new(Fixed::<64> {
+        bytes: [0; 64],
+        len: 0,
+    });
+
+    for record in RecordReader::new(bytes.as_slice()) {
+        writer.write_record(&record.unwrap()).unwrap();
+    }
+
+    let written = writer.into_inner();
+
+    assert_eq!(&written.bytes[..written.len], bytes.as_slice());
+}
+
+#[test]
+fn reader_and_writer_errors_keep_their_kind() {
+    let bytes = records();
+    let truncated = RecordReader::new(&bytes[..bytes.len() / 2]).find_map(Result::err);
+    let mut full = RecordWriter::new(Fixed::<4> {
+        bytes: [0; 4],
+        len: 0,
+    });
+
+    assert!(matches!(
+        truncated,
+        Some(ParseError::IoError(error)) if error.kind() == ErrorKind::UnexpectedEof
+    ));
+    assert_eq!(
+        full.write_header(1).unwrap_err().kind(),
+        ErrorKind::WriteZero
+    );
+}
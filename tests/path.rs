@@ -0,0 +1,126 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::class;
+use ms_nrbf::{Field, PathError, Primitive, PrimitiveArray, Stream};
+
+fn item(name: &str, count: u16) -> Field {
+    Field::Class(class(
+        "Game.Item",
+        vec![
+            ("Name", Field::Primitive(Primitive::String(name.to_owned()))),
+            ("Count", Field::Primitive(Primitive::UInt16(count))),
+        ],
+    ))
+}
+
+fn player() -> Stream {
+    let player = class(
+        "Game.Player",
+        vec![
+            ("Level", Field::Primitive(Primitive::Int32(3))),
+            (
+                "Inventory",
+                Field::ObjectArray(vec![item("Apple", 3), item("Pear", 5)]),
+            ),
+            (
+                "Scores",
+                Field::PrimitiveArray(PrimitiveArray::Int16(vec![1, 2, 3])),
+            ),
+        ],
+    );
+
+    Stream {
+        root: class("Game.Save", vec![("Player", Field::Class(player))]),
+    }
+}
+
+fn primitive(stream: &Stream, path: &str) -> Primitive {
+    match stream.get(path).unwrap().into_owned() {
+        Field::Primitive(primitive) => primitive,
+        other => panic!("{path}: expected a primitive, found {other:?}"),
+    }
+}
+
+#[test]
+fn paths_lead_into_members_and_elements() {
+    let stream = player();
+
+    assert_eq!(primitive(&stream, "Player.Level"), Primitive::Int32(3));
+    assert_eq!(
+        primitive(&stream, "Player.Inventory[1].Name"),
+        Primitive::String("Pear".to_owned())
+    );
+    assert_eq!(primitive(&stream, "Player.Scores[2]"), Primitive::Int16(3));
+}
+
+#[test]
+fn paths_that_lead_nowhere_say_where_they_stopped() {
+    let stream = player();
+
+    assert!(matches!(
+        stream.get("Player.Health"),
+        Err(PathError::MissingMember { path }) if path == "Player.Health"
+    ));
+    assert!(matches!(
+        stream.get("Player.Inventory[7].Name"),
+        Err(PathError::OutOfBounds { path, length: 2 }) if path == "Player.Inventory[7]"
+    ));
+    assert!(matches!(
+        stream.get("Player.Level.Value"),
+        Err(PathError::TypeMismatch { path, .. }) if path == "Player.Level"
+    ));
+    assert!(matches!(
+        stream.get("Player..Level"),
+        Err(PathError::Invalid(_))
+    ));
+}
+
+#[test]
+fn set_keeps_the_type_of_the_primitive_it_replaces() {
+    let mut stream = player();
+
+    let old = stream.set("Player.Inventory[0].Count", 7).unwrap();
+
+    assert!(matches!(old, Field::Primitive(Primitive::UInt16(3))));
+    assert_eq!(
+        primitive(&stream, "Player.Inventory[0].Count"),
+        Primitive::UInt16(7)
+    );
+
+    stream.set("Player.Scores[0]", 10).unwrap();
+    assert_eq!(primitive(&stream, "Player.Scores[0]"), Primitive::Int16(10));
+}
+
+#[test]
+fn set_rejects_values_that_do_not_fit() {
+    let mut stream = player();
+
+    assert!(matches!(
+        stream.set("Player.Inventory[0].Count", -1),
+        Err(PathError::TypeMismatch { .. })
+    ));
+    assert!(matches!(
+        stream.set("Player.Level", "high".to_owned()),
+        Err(PathError::TypeMismatch { .. })
+    ));
+    assert_eq!(primitive(&stream, "Player.Level"), Primitive::Int32(3));
+}
+
+#[test]
+fn remove_shifts_later_elements_down() {
+    let mut stream = player();
+
+    let removed = stream.remove("Player.Inventory[0]").unwrap();
+
+    assert!(matches!(removed, Field::Class(class) if class.name == "Game.Item"));
+    assert_eq!(
+        primitive(&stream, "Player.Inventory[0].Name"),
+        Primitive::String("Pear".to_owned())
+    );
+    assert!(stream.get("Player.Inventory[1]").is_err());
+
+    stream.remove("Player.Level").unwrap();
+    assert!(stream.get("Player.Level").is_err());
+}
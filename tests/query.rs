@@ -0,0 +1,102 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::class;
+use ms_nrbf::{Field, Primitive, PrimitiveArray, Query, Stream};
+
+fn item(name: &str, durability: i32) -> Field {
+    Field::Class(class(
+        "Game.Item",
+        vec![
+            ("Name", Field::Primitive(Primitive::String(name.to_owned()))),
+            ("Durability", Field::Primitive(Primitive::Int32(durability))),
+        ],
+    ))
+}
+
+fn world() -> Stream {
+    let player = class(
+        "Game.Player",
+        vec![
+            (
+                "Name",
+                Field::Primitive(Primitive::String("Ann".to_owned())),
+            ),
+            ("Hidden", Field::Primitive(Primitive::Boolean(false))),
+            ("Guild", Field::Null),
+            (
+                "Items",
+                Field::ObjectArray(vec![item("Sword", 5), item("Shield", 40), item("Bow", 9)]),
+            ),
+            (
+                "Scores",
+                Field::PrimitiveArray(PrimitiveArray::Int32(vec![7, 12])),
+            ),
+        ],
+    );
+
+    Stream {
+        root: class("Game.World", vec![("Player", Field::Class(player))]),
+    }
+}
+
+fn paths(stream: &Stream, query: &str) -> Vec<String> {
+    stream
+        .query(&query.parse().unwrap())
+        .into_iter()
+        .map(|found| found.path)
+        .collect()
+}
+
+#[test]
+fn classes_are_matched_by_type_and_condition() {
+    let stream = world();
+
+    assert_eq!(
+        paths(&stream, "Game.Item where Durability < 10"),
+        ["Player.Items[0]", "Player.Items[2]"]
+    );
+    assert_eq!(
+        paths(
+            &stream,
+            "Game.* where Name startswith \"S\" and not Durability > 10"
+        ),
+        ["Player.Items[0]"]
+    );
+    assert_eq!(
+        paths(
+            &stream,
+            "Game.Player where Hidden == false and not Guild and Items[1].Durability == 40"
+        ),
+        ["Player"]
+    );
+}
+
+#[test]
+fn primitives_arrays_and_null_are_matched_too() {
+    let stream = world();
+
+    assert_eq!(
+        paths(&stream, "String where . contains \"w\""),
+        ["Player.Items[0].Name", "Player.Items[2].Name"]
+    );
+    assert_eq!(paths(&stream, "Int32[]"), ["Player.Scores"]);
+    assert_eq!(paths(&stream, "null"), ["Player.Guild"]);
+
+    let matches = stream.query(&Query::parse("Int32 where . > 10").unwrap());
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[1].path, "Player.Scores[1]");
+    assert!(matches!(
+        matches[1].value.as_ref(),
+        Field::Primitive(Primitive::Int32(12))
+    ));
+}
+
+#[test]
+fn errors_give_the_position_of_the_problem() {
+    let error = Query::parse("Game.Item where Durability <").unwrap_err();
+
+    assert_eq!(error.position, 28);
+    assert!(Query::parse("Game.Item where (Durability < 3").is_err());
+}
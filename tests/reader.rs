@@ -0,0 +1,162 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{class, encode};
+use ms_nrbf::{
+    records::{
+        AdditionalInfo, ArrayInfo, ArraySinglePrimitive, BinaryArray, BinaryArrayType, BinaryType,
+        ClassWithId, PrimitiveType, Record, RecordType,
+    },
+    Field, ParseError, Primitive, PrimitiveArray, RecordReader, RecordWriter, Stream,
+};
+use std::io::ErrorKind;
+
+fn stream_bytes() -> Vec<u8> {
+    encode(&Stream {
+        root: class(
+            "Game.Player",
+            vec![(
+                "Name",
+                Field::Primitive(Primitive::String("Ann".to_owned())),
+            )],
+        ),
+    })
+}
+
+#[test]
+fn records_are_read_one_at_a_time() {
+    let types: Vec<RecordType> = RecordReader::new(stream_bytes().as_slice())
+        .map(|record| record.unwrap().record_type())
+        .collect();
+
+    assert_eq!(
+        types,
+        [
+            RecordType::SerializedStreamHeader,
+            RecordType::BinaryLibrary,
+            RecordType::ClassWithMembersAndTypes,
+            RecordType::MessageEnd,
+        ]
+    );
+}
+
+#[test]
+fn unknown_record_types_are_rejected() {
+    let mut reader = RecordReader::new([0xFF].as_slice());
+
+    assert!(matches!(
+        reader.next(),
+        Some(Err(ParseError::InvalidRecordType(_)))
+    ));
+}
+
+#[test]
+fn truncated_streams_end_with_an_error() {
+    let bytes = stream_bytes();
+    let records: Vec<_> = RecordReader::new(&bytes[..bytes.len() - 4]).collect();
+
+    assert!(records[..records.len() - 1].iter().all(Result::is_ok));
+    assert!(matches!(
+        records.last(),
+        Some(Err(ParseError::IoError(error))) if error.kind() == ErrorKind::UnexpectedEof
+    ));
+}
+
+#[test]
+fn reading_stops_after_the_first_error() {
+    let mut reader = RecordReader::new([0xFF, 0x0B].as_slice());
+
+    assert!(matches!(reader.next(), Some(Err(_))));
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn classes_with_id_need_their_metadata() {
+    let mut writer = RecordWriter::new(vec![]);
+    writer
+        .write_record(&Record::ClassWithId(ClassWithId {
+            object_id: 2,
+            metadata_id: 1,
+            member_references: vec![],
+        }))
+        .unwrap();
+    let bytes = writer.into_inner();
+
+    assert!(matches!(
+        RecordReader::new(bytes.as_slice()).next(),
+        Some(Err(ParseError::MissingMetadata(1)))
+    ));
+}
+
+#[test]
+fn method_names_must_be_strings() {
+    let mut bytes = vec![RecordType::MethodCall as u8];
+    // NoArgs and NoContext, then a method name tagged as an object rather than a string.
+    bytes.extend_from_slice(&0x11u32.to_le_bytes());
+    bytes.extend_from_slice(&[BinaryType::Object as u8, 3, b'R', b'u', b'n']);
+
+    assert!(matches!(
+        RecordReader::new(bytes.as_slice()).next(),
+        Some(Err(ParseError::UnexpectedBinaryType(BinaryType::Object)))
+    ));
+}
+
+fn read_one(record: Record) -> Result<Record, ParseError> {
+    let mut writer = RecordWriter::new(vec![]);
+    writer.write_record(&record).unwrap();
+
+    RecordReader::new(writer.into_inner().as_slice()).read_record()
+}
+
+fn primitive_array(length: i32, members: PrimitiveArray) -> Record {
+    Record::ArraySinglePrimitive(ArraySinglePrimitive {
+        array_info: ArrayInfo {
+            object_id: 1,
+            length,
+        },
+        members,
+    })
+}
+
+#[test]
+fn primitive_arrays_are_read_whole() {
+    let array = primitive_array(3, PrimitiveArray::Double(vec![0.5, -1.0, 2.25]));
+
+    assert_eq!(read_one(array.clone()).unwrap(), array);
+}
+
+#[test]
+fn primitive_arrays_shorter_than_their_length_are_errors() {
+    // Far more than is written, which must not be allocated before the bytes arrive.
+    let array = primitive_array(1 << 30, PrimitiveArray::Int64(vec![7]));
+
+    assert!(matches!(
+        read_one(array),
+        Err(ParseError::IoError(error)) if error.kind() == ErrorKind::UnexpectedEof
+    ));
+}
+
+#[test]
+fn negative_array_lengths_are_rejected() {
+    let single = primitive_array(-1, PrimitiveArray::Int32(vec![]));
+    let binary = Record::BinaryArray(BinaryArray {
+        object_id: 1,
+        binary_array_type: BinaryArrayType::Rectangular,
+        rank: 2,
+        lengths: vec![2, -3],
+        lower_bounds: None,
+        binary_type: BinaryType::Primitive_,
+        additional_info: Some(AdditionalInfo::Primitive(PrimitiveType::Int32)),
+        members: vec![],
+    });
+
+    assert!(matches!(
+        read_one(single),
+        Err(ParseError::InvalidCount(-1))
+    ));
+    assert!(matches!(
+        read_one(binary),
+        Err(ParseError::InvalidCount(-3))
+    ));
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "serde")]
+
+use ms_nrbf::{
+    from_stream,
+    records::{AdditionalInfo, BinaryArrayType, Record},
+    to_stream, Naming, RecordReader,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MSCORLIB: &str =
+    "mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Inventory {
+    owner: String,
+    counts: BTreeMap<String, i32>,
+}
+
+fn inventory() -> Inventory {
+    Inventory {
+        owner: "Ann".to_owned(),
+        counts: BTreeMap::from([("apple".to_owned(), 3), ("pear".to_owned(), 5)]),
+    }
+}
+
+fn records(value: &impl Serialize) -> Vec<Record> {
+    let mut bytes = vec![];
+    to_stream(value, &Naming::default())
+        .unwrap()
+        .encode(&mut bytes)
+        .unwrap();
+
+    RecordReader::new(bytes.as_slice())
+        .map(Result::unwrap)
+        .collect()
+}
+
+// The name, and member names, of every class record, in stream order.
+fn classes(records: &[Record]) -> Vec<(String, Vec<String>)> {
+    records
+        .iter()
+        .filter_map(|record| match record {
+            Record::SystemClassWithMembersAndTypes(class) => Some((
+                class.class_info.name.clone(),
+                class.class_info.member_names.clone(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn maps_are_written_as_a_dotnet_dictionary() {
+    let records = records(&inventory());
+    let arguments = format!("[[System.String, {MSCORLIB}],[System.Int32, {MSCORLIB}]]");
+    let pair = format!("System.Collections.Generic.KeyValuePair`2{arguments}");
+
+    assert_eq!(
+        classes(&records),
+        [
+            (
+                "Inventory".to_owned(),
+                vec!["owner".to_owned(), "counts".to_owned()]
+            ),
+            (
+                format!("System.Collections.Generic.Dictionary`2{arguments}"),
+                vec![
+                    "Version".to_owned(),
+                    "Comparer".to_owned(),
+                    "HashSize".to_owned(),
+                    "KeyValuePairs".to_owned(),
+                ]
+            ),
+            (
+                format!(
+                    "System.Collections.Generic.GenericEqualityComparer`1[[System.String, {MSCORLIB}]]"
+                ),
+                vec![]
+            ),
+            (pair.clone(), vec!["key".to_owned(), "value".to_owned()]),
+        ]
+    );
+
+    let array = records
+        .iter()
+        .find_map(|record| match record {
+            Record::BinaryArray(array) => Some(array),
+            _ => None,
+        })
+        .expect("the pairs are a typed array");
+
+    assert_eq!(array.binary_array_type, BinaryArrayType::Single);
+    assert_eq!(array.lengths, [2]);
+    assert_eq!(
+        array.additional_info,
+        Some(AdditionalInfo::SystemClass(pair))
+    );
+}
+
+#[test]
+fn empty_maps_have_no_pairs() {
+    let empty = Inventory {
+        owner: "Ann".to_owned(),
+        counts: BTreeMap::new(),
+    };
+    let records = records(&empty);
+
+    assert_eq!(classes(&records)[1].1, ["Version", "Comparer", "HashSize"]);
+    assert!(!records
+        .iter()
+        .any(|record| matches!(record, Record::BinaryArray(_))));
+}
+
+#[test]
+fn maps_round_trip() {
+    let stream = to_stream(&inventory(), &Naming::default()).unwrap();
+    let mut bytes = vec![];
+    stream.encode(&mut bytes).unwrap();
+
+    let decoded = ms_nrbf::Stream::decode(&mut bytes.as_slice()).unwrap();
+
+    assert_eq!(from_stream::<Inventory>(decoded).unwrap(), inventory());
+}
@@ -0,0 +1,37 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use ms_nrbf::{DateTime, DateTimeKind, OutOfRangeError, TimeSpan};
+
+fn new_year(year: i32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap()
+}
+
+#[test]
+fn times_convert_to_ticks_and_back() {
+    let naive = new_year(2024) + Duration::nanoseconds(1_234_567_800);
+    let time = DateTime::try_from(naive).unwrap();
+
+    // .NET's `new DateTime(2024, 1, 1).Ticks`, plus the fraction of a second.
+    assert_eq!(
+        time,
+        DateTime::new(638_396_640_012_345_678, DateTimeKind::Unspecified)
+    );
+    assert_eq!(time.to_naive(), Some(naive));
+    assert_eq!(
+        TimeSpan::try_from(Duration::milliseconds(-1_500)),
+        Ok(TimeSpan::new(-15_000_000))
+    );
+}
+
+#[test]
+fn times_out_of_range_are_errors() {
+    assert_eq!(DateTime::try_from(new_year(30_000)), Err(OutOfRangeError));
+    assert_eq!(DateTime::try_from(new_year(20_000)), Err(OutOfRangeError));
+    assert_eq!(DateTime::try_from(new_year(0)), Err(OutOfRangeError));
+    assert!(DateTime::try_from(new_year(14_000)).is_ok());
+    assert_eq!(
+        TimeSpan::try_from(Duration::days(30_000 * 366)),
+        Err(OutOfRangeError)
+    );
+}
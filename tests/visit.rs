@@ -0,0 +1,138 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::class;
+use ms_nrbf::{Class, Field, Primitive, PrimitiveArray, Stream, VisitContext, Visitor, VisitorMut};
+
+fn world() -> Stream {
+    let item = class(
+        "Game.Item",
+        vec![("Count", Field::Primitive(Primitive::Int32(2)))],
+    );
+    let player = class(
+        "Game.Player",
+        vec![
+            ("Level", Field::Primitive(Primitive::Int32(3))),
+            ("Held", Field::Shared(2, Box::new(Field::Class(item)))),
+            (
+                "Items",
+                Field::ObjectArray(vec![Field::Reference(2), Field::Null]),
+            ),
+            (
+                "Scores",
+                Field::PrimitiveArray(PrimitiveArray::Int16(vec![4, 5])),
+            ),
+        ],
+    );
+
+    Stream {
+        root: class("Game.World", vec![("Player", Field::Class(player))]),
+    }
+}
+
+// Every callback as a line naming it and where it was called.
+#[derive(Default)]
+struct Trace {
+    lines: Vec<String>,
+    skip: Option<&'static str>,
+}
+
+impl Visitor for Trace {
+    fn enter_class(&mut self, class: &Class, context: &VisitContext) -> bool {
+        self.lines.push(format!(
+            "class {} at {:?} depth {} id {:?}",
+            class.name, context.path, context.depth, context.id
+        ));
+        self.skip != Some(class.name.as_str())
+    }
+
+    fn enter_array(&mut self, _: &Field, context: &VisitContext) -> bool {
+        self.lines.push(format!("array at {}", context.path));
+        true
+    }
+
+    fn primitive(&mut self, value: &Primitive, context: &VisitContext) {
+        self.lines.push(format!("{} = {value}", context.path));
+    }
+
+    fn reference(&mut self, id: i32, context: &VisitContext) {
+        self.lines.push(format!("{} -> #{id}", context.path));
+    }
+
+    fn null(&mut self, context: &VisitContext) {
+        self.lines.push(format!("{} = null", context.path));
+    }
+}
+
+#[test]
+fn values_are_visited_depth_first_in_stream_order() {
+    let mut trace = Trace::default();
+    world().walk(&mut trace);
+
+    assert_eq!(
+        trace.lines,
+        [
+            "class Game.World at \"\" depth 0 id Some(1)",
+            "class Game.Player at \"Player\" depth 1 id None",
+            "Player.Level = 3",
+            "class Game.Item at \"Player.Held\" depth 2 id Some(2)",
+            "Player.Held.Count = 2",
+            "array at Player.Items",
+            "Player.Items[0] -> #2",
+            "Player.Items[1] = null",
+            "array at Player.Scores",
+            "Player.Scores[0] = 4",
+            "Player.Scores[1] = 5",
+        ]
+    );
+}
+
+#[test]
+fn entering_a_class_can_skip_its_members() {
+    let mut trace = Trace {
+        skip: Some("Game.Player"),
+        ..Default::default()
+    };
+    world().walk(&mut trace);
+
+    assert_eq!(
+        trace.lines,
+        [
+            "class Game.World at \"\" depth 0 id Some(1)",
+            "class Game.Player at \"Player\" depth 1 id None",
+        ]
+    );
+}
+
+// Doubles every `Int32` and `Int16`.
+struct Double;
+
+impl VisitorMut for Double {
+    fn primitive(&mut self, value: &mut Primitive, _: &VisitContext) {
+        match value {
+            Primitive::Int32(value) => *value *= 2,
+            Primitive::Int16(value) => *value *= 2,
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn mutable_visitors_change_values_in_place() {
+    let mut stream = world();
+    stream.walk_mut(&mut Double);
+
+    assert!(matches!(
+        stream.get("Player.Level").unwrap().as_ref(),
+        Field::Primitive(Primitive::Int32(6))
+    ));
+    assert!(matches!(
+        stream.get("Player.Held.Count").unwrap().as_ref(),
+        Field::Primitive(Primitive::Int32(4))
+    ));
+    assert!(matches!(
+        stream.get("Player.Scores").unwrap().as_ref(),
+        Field::PrimitiveArray(PrimitiveArray::Int16(scores)) if scores == &[8, 10]
+    ));
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "std")]
+
+use ms_nrbf::{
+    records::{
+        AdditionalInfo, BinaryType, ClassInfo, ClassWithMembersAndTypes, MemberTypeInfo,
+        PrimitiveType, Record, RecordType,
+    },
+    Primitive, RecordReader, RecordWriter, StreamedArray,
+};
+use std::io::ErrorKind;
+
+#[test]
+fn records_written_piece_by_piece_read_back_whole() {
+    let mut class = ClassWithMembersAndTypes {
+        class_info: ClassInfo {
+            object_id: 1,
+            name: "Game.Player".to_owned(),
+            member_count: 2,
+            member_names: vec!["Level".to_owned(), "Guild".to_owned()],
+        },
+        member_type_info: MemberTypeInfo {
+            member_types: vec![BinaryType::Primitive_, BinaryType::Object],
+            additional_info: vec![AdditionalInfo::Primitive(PrimitiveType::Int32)],
+        },
+        library_id: 2,
+        member_references: vec![],
+    };
+    let mut writer = RecordWriter::new(vec![]);
+
+    writer.write_header(1).unwrap();
+    writer.write_library(2, "Game").unwrap();
+    writer.write_class_with_members_and_types(&class).unwrap();
+    writer.write_member_primitive(&Primitive::Int32(7)).unwrap();
+    writer.write_object_null().unwrap();
+
+    let bytes = writer.finish().unwrap();
+    let records: Vec<Record> = RecordReader::new(bytes.as_slice())
+        .map(Result::unwrap)
+        .collect();
+
+    class.member_references = vec![
+        Record::MemberPrimitiveUnTyped(Primitive::Int32(7)),
+        Record::ObjectNull,
+    ];
+
+    assert_eq!(records[2], Record::ClassWithMembersAndTypes(class));
+    assert_eq!(records.last(), Some(&Record::MessageEnd));
+}
+
+#[test]
+fn null_runs_use_the_smallest_record() {
+    let first_byte = |null_count| {
+        let mut writer = RecordWriter::new(vec![]);
+        writer.write_object_null_multiple(null_count).unwrap();
+        writer.into_inner()[0]
+    };
+
+    assert_eq!(first_byte(3), RecordType::ObjectNullMultiple256 as u8);
+    assert_eq!(first_byte(300), RecordType::ObjectNullMultiple as u8);
+}
+
+#[test]
+fn negative_null_runs_are_rejected() {
+    let mut writer = RecordWriter::new(vec![]);
+
+    assert_eq!(
+        writer.write_object_null_multiple(-1).unwrap_err().kind(),
+        ErrorKind::InvalidData
+    );
+    assert!(writer.into_inner().is_empty());
+}
+
+#[test]
+fn write_errors_are_returned() {
+    let mut buffer = [0; 4];
+    let mut writer = RecordWriter::new(buffer.as_mut_slice());
+
+    assert_eq!(
+        writer.write_header(1).unwrap_err().kind(),
+        ErrorKind::WriteZero
+    );
+}
+
+#[test]
+fn streamed_arrays_must_produce_their_length() {
+    let short = StreamedArray::from_iter(5, || 0..3i32);
+    let long = StreamedArray::from_iter(2, || 0..3i32);
+
+    for array in [short, long] {
+        let mut writer = RecordWriter::new(vec![]);
+
+        assert_eq!(
+            writer.write_streamed_array(1, &array).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}